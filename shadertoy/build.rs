@@ -19,20 +19,52 @@ fn main() {
         .unwrap();
     let mut buf = BufWriter::new(file);
 
-    buf.write_all(b"pub const SHADERS: &'static [(&'static str, &'static str)] = &[")
+    buf.write_all(b"pub const SHADERS: &[(&str, &str)] = &[")
         .unwrap();
-    for path in glob::glob("src/assets/toy/*.wgsl").expect("Cannot glob") {
+    // The toys in subdirectories are named after their path, e.g. `fractal/mandelbrot.wgsl`,
+    // the directory is their category.
+    for path in glob::glob("src/assets/toy/**/*.wgsl").expect("Cannot glob") {
         let path = path.unwrap();
-        let filename = path.file_name().unwrap().to_string_lossy();
+        let name = path
+            .strip_prefix("src/assets/toy")
+            .unwrap()
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
         write!(
             buf,
-            "(\"{filename}\", std::include_str!(\"{root_dir}/src/assets/toy/{filename}\")),"
+            "(\"{name}\", std::include_str!(\"{root_dir}/src/assets/toy/{name}\")),"
+        )
+        .unwrap();
+    }
+    buf.write_all(b"];\n").unwrap();
+
+    // The files the toys can load in their channels, named after their path in `channel`.
+    buf.write_all(b"pub const CHANNEL_FILES: &[(&str, &[u8])] = &[")
+        .unwrap();
+    for path in glob::glob("src/assets/channel/**/*").expect("Cannot glob") {
+        let path = path.unwrap();
+        if !path.is_file() {
+            continue;
+        }
+        let name = path
+            .strip_prefix("src/assets/channel")
+            .unwrap()
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        write!(
+            buf,
+            "(\"{name}\", std::include_bytes!(\"{root_dir}/src/assets/channel/{name}\")),"
         )
         .unwrap();
     }
     buf.write_all(b"];").unwrap();
     buf.flush().unwrap();
 
-    println!("cargo:rerun-if-changed=src/assets/toy/*.wgsl");
+    println!("cargo:rerun-if-changed=src/assets/toy");
+    println!("cargo:rerun-if-changed=src/assets/channel");
     println!("cargo:rerun-if-changed=build.rs");
 }
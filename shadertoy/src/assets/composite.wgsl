@@ -0,0 +1,29 @@
+struct Composite {
+    opacity: f32,
+    // 0: alpha, 1: add, 2: multiply, 3: screen.
+    mode: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> c: Composite;
+
+// The layer, blended over the toy by the blend state of the pipeline.
+@group(1) @binding(0)
+var layer: texture_2d<f32>;
+
+@fragment
+fn main(@builtin(position) frag_coord: vec4<f32>) -> @location(0) vec4<f32> {
+    let color = textureLoad(layer, vec2<i32>(frag_coord.xy), 0);
+
+    switch c.mode {
+        case 0u: {
+            return vec4(color.rgb, color.a * c.opacity);
+        }
+        case 2u: {
+            return vec4(mix(vec3(1.), color.rgb, c.opacity), 1.);
+        }
+        default: {
+            return vec4(color.rgb * c.opacity, 1.);
+        }
+    }
+}
@@ -5,19 +5,152 @@ struct Uniforms {
     // mouse_release: vec2<f32>,
     resolution: vec2<f32>,
     time: f32,
+    // Applied to the fragment coordinate before calling `main_image`,
+    // used to render a frame in several tiles.
+    scale: f32,
+    offset: vec2<f32>,
+    // Pan, zoom and rotation of the view, moved with the mouse and the keyboard.
+    // The center is a double-float: `xy` is the high part and `zw` the low part.
+    view_zoom: f32,
+    one: f32,
+    view_center: vec4<f32>,
+    view_angle: f32,
+    // Number of vertices drawn by a vertex toy.
+    vertex_count: f32,
+    // Size of the render target, only differs from `resolution` for the tiles.
+    target_resolution: vec2<f32>,
+    // Custom parameters, set with OSC, read them with `param`.
+    params: array<vec4<f32>, 2>,
 };
 
 @group(0) @binding(0)
 var<uniform> u: Uniforms;
 
+// Returned by `vertex_main` in the vertex toys, the colors of the primitives are added together.
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
 fn texture(texture: texture_2d<f32>, spl: sampler, uv: vec2<f32>) -> vec4<f32> {
     return textureSample(texture, spl, vec2(uv.x, 1.0 - uv.y));
 }
 
+// Sampler of the channels, declared with `// channel0: <source>` to `// channel3: <source>`.
+// `image`, `sequence` and `video` channels are `texture_2d<f32>`, `cubemap` are
+// `texture_cube<f32>` and `volume` are `texture_3d<f32>`.
+@group(1) @binding(0)
+var channel_sampler: sampler;
+
+// The color of a cubemap channel in a direction, e.g. a reflected ray.
+fn sample_cubemap(channel: texture_cube<f32>, direction: vec3<f32>) -> vec4<f32> {
+    return textureSampleLevel(channel, channel_sampler, direction, 0.);
+}
+
+// The value of a volume channel at a position, it repeats every unit.
+fn sample_volume(channel: texture_3d<f32>, position: vec3<f32>) -> vec4<f32> {
+    return textureSampleLevel(channel, channel_sampler, position, 0.);
+}
+
+// The custom parameter `index`, from 0 to 7.
+fn param(index: i32) -> f32 {
+    return u.params[index / 4][index % 4];
+}
+
+// x and y: [-1, 1] when the view isn't moved.
 fn uv_centered(coord: vec2<f32>) -> vec2<f32> {
-    return ((coord / u.resolution) - .5) * 2.;
+    return uv_ratioed(coord) * vec2(u.resolution.y / u.resolution.x, 1.);
 }
 
+// y: [-1, 1] and x keep the aspect ratio when the view isn't moved.
 fn uv_ratioed(coord: vec2<f32>) -> vec2<f32> {
+    return view_transform(screen_uv_ratioed(coord));
+}
+
+// Same as `uv_ratioed`, ignoring the view.
+fn screen_uv_ratioed(coord: vec2<f32>) -> vec2<f32> {
     return (coord * 2. - u.resolution.xy) / u.resolution.y;
 }
+
+// Pan, zoom and rotate a position on the screen.
+fn view_transform(uv: vec2<f32>) -> vec2<f32> {
+    return u.view_center.xy + view_rotate(uv) / u.view_zoom;
+}
+
+fn view_rotate(uv: vec2<f32>) -> vec2<f32> {
+    let c = cos(u.view_angle);
+    let s = sin(u.view_angle);
+    return vec2(uv.x * c - uv.y * s, uv.x * s + uv.y * c);
+}
+
+// Double-float arithmetic, a value is the unevaluated sum `hi + lo` stored in a `vec2<f32>`.
+// It gives about twice the precision of a `f32`: the pixels stay evenly spaced until the view is
+// about 1e-11 wide, then they drift apart and merge into blocks.
+//
+// The shader compilers are free to simplify `(a + b) - a` to `b` and to fuse a product with the
+// following addition, both lose the error terms. Multiplying by `df64_one()`, unknown at compile
+// time, prevent these optimizations.
+
+struct Df64Vec2 {
+    x: vec2<f32>,
+    y: vec2<f32>,
+};
+
+// Same as `uv_ratioed` with double-float precision, use it for deep zooms.
+fn uv_ratioed_df64(coord: vec2<f32>) -> Df64Vec2 {
+    let delta = view_rotate(screen_uv_ratioed(coord)) / u.view_zoom;
+    return Df64Vec2(
+        df64_add(vec2(u.view_center.x, u.view_center.z), df64(delta.x)),
+        df64_add(vec2(u.view_center.y, u.view_center.w), df64(delta.y)),
+    );
+}
+
+fn df64_one() -> f32 {
+    return u.one;
+}
+
+fn df64(a: f32) -> vec2<f32> {
+    return vec2(a, 0.);
+}
+
+fn df64_two_sum(a: f32, b: f32) -> vec2<f32> {
+    let s = a + b;
+    let v = s * df64_one() - a;
+    return vec2(s, (a - (s - v)) + (b - v));
+}
+
+fn df64_quick_two_sum(a: f32, b: f32) -> vec2<f32> {
+    let s = a + b;
+    return vec2(s, b - (s * df64_one() - a));
+}
+
+// Split a `f32` in two halves of 12 bits, so their products are exact.
+fn df64_split(a: f32) -> vec2<f32> {
+    let t = 4097. * a * df64_one();
+    let hi = t - (t * df64_one() - a);
+    return vec2(hi, a - hi);
+}
+
+fn df64_two_prod(a: f32, b: f32) -> vec2<f32> {
+    let p = a * b * df64_one();
+    let sa = df64_split(a);
+    let sb = df64_split(b);
+    let e = ((sa.x * sb.x - p) + sa.x * sb.y + sa.y * sb.x) + sa.y * sb.y;
+    return vec2(p, e);
+}
+
+fn df64_add(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    let s = df64_two_sum(a.x, b.x);
+    let t = df64_two_sum(a.y, b.y);
+    let r = df64_quick_two_sum(s.x, s.y + t.x);
+    return df64_quick_two_sum(r.x, r.y + t.y);
+}
+
+fn df64_sub(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    return df64_add(a, -b);
+}
+
+fn df64_mul(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    let p = df64_two_prod(a.x, b.x);
+    return df64_quick_two_sum(p.x, p.y + (a.x * b.y + a.y * b.x));
+}
@@ -1,4 +1,4 @@
 @fragment
 fn main(@builtin(position) frag_coord: vec4<f32>) -> @location(0) vec4<f32> {
-    return main_image(frag_coord);
+    return main_image(vec4(frag_coord.xy * u.scale + u.offset, frag_coord.zw));
 }
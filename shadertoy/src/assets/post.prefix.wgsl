@@ -0,0 +1,33 @@
+// Start of every post-processing effect, an effect defines
+// `fn effect(coord: vec2<f32>) -> vec4<f32>` returning the color at `coord`, in window pixels.
+
+// The first fields of the uniforms of the toys, shared with them.
+struct Uniforms {
+    resolution: vec2<f32>,
+    time: f32,
+    // Map the pixels of the render target to the window, used to render a frame in several tiles.
+    scale: f32,
+    offset: vec2<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> u: Uniforms;
+
+// The output of the toy or of the previous effect.
+@group(1) @binding(0)
+var input_texture: texture_2d<f32>;
+
+@group(1) @binding(1)
+var input_sampler: sampler;
+
+// The input color at a position in window pixels, interpolated between the pixels.
+fn input_at(coord: vec2<f32>) -> vec4<f32> {
+    let target_coord = (coord - u.offset) / u.scale;
+    let size = vec2<f32>(textureDimensions(input_texture));
+    return textureSampleLevel(input_texture, input_sampler, target_coord / size, 0.);
+}
+
+@fragment
+fn main(@builtin(position) frag_coord: vec4<f32>) -> @location(0) vec4<f32> {
+    return effect(frag_coord.xy * u.scale + u.offset);
+}
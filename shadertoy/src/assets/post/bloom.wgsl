@@ -0,0 +1,32 @@
+// The bright parts glow over their neighbours.
+const BLOOM_TAPS: i32 = 48;
+const BLOOM_RADIUS: f32 = 24.;
+const BLOOM_THRESHOLD: f32 = .8;
+const BLOOM_STRENGTH: f32 = .8;
+const GOLDEN_ANGLE: f32 = 2.39996;
+
+// http://www.iryoku.com/next-generation-post-processing-in-call-of-duty-advanced-warfare
+fn interleaved_gradient_noise(coord: vec2<f32>) -> f32 {
+    return fract(52.9829189 * fract(dot(coord, vec2(.06711056, .00583715))));
+}
+
+fn effect(coord: vec2<f32>) -> vec4<f32> {
+    let color = input_at(coord);
+
+    // Spiral of taps weighted toward the center, rotated per pixel to hide the gaps.
+    // Every tap is compressed, so a single very bright pixel doesn't light up its surroundings.
+    let rotation = interleaved_gradient_noise(coord) * 6.2831853;
+    var glow = vec3(0.);
+    var total = 0.;
+    for (var i = 0; i < BLOOM_TAPS; i++) {
+        let t = (f32(i) + .5) / f32(BLOOM_TAPS);
+        let angle = f32(i) * GOLDEN_ANGLE + rotation;
+        let offset = sqrt(t) * BLOOM_RADIUS * vec2(cos(angle), sin(angle));
+        let bright = max(min(input_at(coord + offset).rgb, vec3(1e4)) - BLOOM_THRESHOLD, vec3(0.));
+        let weight = 1. - t;
+        glow += bright / (1. + max(bright.r, max(bright.g, bright.b))) * weight;
+        total += weight;
+    }
+
+    return vec4(color.rgb + glow / total * BLOOM_STRENGTH, color.a);
+}
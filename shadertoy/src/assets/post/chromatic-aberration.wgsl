@@ -0,0 +1,8 @@
+// The red and blue components drift apart toward the edges, like through a cheap lens.
+const ABERRATION: f32 = .008;
+
+fn effect(coord: vec2<f32>) -> vec4<f32> {
+    let offset = (coord - u.resolution / 2.) * ABERRATION;
+    let color = input_at(coord);
+    return vec4(input_at(coord + offset).r, color.g, input_at(coord - offset).b, color.a);
+}
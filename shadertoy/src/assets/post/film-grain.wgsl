@@ -0,0 +1,21 @@
+// Noise changing 24 times per second, stronger in the bright parts.
+const GRAIN_FPS: f32 = 24.;
+const GRAIN_STRENGTH: f32 = .12;
+
+// https://www.pcg-random.org/, see "Hash Functions for GPU Rendering".
+fn grain_hash(seed: vec3<u32>) -> f32 {
+    var v = seed * 1664525u + 1013904223u;
+    v.x += v.y * v.z;
+    v.y += v.z * v.x;
+    v.z += v.x * v.y;
+    v ^= v >> vec3(16u);
+    v.x += v.y * v.z;
+    return f32(v.x) / 4294967295.;
+}
+
+fn effect(coord: vec2<f32>) -> vec4<f32> {
+    let color = input_at(coord);
+    let frame = u32(max(u.time, 0.) * GRAIN_FPS);
+    let grain = grain_hash(vec3(vec2<u32>(coord), frame)) - .5;
+    return vec4(max(color.rgb * (1. + grain * GRAIN_STRENGTH * 2.) + grain * .01, vec3(0.)), color.a);
+}
@@ -0,0 +1,7 @@
+// Big square pixels, the color of their center.
+const PIXEL_SIZE: f32 = 8.;
+
+fn effect(coord: vec2<f32>) -> vec4<f32> {
+    let center = (floor(coord / PIXEL_SIZE) + .5) * PIXEL_SIZE;
+    return input_at(center);
+}
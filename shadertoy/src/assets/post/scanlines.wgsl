@@ -0,0 +1,14 @@
+// The dark lines between the rows and the phosphor stripes of a CRT screen.
+const SCANLINE_PERIOD: f32 = 3.;
+const SCANLINE_DEPTH: f32 = .4;
+
+fn effect(coord: vec2<f32>) -> vec4<f32> {
+    let color = input_at(coord);
+
+    let line = .5 + .5 * cos(coord.y * 6.2831853 / SCANLINE_PERIOD);
+    var stripes = vec3(.8);
+    // The coordinates are negative in the margins of the screenshots.
+    stripes[(i32(floor(coord.x)) % 3 + 3) % 3] = 1.2;
+
+    return vec4(color.rgb * (1. - SCANLINE_DEPTH * (1. - line)) * stripes, color.a);
+}
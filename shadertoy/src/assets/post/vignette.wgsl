@@ -0,0 +1,7 @@
+// Darken the corners.
+fn effect(coord: vec2<f32>) -> vec4<f32> {
+    let color = input_at(coord);
+    let uv = coord / u.resolution;
+    let vignette = pow(16. * uv.x * uv.y * (1. - uv.x) * (1. - uv.y), .25);
+    return vec4(color.rgb * vignette, color.a);
+}
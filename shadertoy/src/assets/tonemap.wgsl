@@ -0,0 +1,75 @@
+struct Tonemap {
+    // 0: none, 1: reinhard, 2: aces.
+    mode: u32,
+    // 1 when the surface doesn't encode to sRGB by itself.
+    encode_srgb: u32,
+    // Size of the HDR texture relative to the output.
+    render_scale: f32,
+};
+
+@group(0) @binding(0)
+var hdr: texture_2d<f32>;
+
+@group(0) @binding(1)
+var<uniform> t: Tonemap;
+
+fn reinhard(color: vec3<f32>) -> vec3<f32> {
+    return color / (1. + color);
+}
+
+// https://knarkowicz.wordpress.com/2016/01/06/aces-filmic-tone-mapping-curve/
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return (color * (a * color + b)) / (color * (c * color + d) + e);
+}
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3(1. / 2.4)) - 0.055;
+    return select(high, low, color <= vec3(0.0031308));
+}
+
+// The HDR color at a pixel of the output, interpolated when the texture has another size.
+fn hdr_at(coord: vec2<f32>) -> vec4<f32> {
+    if t.render_scale == 1. {
+        return textureLoad(hdr, vec2<i32>(coord), 0);
+    }
+
+    let position = coord * t.render_scale - .5;
+    let last = vec2<i32>(textureDimensions(hdr)) - 1;
+    let texel = vec2<i32>(floor(position));
+    let f = fract(position);
+    let c00 = textureLoad(hdr, clamp(texel, vec2(0), last), 0);
+    let c10 = textureLoad(hdr, clamp(texel + vec2(1, 0), vec2(0), last), 0);
+    let c01 = textureLoad(hdr, clamp(texel + vec2(0, 1), vec2(0), last), 0);
+    let c11 = textureLoad(hdr, clamp(texel + vec2(1, 1), vec2(0), last), 0);
+    return mix(mix(c00, c10, f.x), mix(c01, c11, f.x), f.y);
+}
+
+@fragment
+fn main(@builtin(position) frag_coord: vec4<f32>) -> @location(0) vec4<f32> {
+    let hdr_color = hdr_at(frag_coord.xy);
+    var color = max(hdr_color.rgb, vec3(0.));
+
+    switch t.mode {
+        case 1u: {
+            color = reinhard(color);
+        }
+        case 2u: {
+            color = aces(color);
+        }
+        default: {}
+    }
+
+    color = clamp(color, vec3(0.), vec3(1.));
+
+    if t.encode_srgb == 1u {
+        color = linear_to_srgb(color);
+    }
+
+    return vec4(color, hdr_color.a);
+}
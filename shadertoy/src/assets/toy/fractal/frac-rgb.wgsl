@@ -1,3 +1,4 @@
+// tags: fractal, palette, color
 // https://iquilezles.org/articles/palettes/
 fn palette(time: f32) -> vec3<f32> {
     let a = vec3(.5, .5, .5);
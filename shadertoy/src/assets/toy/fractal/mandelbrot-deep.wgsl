@@ -0,0 +1,50 @@
+// tags: fractal, mandelbrot, zoom
+// The mandelbrot set computed with double-floats, drag and scroll to zoom until the view is about
+// 1e-11 wide.
+
+const MAX_ITER: i32 = 2000;
+
+fn hsv2rgb(c: vec3<f32>) -> vec3<f32> {
+    let K = vec4(1.0, 2.0 / 3.0, 1.0 / 3.0, 3.0);
+    let p = abs(fract(c.xxx + K.xyz) * 6.0 - K.www);
+    return c.z * mix(K.xxx, clamp(p - K.xxx, vec3(0.0), vec3(1.0)), c.y);
+}
+
+// Same framing as `mandelbrot.wgsl`: y: [-1.12, 1.12] centered on x = -0.765.
+fn scale_in_mandelbrot(xy: vec2<f32>) -> Df64Vec2 {
+    let p = uv_ratioed_df64(xy);
+    return Df64Vec2(
+        df64_add(df64_mul(p.x, df64(1.12)), df64(-.765)),
+        df64_mul(p.y, df64(1.12)),
+    );
+}
+
+fn mandelbrot(c: Df64Vec2) -> f32 {
+    var x = df64(0.);
+    var y = df64(0.);
+    var x2 = df64(0.);
+    var y2 = df64(0.);
+    var i = 0;
+
+    for (; x2.x + y2.x <= 4. && i < MAX_ITER; i++) {
+        let xy = df64_mul(x, y);
+        x = df64_add(df64_sub(x2, y2), c.x);
+        y = df64_add(df64_add(xy, xy), c.y);
+        x2 = df64_mul(x, x);
+        y2 = df64_mul(y, y);
+    }
+
+    if i == MAX_ITER {
+        return -1.;
+    }
+    return f32(i) + 1. - log(log2(x2.x + y2.x));
+}
+
+fn main_image(frag_coord: vec4<f32>) -> vec4<f32> {
+    let iter = mandelbrot(scale_in_mandelbrot(frag_coord.xy));
+
+    if iter < 0. {
+        return vec4(.0, .0, .0, 1.);
+    }
+    return vec4(hsv2rgb(vec3(fract(iter / 64.), .8, 1.)), 1.);
+}
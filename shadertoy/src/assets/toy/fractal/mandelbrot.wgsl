@@ -1,8 +1,8 @@
+// tags: fractal, mandelbrot
 // The mandelbrot set is contain between x: [-2, 0.47] and y: [-1.12, 1.12]
-// Here we scale the pixel coord (where x: [0, screen size] and y: [0, screen size]) to the mandelbrot coord
+// Here we scale the uv (where y: [-1, 1] when the view isn't moved) to the mandelbrot coord
 fn scale_in_mandelbrot(xy: vec2<f32>) -> vec2<f32> {
-    let max_xy: vec2<f32> = u.resolution.xy;
-    return (xy / max_xy) * vec2(2.47, 2.24) + vec2(-2., -1.12);
+    return uv_ratioed(xy) * 1.12 + vec2(-.765, 0.);
 }
 
 fn above_threshold(uv_pow2: vec2<f32>) -> bool {
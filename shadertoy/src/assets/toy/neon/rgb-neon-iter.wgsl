@@ -1,3 +1,8 @@
+// tags: neon, palette, fractal
+// variant: three-layers
+// variant: five-layers LAYERS=5
+override LAYERS: i32 = 3;
+
 // https://iquilezles.org/articles/palettes/
 fn palette(time: f32) -> vec3<f32> {
     let a = vec3(.5, .5, .5);
@@ -15,7 +20,7 @@ fn main_image(frag_coord: vec4<f32>) -> vec4<f32> {
 
     var final_color = vec3(.0);
 
-    for (var i = 0; i < 3; i++) {
+    for (var i = 0; i < LAYERS; i++) {
         uv = fract(uv * 2.) - .5;
         let l = length(uv) * exp(-d0);
         let col = palette(d0 + f32(i) * .4 + time * .4);
@@ -25,6 +30,5 @@ fn main_image(frag_coord: vec4<f32>) -> vec4<f32> {
         final_color += col * pow(0.01 / d, 1.2);
     }
 
-
     return vec4(final_color, 1.);
 }
@@ -0,0 +1,40 @@
+// tags: raymarch, clouds, volume
+// channel0: volume noise 64
+fn density(p: vec3<f32>) -> f32 {
+    let wind = vec3(u.time * .03, 0., u.time * .01);
+    let noise = sample_volume(channel0, p * .2 + wind);
+    let shape = noise.r * .6 + noise.g * .3 + noise.b * .1;
+    // Thin the clouds out at the top and the bottom of their layer.
+    let layer = 1. - abs(p.y - 1.5);
+    return max(shape * layer - .42, 0.) * 8.;
+}
+
+fn main_image(frag_coord: vec4<f32>) -> vec4<f32> {
+    let uv = uv_ratioed(frag_coord.xy) * vec2(1., -1.);
+    let direction = normalize(vec3(uv.x, uv.y + .5, -1.5));
+    let sky = mix(vec3(.75, .85, .95), vec3(.2, .45, .8), clamp(direction.y * 2., 0., 1.));
+    if direction.y < .05 {
+        return vec4(sky, 1.);
+    }
+
+    // March through the layer of clouds, between 0.5 and 2.5 above the camera.
+    let enter = .5 / direction.y;
+    let exit = min(2.5 / direction.y, enter + 10.);
+    let step_size = (exit - enter) / 48.;
+
+    var transmittance = 1.;
+    var light = vec3(0.);
+    for (var i = 0; i < 48; i++) {
+        let p = direction * (enter + (f32(i) + .5) * step_size);
+        let d = density(p);
+        if d > 0. {
+            // The sun is above, the clouds get darker under a thick layer.
+            let lit = exp(-density(p + vec3(0., .3, 0.)) * 1.5);
+            let absorbed = exp(-d * step_size);
+            light += transmittance * (1. - absorbed) * mix(vec3(.35, .4, .5), vec3(1.), lit);
+            transmittance *= absorbed;
+        }
+    }
+
+    return vec4(sky * transmittance + light, 1.);
+}
@@ -0,0 +1,60 @@
+// tags: raymarch, reflection, cubemap
+// channel0: cubemap sky.png
+fn scene(p: vec3<f32>) -> f32 {
+    let t = u.time * .5;
+    let a = length(p - vec3(sin(t) * 1.6, .2, cos(t) * 1.6)) - .5;
+    let b = length(p - vec3(-sin(t) * 1.6, .3 * sin(t * 2.), -cos(t) * 1.6)) - .4;
+    let c = length(p) - .8;
+    return min(c, min(a, b));
+}
+
+fn normal(p: vec3<f32>) -> vec3<f32> {
+    let e = vec2(.001, 0.);
+    return normalize(vec3(
+        scene(p + e.xyy) - scene(p - e.xyy),
+        scene(p + e.yxy) - scene(p - e.yxy),
+        scene(p + e.yyx) - scene(p - e.yyx),
+    ));
+}
+
+fn main_image(frag_coord: vec4<f32>) -> vec4<f32> {
+    let uv = uv_ratioed(frag_coord.xy) * vec2(1., -1.);
+
+    let angle = u.time * .1;
+    let origin = vec3(sin(angle) * 4., 1., cos(angle) * 4.);
+    let forward = normalize(-origin);
+    let right = normalize(cross(forward, vec3(0., 1., 0.)));
+    let up = cross(right, forward);
+
+    var position = origin;
+    var direction = normalize(forward * 1.8 + right * uv.x + up * uv.y);
+    var weight = vec3(1.);
+
+    // Bounce between the spheres, the sky is seen in the last direction.
+    for (var bounce = 0; bounce < 3; bounce++) {
+        var t = 0.;
+        var hit = false;
+        for (var i = 0; i < 96; i++) {
+            let d = scene(position + direction * t);
+            if d < .001 {
+                hit = true;
+                break;
+            }
+            t += d;
+            if t > 20. {
+                break;
+            }
+        }
+        if !hit {
+            break;
+        }
+
+        position += direction * t;
+        let n = normal(position);
+        direction = reflect(direction, n);
+        position += n * .01;
+        weight *= vec3(.9, .85, .8);
+    }
+
+    return vec4(weight * sample_cubemap(channel0, direction).rgb, 1.);
+}
@@ -1,3 +1,4 @@
+// tags: ring, monochrome, animated
 fn main_image(frag_coord: vec4<f32>) -> vec4<f32> {
     let uv: vec2<f32> = uv_ratioed(frag_coord.xy);
 
@@ -0,0 +1,15 @@
+// tags: vertex, points, blue
+// vertices: 16384 points
+fn vertex_main(vertex_index: u32) -> VertexOutput {
+    // A square grid of points, rippling from the center,
+    // at least 2 points wide so the cells can be divided by `side - 1`.
+    let side = max(u32(sqrt(u.vertex_count)), 2u);
+    let cell = vec2(f32(vertex_index % side), f32(vertex_index / side)) / f32(side - 1u);
+    let uv = cell * 2. - 1.;
+
+    let wave = sin(length(uv) * 10. - u.time * 3.);
+    let position = uv * (0.8 + 0.04 * wave) * vec2(u.resolution.y / u.resolution.x, 1.);
+
+    let brightness = 0.6 + 0.4 * wave;
+    return VertexOutput(vec4(position, 0., 1.), vec4(vec3(0.3, 0.6, 1.) * brightness, 1.));
+}
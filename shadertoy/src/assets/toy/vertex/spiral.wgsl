@@ -0,0 +1,15 @@
+// tags: vertex, lines, color
+// vertices: 6000 lines
+fn vertex_main(vertex_index: u32) -> VertexOutput {
+    // Every line goes from a point of the spiral to the same point on the next turn.
+    let line = f32(vertex_index / 2u);
+    let end = f32(vertex_index % 2u);
+    let progress = line / (u.vertex_count * 0.5);
+
+    let angle = progress * 60. + end * 6.2832 + u.time * 0.3;
+    let radius = 0.05 + 0.8 * progress + end * 0.02 * sin(line * 0.1 + u.time * 2.);
+    let position = vec2(cos(angle), sin(angle)) * radius * vec2(u.resolution.y / u.resolution.x, 1.);
+
+    let color = 0.5 + 0.5 * cos(6.2832 * (progress + vec3(0., 0.33, 0.67)) + u.time);
+    return VertexOutput(vec4(position, 0., 1.), vec4(color * 0.3, 1.));
+}
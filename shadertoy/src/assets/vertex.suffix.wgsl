@@ -0,0 +1,18 @@
+@vertex
+fn vertex(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var out = vertex_main(vertex_index);
+
+    // Move the position to the render target, which is only a tile of the frame when
+    // `scale` and `offset` are used.
+    let ndc = out.position.xy / out.position.w;
+    let coord = (vec2(ndc.x, -ndc.y) + 1.) * 0.5 * u.resolution;
+    let moved = (coord - u.offset) / u.scale / u.target_resolution * 2. - 1.;
+    out.position = vec4(vec2(moved.x, -moved.y) * out.position.w, out.position.zw);
+
+    return out;
+}
+
+@fragment
+fn main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
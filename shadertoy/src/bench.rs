@@ -0,0 +1,258 @@
+use std::{io::Write, sync::Arc};
+
+use anyhow::Context;
+use instant::Instant;
+
+use crate::{
+    cli::BenchOptions,
+    gpu,
+    hdr::{Tonemap, TonemapPass, HDR_FORMAT},
+    pipeline::{self, PipelineCache},
+    shader_list::ShaderList,
+    timing::{GpuTimer, RollingWindow, Summary},
+};
+
+/// Time elapsed between two frames, the toys see the same time on every run.
+const TIME_STEP: f32 = 1. / 60.;
+const OUTPUT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReportFormat {
+    #[default]
+    Table,
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+struct ToyReport {
+    name: String,
+    frames: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cpu: Option<Timings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gpu: Option<Timings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Timings in milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+struct Timings {
+    min: f64,
+    avg: f64,
+    p99: f64,
+}
+
+impl From<Summary> for Timings {
+    fn from(summary: Summary) -> Self {
+        Self {
+            min: summary.min.as_secs_f64() * 1000.,
+            avg: summary.avg.as_secs_f64() * 1000.,
+            p99: summary.p99.as_secs_f64() * 1000.,
+        }
+    }
+}
+
+/// Render every toy offscreen for a fixed number of frames and print a report on stdout.
+pub async fn run(shader_list: ShaderList, options: BenchOptions) -> anyhow::Result<()> {
+    let instance = gpu::create_instance();
+    let adapter = gpu::request_adapter(&instance, None).await?;
+    let (device, queue) = gpu::request_device(&adapter).await?;
+    let queue = Arc::new(queue);
+    let max_size = device.limits().max_texture_dimension_2d;
+    anyhow::ensure!(
+        options.width <= max_size && options.height <= max_size,
+        "The frames can't be bigger than {max_size}x{max_size} on this GPU, not {}x{}",
+        options.width,
+        options.height
+    );
+
+    let (mut uniform, uniform_buffer, uniform_bind_group_layout, uniform_bind_group) =
+        crate::uniform::setup_uniform(&device);
+    uniform.resolution = [options.width as f32, options.height as f32];
+    uniform.target_resolution = uniform.resolution;
+
+    let tonemap_pass = TonemapPass::new(&device, OUTPUT_FORMAT, Tonemap::default(), 1.);
+    let hdr_target = tonemap_pass.create_target(&device, options.width, options.height);
+
+    let output = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("bench-output-texture"),
+        size: wgpu::Extent3d {
+            width: options.width,
+            height: options.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: OUTPUT_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+    let output_view = output.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let mut pipeline_cache = PipelineCache::new(&device, queue.clone(), uniform_bind_group_layout);
+    let mut gpu_timer = GpuTimer::new(&device, &queue);
+    let mut reports = Vec::new();
+
+    for (name, shader) in shader_list.iter() {
+        log::info!("Benchmarking {name}");
+
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let pipeline = pipeline_cache.get_or_build(&device, &shader, HDR_FORMAT);
+        if let Some(error) = device.pop_error_scope().await {
+            log::error!("Can't build the pipeline of {name}: {error}");
+            reports.push(ToyReport {
+                name,
+                frames: 0,
+                cpu: None,
+                gpu: None,
+                error: Some(error.to_string()),
+            });
+            continue;
+        }
+        uniform.vertex_count = pipeline.geometry().vertex_count() as f32;
+
+        let frames = options.frames as usize;
+        let mut cpu_times = RollingWindow::new(frames);
+        let mut gpu_times = RollingWindow::new(frames);
+
+        for frame in 0..u64::from(options.warmup) + u64::from(options.frames) {
+            uniform.time = frame as f32 * TIME_STEP;
+            queue.write_buffer(&uniform_buffer, 0, uniform.as_ref());
+
+            let start = Instant::now();
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("bench-encoder"),
+            });
+
+            pipeline.update_channels(&mut encoder, uniform.time);
+            gpu_timer.begin(&mut encoder);
+            pipeline::render_toy(
+                &mut encoder,
+                &pipeline,
+                &uniform_bind_group,
+                &hdr_target.view,
+            );
+            tonemap_pass.render(&mut encoder, &hdr_target, &output_view);
+            gpu_timer.end(&mut encoder);
+
+            queue.submit(std::iter::once(encoder.finish()));
+            gpu_timer.submitted(&queue);
+            pipeline.channels_submitted();
+            device.poll(wgpu::Maintain::Wait);
+
+            let cpu_time = start.elapsed();
+            let gpu_time = gpu_timer.poll();
+
+            if frame >= u64::from(options.warmup) {
+                cpu_times.push(cpu_time);
+                if let Some(gpu_time) = gpu_time {
+                    gpu_times.push(gpu_time);
+                }
+            }
+        }
+        pipeline.close_streams();
+
+        reports.push(ToyReport {
+            name,
+            frames,
+            cpu: cpu_times.summary().map(Timings::from),
+            gpu: gpu_times.summary().map(Timings::from),
+            error: None,
+        });
+    }
+
+    let stdout = std::io::stdout().lock();
+    match options.format {
+        ReportFormat::Table => write_table(&reports, stdout),
+        ReportFormat::Json => {
+            serde_json::to_writer_pretty(stdout, &reports).map_err(std::io::Error::from)
+        }
+    }
+    .context("Can't write the report")
+}
+
+fn write_table(reports: &[ToyReport], mut writer: impl Write) -> std::io::Result<()> {
+    let name_width = reports
+        .iter()
+        .map(|report| report.name.len())
+        .chain(std::iter::once("toy".len()))
+        .max()
+        .unwrap_or_default();
+
+    writeln!(
+        writer,
+        "{:name_width$} | {:>6} | {:>26} | {:>26}",
+        "toy", "frames", "cpu min/avg/p99 (ms)", "gpu min/avg/p99 (ms)"
+    )?;
+
+    for report in reports {
+        match &report.error {
+            Some(error) => writeln!(
+                writer,
+                "{:name_width$} | {:>6} | error: {}",
+                report.name,
+                report.frames,
+                error.lines().next().unwrap_or_default()
+            )?,
+            None => writeln!(
+                writer,
+                "{:name_width$} | {:>6} | {:>26} | {:>26}",
+                report.name,
+                report.frames,
+                format_timings(report.cpu),
+                format_timings(report.gpu)
+            )?,
+        }
+    }
+
+    Ok(())
+}
+
+fn format_timings(timings: Option<Timings>) -> String {
+    timings
+        .map(|t| format!("{:.3}/{:.3}/{:.3}", t.min, t.avg, t.p99))
+        .unwrap_or_else(|| "n/a".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_table, Timings, ToyReport};
+
+    #[test]
+    fn table() {
+        let reports = [
+            ToyReport {
+                name: "default.wgsl".to_string(),
+                frames: 2,
+                cpu: Some(Timings {
+                    min: 1.,
+                    avg: 1.5,
+                    p99: 2.,
+                }),
+                gpu: None,
+                error: None,
+            },
+            ToyReport {
+                name: "broken.wgsl".to_string(),
+                frames: 0,
+                cpu: None,
+                gpu: None,
+                error: Some("Shader validation error:\nmore details".to_string()),
+            },
+        ];
+
+        let mut table = Vec::new();
+        write_table(&reports, &mut table).unwrap();
+
+        assert_eq!(
+            String::from_utf8(table).unwrap(),
+            "\
+toy          | frames |       cpu min/avg/p99 (ms) |       gpu min/avg/p99 (ms)
+default.wgsl |      2 |          1.000/1.500/2.000 |                        n/a
+broken.wgsl  |      0 | error: Shader validation error:
+"
+        );
+    }
+}
@@ -0,0 +1,724 @@
+use std::f32::consts::{PI, TAU};
+
+use anyhow::Context;
+
+use crate::stream::Stream;
+
+/// Number of channels of a toy, `channel0` to `channel3`.
+pub const MAX_CHANNELS: usize = 4;
+/// Prefix of the comments declaring the channels, e.g. `// channel0: cubemap sky.png`.
+const CHANNEL_PREFIX: &str = "// channel";
+/// Largest volume, the 3D textures of WebGL2 are limited to 256 texels.
+const MAX_VOLUME_SIZE: u32 = 256;
+
+/// A texture bound to a toy, declared by a `// channel<index>: <source>` comment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Channel {
+    pub index: usize,
+    pub source: ChannelSource,
+}
+
+/// Where the texels of a channel come from, the files are in `assets/channel`
+/// except for the streams which are read from the disk while the toy plays.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChannelSource {
+    /// `image <file>`, a `texture_2d<f32>`.
+    Image(String),
+    /// `cubemap <file>` for an equirectangular image, converted when loaded,
+    /// or `cubemap <+x> <-x> <+y> <-y> <+z> <-z>` for the six faces, a `texture_cube<f32>`.
+    Cubemap(Vec<String>),
+    /// `volume <directory>`, the images of the directory stacked in the order of their names,
+    /// a `texture_3d<f32>`.
+    VolumeSlices(String),
+    /// `volume noise <size>`, tileable value noise with a different seed in every component,
+    /// a `texture_3d<f32>`.
+    VolumeNoise(u32),
+    /// `sequence <directory> <fps>`, the numbered PNGs of a local directory played in a loop,
+    /// a `texture_2d<f32>`.
+    Sequence { directory: String, fps: f32 },
+    /// `video <file>`, a local video decoded by `ffmpeg` and played in a loop,
+    /// a `texture_2d<f32>`.
+    Video(String),
+}
+
+impl ChannelSource {
+    pub fn parse(declaration: &str) -> anyhow::Result<Self> {
+        let words = declaration.split_whitespace().collect::<Vec<_>>();
+        let source = match words.as_slice() {
+            ["image", file] => Self::Image(file.to_string()),
+            ["cubemap", file] => Self::Cubemap(vec![file.to_string()]),
+            ["cubemap", faces @ ..] if faces.len() == 6 => {
+                Self::Cubemap(faces.iter().map(|face| face.to_string()).collect())
+            }
+            ["volume", "noise", size] => {
+                let size = size
+                    .parse()
+                    .with_context(|| format!("Invalid volume size `{size}`"))?;
+                anyhow::ensure!(
+                    (2..=MAX_VOLUME_SIZE).contains(&size),
+                    "The volume size must be between 2 and {MAX_VOLUME_SIZE}"
+                );
+                Self::VolumeNoise(size)
+            }
+            ["volume", "noise", ..] => anyhow::bail!("Expected `volume noise <size>`"),
+            ["volume", directory] => {
+                Self::VolumeSlices(directory.trim_end_matches('/').to_string())
+            }
+            ["sequence", directory, fps] => {
+                let fps = fps
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|fps| fps.is_finite() && *fps > 0.)
+                    .with_context(|| format!("Invalid frame rate `{fps}`"))?;
+                Self::Sequence {
+                    directory: directory.trim_end_matches('/').to_string(),
+                    fps,
+                }
+            }
+            ["video", file] => Self::Video(file.to_string()),
+            ["image", ..] => anyhow::bail!("Expected `image <file>`"),
+            ["cubemap", ..] => anyhow::bail!(
+                "Expected `cubemap <file>` or `cubemap <+x> <-x> <+y> <-y> <+z> <-z>`"
+            ),
+            ["volume", ..] => {
+                anyhow::bail!("Expected `volume <directory>` or `volume noise <size>`")
+            }
+            ["sequence", ..] => anyhow::bail!("Expected `sequence <directory> <fps>`"),
+            ["video", ..] => anyhow::bail!("Expected `video <file>`"),
+            [kind, ..] => anyhow::bail!("Unknown channel kind `{kind}`"),
+            [] => anyhow::bail!("The channel kind is missing"),
+        };
+        Ok(source)
+    }
+
+    pub fn view_dimension(&self) -> wgpu::TextureViewDimension {
+        match self {
+            Self::Image(_) | Self::Sequence { .. } | Self::Video(_) => {
+                wgpu::TextureViewDimension::D2
+            }
+            Self::Cubemap(_) => wgpu::TextureViewDimension::Cube,
+            Self::VolumeSlices(_) | Self::VolumeNoise(_) => wgpu::TextureViewDimension::D3,
+        }
+    }
+
+    /// Whether the texels change while the toy plays.
+    pub fn is_stream(&self) -> bool {
+        matches!(self, Self::Sequence { .. } | Self::Video(_))
+    }
+
+    fn wgsl_type(&self) -> &'static str {
+        match self.view_dimension() {
+            wgpu::TextureViewDimension::Cube => "texture_cube<f32>",
+            wgpu::TextureViewDimension::D3 => "texture_3d<f32>",
+            _ => "texture_2d<f32>",
+        }
+    }
+}
+
+/// The channels declared by a toy with the line of their comment, starting from 1.
+///
+/// Only the comments written exactly `// channel<digit>:` declare a channel,
+/// e.g. `// channels: see below` is a plain comment.
+pub fn channels(body: &str) -> Vec<(usize, anyhow::Result<Channel>)> {
+    let mut declared = [false; MAX_CHANNELS];
+    body.lines()
+        .enumerate()
+        .filter_map(|(line, text)| {
+            let declaration = text.trim().strip_prefix(CHANNEL_PREFIX)?;
+            let digit = declaration.chars().next()?.to_digit(10)?;
+            let declaration = declaration[1..].strip_prefix(':')?;
+            let channel = Some(digit as usize)
+                .filter(|&index| index < MAX_CHANNELS)
+                .with_context(|| {
+                    format!(
+                        "The channels go from channel0 to channel{}",
+                        MAX_CHANNELS - 1
+                    )
+                })
+                .and_then(|index| {
+                    anyhow::ensure!(
+                        !std::mem::replace(&mut declared[index], true),
+                        "channel{index} is already declared"
+                    );
+                    let source = ChannelSource::parse(declaration)?;
+                    Ok(Channel { index, source })
+                });
+            Some((line + 1, channel))
+        })
+        .collect()
+}
+
+/// The valid channels of a toy, the invalid ones aren't bound.
+pub fn valid_channels(body: &str) -> Vec<Channel> {
+    channels(body)
+        .into_iter()
+        .filter_map(|(_, channel)| channel.ok())
+        .collect()
+}
+
+/// The WGSL declarations of the channels of a toy, the sampler is declared by the prefix.
+pub fn bindings(body: &str) -> String {
+    valid_channels(body)
+        .iter()
+        .map(|channel| {
+            format!(
+                "\n@group(1) @binding({})\nvar channel{}: {};\n",
+                channel.index + 1,
+                channel.index,
+                channel.source.wgsl_type()
+            )
+        })
+        .collect()
+}
+
+pub fn bind_group_layout(device: &wgpu::Device, channels: &[Channel]) -> wgpu::BindGroupLayout {
+    let sampler = wgpu::BindGroupLayoutEntry {
+        binding: 0,
+        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+        count: None,
+    };
+    let textures = channels.iter().map(|channel| wgpu::BindGroupLayoutEntry {
+        binding: channel.index as u32 + 1,
+        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: channel.source.view_dimension(),
+            multisampled: false,
+        },
+        count: None,
+    });
+
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("channel-bind-group-layout"),
+        entries: &std::iter::once(sampler).chain(textures).collect::<Vec<_>>(),
+    })
+}
+
+/// Load the textures of the channels and open their streams,
+/// a channel that can't be loaded is black.
+pub fn bind_group(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    layout: &wgpu::BindGroupLayout,
+    channels: &[Channel],
+) -> (wgpu::BindGroup, Vec<Stream>) {
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("channel-sampler"),
+        address_mode_u: wgpu::AddressMode::Repeat,
+        address_mode_v: wgpu::AddressMode::Repeat,
+        address_mode_w: wgpu::AddressMode::Repeat,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    });
+
+    let mut streams = Vec::new();
+    let views = channels
+        .iter()
+        .map(|channel| {
+            let texels = if channel.source.is_stream() {
+                match Stream::new(device, queue, &channel.source) {
+                    Ok(stream) => {
+                        let view = stream.create_view();
+                        streams.push(stream);
+                        return view;
+                    }
+                    Err(e) => {
+                        log::error!("Can't open channel{}: {e:#}", channel.index);
+                        TextureData::black(&channel.source)
+                    }
+                }
+            } else {
+                TextureData::decode(&channel.source, crate::CHANNEL_FILES).unwrap_or_else(|e| {
+                    log::error!("Can't load channel{}: {e:#}", channel.index);
+                    TextureData::black(&channel.source)
+                })
+            };
+            texels
+                .upload(device, queue)
+                .create_view(&wgpu::TextureViewDescriptor {
+                    dimension: Some(channel.source.view_dimension()),
+                    ..Default::default()
+                })
+        })
+        .collect::<Vec<_>>();
+
+    let sampler = wgpu::BindGroupEntry {
+        binding: 0,
+        resource: wgpu::BindingResource::Sampler(&sampler),
+    };
+    let textures = channels
+        .iter()
+        .zip(&views)
+        .map(|(channel, view)| wgpu::BindGroupEntry {
+            binding: channel.index as u32 + 1,
+            resource: wgpu::BindingResource::TextureView(view),
+        });
+
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("channel-bind-group"),
+        layout,
+        entries: &std::iter::once(sampler).chain(textures).collect::<Vec<_>>(),
+    });
+    (bind_group, streams)
+}
+
+/// The RGBA8 texels of a channel, layer by layer.
+#[derive(Debug, Clone, PartialEq)]
+struct TextureData {
+    width: u32,
+    height: u32,
+    /// The faces of a cubemap or the slices of a volume.
+    layers: u32,
+    dimension: wgpu::TextureViewDimension,
+    /// The images are colors, the volumes are data.
+    srgb: bool,
+    texels: Vec<u8>,
+}
+
+impl TextureData {
+    fn decode(source: &ChannelSource, files: &[(&str, &[u8])]) -> anyhow::Result<Self> {
+        let dimension = source.view_dimension();
+        match source {
+            ChannelSource::Image(file) => {
+                let image = load_image(files, file)?;
+                Ok(Self {
+                    width: image.width(),
+                    height: image.height(),
+                    layers: 1,
+                    dimension,
+                    srgb: true,
+                    texels: image.into_raw(),
+                })
+            }
+            ChannelSource::Cubemap(faces) if faces.len() == 1 => {
+                let image = load_image(files, &faces[0])?;
+                let size = (image.height() / 2).max(1);
+                Ok(Self {
+                    width: size,
+                    height: size,
+                    layers: 6,
+                    dimension,
+                    srgb: true,
+                    texels: equirectangular_to_cubemap(&image, size),
+                })
+            }
+            ChannelSource::Cubemap(faces) => {
+                let faces = faces
+                    .iter()
+                    .map(|face| load_image(files, face))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                let (width, height) = faces[0].dimensions();
+                anyhow::ensure!(width == height, "The faces of a cubemap must be square");
+                anyhow::ensure!(
+                    faces
+                        .iter()
+                        .all(|face| face.dimensions() == (width, height)),
+                    "The faces of a cubemap must have the same size"
+                );
+                Ok(Self {
+                    width,
+                    height,
+                    layers: 6,
+                    dimension,
+                    srgb: true,
+                    texels: faces.into_iter().flat_map(|face| face.into_raw()).collect(),
+                })
+            }
+            ChannelSource::VolumeSlices(directory) => {
+                let prefix = format!("{directory}/");
+                let mut names = files
+                    .iter()
+                    .map(|(name, _)| *name)
+                    .filter(|name| name.starts_with(&prefix))
+                    .collect::<Vec<_>>();
+                names.sort_unstable();
+                anyhow::ensure!(!names.is_empty(), "There is no image in `{directory}`");
+
+                let slices = names
+                    .iter()
+                    .map(|name| load_image(files, name))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                let (width, height) = slices[0].dimensions();
+                anyhow::ensure!(
+                    slices
+                        .iter()
+                        .all(|slice| slice.dimensions() == (width, height)),
+                    "The slices of a volume must have the same size"
+                );
+                anyhow::ensure!(
+                    width.max(height).max(slices.len() as u32) <= MAX_VOLUME_SIZE,
+                    "A volume can't be bigger than {MAX_VOLUME_SIZE} texels"
+                );
+                Ok(Self {
+                    width,
+                    height,
+                    layers: slices.len() as u32,
+                    dimension,
+                    srgb: false,
+                    texels: slices
+                        .into_iter()
+                        .flat_map(|slice| slice.into_raw())
+                        .collect(),
+                })
+            }
+            ChannelSource::Sequence { .. } | ChannelSource::Video(_) => {
+                anyhow::bail!("The streams are decoded while the toy plays")
+            }
+            &ChannelSource::VolumeNoise(size) => Ok(Self {
+                width: size,
+                height: size,
+                layers: size,
+                dimension,
+                srgb: false,
+                texels: volume_noise(size),
+            }),
+        }
+    }
+
+    /// A black texel, bound in place of a channel that can't be loaded.
+    fn black(source: &ChannelSource) -> Self {
+        let dimension = source.view_dimension();
+        let layers = match dimension {
+            wgpu::TextureViewDimension::Cube => 6,
+            _ => 1,
+        };
+        Self {
+            width: 1,
+            height: 1,
+            layers,
+            dimension,
+            srgb: false,
+            texels: [0, 0, 0, 255].repeat(layers as usize),
+        }
+    }
+
+    fn upload(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> wgpu::Texture {
+        let size = wgpu::Extent3d {
+            width: self.width,
+            height: self.height,
+            depth_or_array_layers: self.layers,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("channel-texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: match self.dimension {
+                wgpu::TextureViewDimension::D3 => wgpu::TextureDimension::D3,
+                _ => wgpu::TextureDimension::D2,
+            },
+            format: if self.srgb {
+                wgpu::TextureFormat::Rgba8UnormSrgb
+            } else {
+                wgpu::TextureFormat::Rgba8Unorm
+            },
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        queue.write_texture(
+            texture.as_image_copy(),
+            &self.texels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(self.width * 4),
+                rows_per_image: Some(self.height),
+            },
+            size,
+        );
+        texture
+    }
+}
+
+fn load_image(files: &[(&str, &[u8])], name: &str) -> anyhow::Result<image::RgbaImage> {
+    let (_, bytes) = files
+        .iter()
+        .find(|(file, _)| *file == name)
+        .with_context(|| format!("There is no file `{name}` in assets/channel"))?;
+    let image = image::load_from_memory(bytes).with_context(|| format!("Can't decode `{name}`"))?;
+    Ok(image.to_rgba8())
+}
+
+/// The direction of the point `(s, t)` of a face, from -1 to 1,
+/// the faces are in the order of the layers: +X, -X, +Y, -Y, +Z, -Z.
+fn cube_direction(face: usize, s: f32, t: f32) -> [f32; 3] {
+    match face {
+        0 => [1., -t, -s],
+        1 => [-1., -t, s],
+        2 => [s, 1., t],
+        3 => [s, -1., -t],
+        4 => [s, -t, 1.],
+        _ => [-s, -t, -1.],
+    }
+}
+
+/// The position of a direction in an equirectangular image, from 0 to 1,
+/// -Z is at the center and +Y at the top.
+fn equirectangular_uv([x, y, z]: [f32; 3]) -> [f32; 2] {
+    let length = (x * x + y * y + z * z).sqrt();
+    let u = 0.5 + x.atan2(-z) / TAU;
+    let v = (y / length).clamp(-1., 1.).acos() / PI;
+    [u, v]
+}
+
+fn equirectangular_to_cubemap(image: &image::RgbaImage, size: u32) -> Vec<u8> {
+    let mut texels = Vec::with_capacity((size * size * 6 * 4) as usize);
+    for face in 0..6 {
+        for y in 0..size {
+            for x in 0..size {
+                let s = (x as f32 + 0.5) / size as f32 * 2. - 1.;
+                let t = (y as f32 + 0.5) / size as f32 * 2. - 1.;
+                let [u, v] = equirectangular_uv(cube_direction(face, s, t));
+                texels.extend(sample_bilinear(image, u, v));
+            }
+        }
+    }
+    texels
+}
+
+/// Sample the image at `(u, v)`, from 0 to 1, repeating horizontally.
+fn sample_bilinear(image: &image::RgbaImage, u: f32, v: f32) -> [u8; 4] {
+    let (width, height) = image.dimensions();
+    let x = u * width as f32 - 0.5;
+    let y = (v * height as f32 - 0.5).clamp(0., (height - 1) as f32);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+
+    let texel = |dx: i64, dy: u32| {
+        let x = (x0 as i64 + dx).rem_euclid(i64::from(width)) as u32;
+        let y = (y0 as u32 + dy).min(height - 1);
+        image.get_pixel(x, y).0.map(f32::from)
+    };
+    let (a, b, c, d) = (texel(0, 0), texel(1, 0), texel(0, 1), texel(1, 1));
+    std::array::from_fn(|i| {
+        let top = a[i] + (b[i] - a[i]) * fx;
+        let bottom = c[i] + (d[i] - c[i]) * fx;
+        (top + (bottom - top) * fy).round() as u8
+    })
+}
+
+/// Tileable value noise, three octaves with one seed per component.
+fn volume_noise(size: u32) -> Vec<u8> {
+    let mut texels = Vec::with_capacity((size * size * size * 4) as usize);
+    for z in 0..size {
+        for y in 0..size {
+            for x in 0..size {
+                let position = [x, y, z].map(|v| v as f32 / size as f32);
+                for seed in 0..4 {
+                    let mut value = 0.;
+                    let mut amplitude = 0.5;
+                    for octave in 0..3 {
+                        let period = (4 << octave).min(size);
+                        value += amplitude * value_noise(position, period, seed);
+                        amplitude *= 0.5;
+                    }
+                    texels.push((value / 0.875 * 255.).round() as u8);
+                }
+            }
+        }
+    }
+    texels
+}
+
+/// Smooth noise from 0 to 1 at `position`, repeating every unit, with `period` cells per unit.
+fn value_noise(position: [f32; 3], period: u32, seed: u32) -> f32 {
+    let cell = position.map(|v| v * period as f32);
+    let corner = cell.map(|v| v.floor());
+    let fraction: [f32; 3] = std::array::from_fn(|i| {
+        let f = cell[i] - corner[i];
+        f * f * (3. - 2. * f)
+    });
+
+    let lattice = |dx: u32, dy: u32, dz: u32| {
+        let [x, y, z] = [(corner[0], dx), (corner[1], dy), (corner[2], dz)]
+            .map(|(corner, delta)| (corner as u32 + delta) % period);
+        hash(x, y, z, seed)
+    };
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    let face = |dz| {
+        lerp(
+            lerp(lattice(0, 0, dz), lattice(1, 0, dz), fraction[0]),
+            lerp(lattice(0, 1, dz), lattice(1, 1, dz), fraction[0]),
+            fraction[1],
+        )
+    };
+    lerp(face(0), face(1), fraction[2])
+}
+
+/// A pseudo-random value from 0 to 1.
+fn hash(x: u32, y: u32, z: u32, seed: u32) -> f32 {
+    let mut h = x.wrapping_mul(0x8da6_b343)
+        ^ y.wrapping_mul(0xd816_3841)
+        ^ z.wrapping_mul(0xcb1a_b31f)
+        ^ seed.wrapping_mul(0x1656_67b1);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x2c1b_3c6d);
+    h ^= h >> 12;
+    h = h.wrapping_mul(0x297a_2d39);
+    h ^= h >> 15;
+    (h & 0xffff) as f32 / 65535.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        bindings, channels, cube_direction, equirectangular_uv, value_noise, Channel,
+        ChannelSource, TextureData,
+    };
+
+    #[test]
+    fn parse_sources() {
+        assert_eq!(
+            ChannelSource::parse(" cubemap sky.png").unwrap(),
+            ChannelSource::Cubemap(vec!["sky.png".to_string()])
+        );
+        assert_eq!(
+            ChannelSource::parse("cubemap px nx py ny pz nz").unwrap(),
+            ChannelSource::Cubemap(
+                ["px", "nx", "py", "ny", "pz", "nz"]
+                    .map(String::from)
+                    .into()
+            )
+        );
+        assert_eq!(
+            ChannelSource::parse("volume clouds/").unwrap(),
+            ChannelSource::VolumeSlices("clouds".to_string())
+        );
+        assert_eq!(
+            ChannelSource::parse("volume noise 32").unwrap(),
+            ChannelSource::VolumeNoise(32)
+        );
+
+        assert!(ChannelSource::parse("cubemap a b").is_err());
+        assert!(ChannelSource::parse("volume noise 1024").is_err());
+        assert!(ChannelSource::parse("volume noise").is_err());
+        assert_eq!(
+            ChannelSource::parse("sequence frames/ 24").unwrap(),
+            ChannelSource::Sequence {
+                directory: "frames".to_string(),
+                fps: 24.
+            }
+        );
+        assert_eq!(
+            ChannelSource::parse("video clip.mp4").unwrap(),
+            ChannelSource::Video("clip.mp4".to_string())
+        );
+        assert!(ChannelSource::parse("sequence frames").is_err());
+        assert!(ChannelSource::parse("sequence frames -1").is_err());
+        assert!(ChannelSource::parse("video").is_err());
+        assert!(ChannelSource::parse("camera 0").is_err());
+        assert!(ChannelSource::parse("").is_err());
+    }
+
+    #[test]
+    fn toy_channels() {
+        let toy = "// channel1: volume noise 8\n// channel0: image logo.png\n// channel4: image a.png\n// channel1: image b.png\n";
+
+        let channels = channels(toy);
+        assert_eq!(
+            channels
+                .iter()
+                .map(|(line, channel)| (*line, channel.is_ok()))
+                .collect::<Vec<_>>(),
+            [(1, true), (2, true), (3, false), (4, false)]
+        );
+        assert_eq!(
+            channels[0].1.as_ref().unwrap(),
+            &Channel {
+                index: 1,
+                source: ChannelSource::VolumeNoise(8)
+            }
+        );
+
+        assert_eq!(
+            bindings(toy),
+            "\n@group(1) @binding(2)\nvar channel1: texture_3d<f32>;\n\n@group(1) @binding(1)\nvar channel0: texture_2d<f32>;\n"
+        );
+    }
+
+    #[test]
+    fn plain_comments() {
+        let toy = "// channels: the noise is below\n// channel 0: image a.png\n\
+            // channel10: image a.png\n// channel0 image a.png\n// channel-1: image a.png\n";
+
+        assert!(channels(toy).is_empty());
+    }
+
+    #[test]
+    fn cube_faces() {
+        let centers = (0..6)
+            .map(|face| cube_direction(face, 0., 0.))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            centers,
+            [
+                [1., 0., 0.],
+                [-1., 0., 0.],
+                [0., 1., 0.],
+                [0., -1., 0.],
+                [0., 0., 1.],
+                [0., 0., -1.]
+            ]
+        );
+
+        assert_eq!(equirectangular_uv([0., 0., -1.]), [0.5, 0.5]);
+        assert_eq!(equirectangular_uv([1., 0., 0.]), [0.75, 0.5]);
+        assert_eq!(equirectangular_uv([0., 2., 0.])[1], 0.);
+        assert_eq!(equirectangular_uv([0., -1., 0.])[1], 1.);
+    }
+
+    fn png(image: &image::RgbaImage) -> Vec<u8> {
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        image
+            .write_to(&mut bytes, image::ImageOutputFormat::Png)
+            .unwrap();
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn decode_cubemap_and_slices() {
+        // A red sky above a blue ground.
+        let sky = image::RgbaImage::from_fn(8, 4, |_, y| match y {
+            0 | 1 => image::Rgba([255, 0, 0, 255]),
+            _ => image::Rgba([0, 0, 255, 255]),
+        });
+        let slice = |value| png(&image::RgbaImage::from_pixel(2, 2, image::Rgba([value; 4])));
+        let (sky, a, b, c) = (png(&sky), slice(1), slice(2), slice(3));
+        let files = [
+            ("sky.png", sky.as_slice()),
+            ("slices/b.png", &b),
+            ("slices/a.png", &a),
+            ("slices-not/c.png", &c),
+        ];
+
+        let cubemap =
+            TextureData::decode(&ChannelSource::Cubemap(vec!["sky.png".into()]), &files).unwrap();
+        assert_eq!((cubemap.width, cubemap.layers), (2, 6));
+        let face = |index: usize| &cubemap.texels[index * 16..index * 16 + 4];
+        assert_eq!(face(2), [255, 0, 0, 255]);
+        assert_eq!(face(3), [0, 0, 255, 255]);
+
+        let volume =
+            TextureData::decode(&ChannelSource::VolumeSlices("slices".into()), &files).unwrap();
+        assert_eq!((volume.width, volume.height, volume.layers), (2, 2, 2));
+        assert_eq!(volume.texels[..4], [1; 4]);
+        assert_eq!(volume.texels[16..20], [2; 4]);
+
+        assert!(TextureData::decode(&ChannelSource::Image("missing.png".into()), &files).is_err());
+    }
+
+    #[test]
+    fn tileable_noise() {
+        for position in [[0., 0.25, 0.625], [0.125, 0.5, 0.75]] {
+            let value = value_noise(position, 4, 1);
+            assert!((0. ..=1.).contains(&value));
+            assert_eq!(value, value_noise(position.map(|v| v + 1.), 4, 1));
+        }
+        assert_ne!(
+            value_noise([0.1, 0.2, 0.3], 4, 0),
+            value_noise([0.1, 0.2, 0.3], 4, 1)
+        );
+    }
+}
@@ -0,0 +1,156 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+
+use crate::{
+    bench::ReportFormat,
+    config::{self, WindowSize},
+    post::Effect,
+    present::{self, PresentMode},
+};
+
+/// Explore the toys shaders.
+///
+/// The options not given are read from `shadertoy/config.toml` in the user config directory,
+/// which also sets the keybindings.
+#[derive(Debug, Clone, clap::Parser)]
+#[command(version)]
+pub struct Options {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Toy shown at startup, fuzzy matched against the toy names and tags,
+    /// the last toy shown by default.
+    #[arg(long)]
+    pub toy: Option<String>,
+
+    /// Present mode of the window surface, fallback to a supported one if needed.
+    #[arg(long, value_enum)]
+    pub present_mode: Option<PresentMode>,
+
+    /// Size of the window at startup, in physical pixels.
+    #[arg(long, value_name = "WIDTHxHEIGHT")]
+    pub window_size: Option<WindowSize>,
+
+    /// Render the toys at this scale of the window size, between 0.1 and 4,
+    /// e.g. 0.5 to render 4 times less pixels.
+    #[arg(long, value_name = "FACTOR", value_parser = config::parse_render_scale)]
+    pub render_scale: Option<f32>,
+
+    /// Limit the number of frames rendered per second.
+    #[arg(long, value_name = "FPS", value_parser = present::parse_max_fps)]
+    pub max_fps: Option<f32>,
+
+    /// Post-processing effects applied after the toy, in this order,
+    /// toggled at runtime with `F1` to `F6`.
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "EFFECT")]
+    pub effects: Vec<Effect>,
+
+    /// Size of the high resolution screenshots (`Shift+P`) relative to the window.
+    #[arg(long, value_name = "FACTOR", default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..=16))]
+    pub screenshot_scale: u32,
+
+    /// Serve the remote control API on this port of localhost, over HTTP and WebSocket.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[arg(long, value_name = "PORT")]
+    pub remote_port: Option<u16>,
+
+    /// Listen for OSC messages on this UDP port of localhost.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[arg(long, value_name = "PORT")]
+    pub osc_port: Option<u16>,
+
+    /// JSON file mapping the OSC addresses to the toy parameters,
+    /// `src/assets/osc-mapping.json` is used by default.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[arg(long, value_name = "FILE", requires = "osc_port")]
+    pub osc_mapping: Option<PathBuf>,
+
+    /// Show only the toy in a second window, e.g. on a projector, while the title bar
+    /// of the main window keeps showing the prompt and the statistics.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[arg(long)]
+    pub output_window: bool,
+
+    /// Show the output window full screen on this monitor, starting from 0.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[arg(long, value_name = "INDEX", requires = "output_window")]
+    pub output_monitor: Option<usize>,
+
+    /// Record the inputs and the time of every frame to this file.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[arg(long, value_name = "FILE", conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+
+    /// Replay a recording made with `--record`, frame by frame, ignoring the inputs.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[arg(long, value_name = "FILE")]
+    pub replay: Option<PathBuf>,
+
+    /// Save every replayed frame to this directory, `--screenshot-scale` times bigger
+    /// than the window, to render a recorded session again at a higher quality.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[arg(long, value_name = "DIR", requires = "replay")]
+    pub replay_frames: Option<PathBuf>,
+}
+
+impl Options {
+    /// The keys of the config set by the options, which take precedence.
+    pub fn overridden_config(&self) -> Vec<&'static str> {
+        [
+            ("present-mode", self.present_mode.is_some()),
+            ("render-scale", self.render_scale.is_some()),
+            ("window", self.window_size.is_some()),
+        ]
+        .into_iter()
+        .filter(|(_, set)| *set)
+        .map(|(key, _)| key)
+        .collect()
+    }
+}
+
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum Command {
+    /// Render every toy offscreen and report how long they take to render.
+    Bench(BenchOptions),
+    /// Parse every toy and report its complexity, flagging the ones likely to time out.
+    Stats(StatsOptions),
+    /// Check that the toys define `main_image` and don't clash with the prefix.
+    Lint(LintOptions),
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct BenchOptions {
+    /// Number of measured frames per toy.
+    #[arg(long, default_value_t = 120, value_parser = clap::value_parser!(u32).range(1..))]
+    pub frames: u32,
+
+    /// Number of frames rendered before starting to measure.
+    #[arg(long, default_value_t = 10)]
+    pub warmup: u32,
+
+    /// Width of the rendered frames.
+    #[arg(long, default_value_t = 1280, value_parser = clap::value_parser!(u32).range(1..))]
+    pub width: u32,
+
+    /// Height of the rendered frames.
+    #[arg(long, default_value_t = 720, value_parser = clap::value_parser!(u32).range(1..))]
+    pub height: u32,
+
+    /// Format of the report printed on stdout.
+    #[arg(long, value_enum, default_value_t)]
+    pub format: ReportFormat,
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct StatsOptions {
+    /// Format of the report printed on stdout.
+    #[arg(long, value_enum, default_value_t)]
+    pub format: ReportFormat,
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct LintOptions {
+    /// Toys to check, the bundled ones by default.
+    #[arg(value_name = "FILE")]
+    pub files: Vec<std::path::PathBuf>,
+}
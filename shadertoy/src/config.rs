@@ -0,0 +1,306 @@
+use std::{
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::Context;
+
+use crate::{keys::Keys, present::PresentMode};
+
+/// Render scales accepted from the command line and the config.
+pub const RENDER_SCALE_RANGE: RangeInclusive<f32> = 0.1..=4.;
+
+/// Size of a window in physical pixels, written `1280x720` on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WindowSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl WindowSize {
+    fn validate(self) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            self.width > 0 && self.height > 0,
+            "The window size must not be empty, not {}x{}",
+            self.width,
+            self.height
+        );
+        Ok(self)
+    }
+}
+
+impl FromStr for WindowSize {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (width, height) = s
+            .split_once('x')
+            .with_context(|| format!("Expected a size like `1280x720`, not `{s}`"))?;
+        let size = Self {
+            width: width.trim().parse().context("Invalid width")?,
+            height: height.trim().parse().context("Invalid height")?,
+        };
+        size.validate()
+    }
+}
+
+/// Parse the `--render-scale` option, see [`RENDER_SCALE_RANGE`].
+pub fn parse_render_scale(s: &str) -> anyhow::Result<f32> {
+    validate_render_scale(s.parse().with_context(|| format!("`{s}` isn't a number"))?)
+}
+
+fn validate_render_scale(scale: f32) -> anyhow::Result<f32> {
+    anyhow::ensure!(
+        RENDER_SCALE_RANGE.contains(&scale),
+        "The render scale must be between {} and {}, not {scale}",
+        RENDER_SCALE_RANGE.start(),
+        RENDER_SCALE_RANGE.end()
+    );
+    Ok(scale)
+}
+
+/// The settings of the viewer kept between runs, in `shadertoy/config.toml`
+/// of the user config directory.
+///
+/// The command line options take precedence and aren't saved,
+/// only the toy, the window size and the favourites are updated on exit.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// Name of the toy shown at startup, the last one shown.
+    pub toy: Option<String>,
+    pub present_mode: PresentMode,
+    /// Size of the render targets relative to the windows, below 1 to render faster.
+    pub render_scale: f32,
+    /// Names of the favourite toys.
+    pub favourites: Vec<String>,
+    pub window: Option<WindowSize>,
+    pub keys: Keys,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            toy: None,
+            present_mode: PresentMode::default(),
+            render_scale: 1.,
+            favourites: Vec::new(),
+            window: None,
+            keys: Keys::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> anyhow::Result<PathBuf> {
+        dirs::config_dir()
+            .map(|dir| dir.join("shadertoy").join("config.toml"))
+            .context("Can't find the user config directory")
+    }
+
+    /// Load the config of the user, the default one if there is no config directory.
+    ///
+    /// The keys of `overridden` are set on the command line, an invalid value is only a warning.
+    pub fn load_user(overridden: &[&str]) -> anyhow::Result<Self> {
+        match Self::path() {
+            Ok(path) => Self::load(&path, overridden),
+            Err(e) => {
+                log::warn!("{e:#}, using the default config");
+                Ok(Self::default())
+            }
+        }
+    }
+
+    pub fn save_user(&self) -> anyhow::Result<()> {
+        self.save(&Self::path()?)
+    }
+
+    /// Load the config, or create a new one importing the favourites
+    /// of the `favourites.txt` file next to it, used before the config.
+    pub fn load(path: &Path, overridden: &[&str]) -> anyhow::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Self::parse_overridden(&text, overridden)
+                .with_context(|| format!("Invalid config {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::with_favourites_of(
+                &path.with_file_name("favourites.txt"),
+            )),
+            Err(e) => Err(e).with_context(|| format!("Can't read {}", path.display())),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Can't create {}", dir.display()))?;
+        }
+        let text = toml::to_string(self).context("Can't serialize the config")?;
+        std::fs::write(path, text).with_context(|| format!("Can't write to {}", path.display()))
+    }
+
+    /// Parse the config, leaving out the keys of `overridden` if the config is invalid with them.
+    fn parse_overridden(text: &str, overridden: &[&str]) -> anyhow::Result<Self> {
+        let error = match Self::parse(text) {
+            Ok(config) => return Ok(config),
+            Err(e) if !overridden.is_empty() => e,
+            Err(e) => return Err(e),
+        };
+
+        let mut table: toml::Table = toml::from_str(text)?;
+        table.retain(|key, _| !overridden.contains(&key));
+        let config = Self::parse(&table.to_string())?;
+        log::warn!("Invalid config, using the command line instead: {error:#}");
+        Ok(config)
+    }
+
+    fn parse(text: &str) -> anyhow::Result<Self> {
+        let config: Self = toml::from_str(text)?;
+        validate_render_scale(config.render_scale)?;
+        config.window.map(WindowSize::validate).transpose()?;
+        config.keys.validate()?;
+        Ok(config)
+    }
+
+    /// The default config with the favourites of `path`, one toy name per line.
+    fn with_favourites_of(path: &Path) -> Self {
+        let favourites = match std::fs::read_to_string(path) {
+            Ok(text) => {
+                log::info!("Importing the favourites of {}", path.display());
+                text.lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(String::from)
+                    .collect()
+            }
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    log::warn!("Can't read the favourites of {}: {e}", path.display());
+                }
+                Vec::new()
+            }
+        };
+
+        Self {
+            favourites,
+            ..Self::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::event::VirtualKeyCode;
+
+    use super::{parse_render_scale, Config, WindowSize};
+    use crate::present::PresentMode;
+
+    #[test]
+    fn parse_config() {
+        let config = Config::parse(
+            r#"
+            toy = "ring.wgsl"
+            present-mode = "mailbox"
+            render-scale = 0.5
+            favourites = ["neon.wgsl"]
+
+            [window]
+            width = 1280
+            height = 720
+
+            [keys]
+            pause = ["P"]
+            screenshot = ["F12"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.toy.as_deref(), Some("ring.wgsl"));
+        assert_eq!(config.present_mode, PresentMode::Mailbox);
+        assert_eq!(config.render_scale, 0.5);
+        assert_eq!(config.favourites, ["neon.wgsl"]);
+        assert_eq!(
+            config.window,
+            Some(WindowSize {
+                width: 1280,
+                height: 720
+            })
+        );
+        assert_eq!(config.keys.pause, [VirtualKeyCode::P]);
+        assert_eq!(config.keys.next_toy, [VirtualKeyCode::Right]);
+
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn invalid_config() {
+        for text in [
+            "render-scale = 10",
+            "present-mode = \"vsync\"",
+            "unknown = 1",
+            "[window]\nwidth = 0\nheight = 720",
+            "[keys]\npause = [\"Pause!\"]",
+            "[keys]\npause = [\"T\"]",
+        ] {
+            assert!(Config::parse(text).is_err(), "{text}");
+        }
+    }
+
+    #[test]
+    fn overridden_config() {
+        let text = "render-scale = 10
+favourites = [\"neon.wgsl\"]";
+        assert!(Config::parse_overridden(text, &[]).is_err());
+        assert!(Config::parse_overridden(text, &["present-mode"]).is_err());
+        let config = Config::parse_overridden(text, &["render-scale"]).unwrap();
+        assert_eq!(config.render_scale, 1.);
+        assert_eq!(config.favourites, ["neon.wgsl"]);
+
+        // A valid value is kept, to be saved again.
+        let config = Config::parse_overridden("render-scale = 2", &["render-scale"]).unwrap();
+        assert_eq!(config.render_scale, 2.);
+        // The other errors still stop the viewer.
+        assert!(
+            Config::parse_overridden("render-scale = 10\nunknown = 1", &["render-scale"]).is_err()
+        );
+    }
+
+    #[test]
+    fn save_and_load() {
+        let dir = std::env::temp_dir().join(format!("shadertoy-config-{}", std::process::id()));
+        let path = dir.join("config.toml");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("favourites.txt"), "ring.wgsl\n\nneon.wgsl\n").unwrap();
+
+        let mut config = Config::load(&path, &[]).unwrap();
+        assert_eq!(config.favourites, ["ring.wgsl", "neon.wgsl"]);
+
+        config.toy = Some("neon.wgsl".to_string());
+        config.window = Some(WindowSize {
+            width: 800,
+            height: 600,
+        });
+        config.save(&path).unwrap();
+        std::fs::write(dir.join("favourites.txt"), "ignored.wgsl\n").unwrap();
+        assert_eq!(Config::load(&path, &[]).unwrap(), config);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn parse_options() {
+        assert_eq!(
+            "1920x1080".parse::<WindowSize>().unwrap(),
+            WindowSize {
+                width: 1920,
+                height: 1080
+            }
+        );
+        assert!("1920".parse::<WindowSize>().is_err());
+        assert!("0x1080".parse::<WindowSize>().is_err());
+
+        assert_eq!(parse_render_scale("0.5").unwrap(), 0.5);
+        assert!(parse_render_scale("0").is_err());
+        assert!(parse_render_scale("half").is_err());
+    }
+}
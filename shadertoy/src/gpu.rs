@@ -0,0 +1,50 @@
+use anyhow::Context;
+
+pub fn create_instance() -> wgpu::Instance {
+    wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::all(),
+        dx12_shader_compiler: Default::default(),
+    })
+}
+
+/// Request an adapter, compatible with the surface if one is provided.
+pub async fn request_adapter(
+    instance: &wgpu::Instance,
+    compatible_surface: Option<&wgpu::Surface>,
+) -> anyhow::Result<wgpu::Adapter> {
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptionsBase {
+            power_preference: wgpu::PowerPreference::default(),
+            force_fallback_adapter: false,
+            compatible_surface,
+        })
+        .await
+        .context("Can't request a compatible adapter")?;
+
+    let adapter_info = adapter.get_info();
+
+    log::info!(
+        "Requested adapter: `{}` with driver `{}`",
+        adapter_info.name,
+        adapter_info.driver
+    );
+
+    Ok(adapter)
+}
+
+pub async fn request_device(
+    adapter: &wgpu::Adapter,
+) -> anyhow::Result<(wgpu::Device, wgpu::Queue)> {
+    adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("wgpu-device"),
+                // Timestamp queries are used to measure the GPU time when available.
+                features: adapter.features() & wgpu::Features::TIMESTAMP_QUERY,
+                limits: wgpu::Limits::default(),
+            },
+            None,
+        )
+        .await
+        .context("Can't request compatible device")
+}
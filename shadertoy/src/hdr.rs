@@ -0,0 +1,401 @@
+use std::{borrow::Cow, fmt::Display};
+
+use wgpu::util::DeviceExt;
+
+/// Format of the offscreen texture the toys render to.
+/// Using a float format allow the toys to output value above `1.0` without clipping.
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tonemap {
+    #[default]
+    None,
+    Reinhard,
+    Aces,
+}
+
+impl Tonemap {
+    pub fn next(self) -> Self {
+        match self {
+            Self::None => Self::Reinhard,
+            Self::Reinhard => Self::Aces,
+            Self::Aces => Self::None,
+        }
+    }
+
+    fn as_u32(self) -> u32 {
+        match self {
+            Self::None => 0,
+            Self::Reinhard => 1,
+            Self::Aces => 2,
+        }
+    }
+}
+
+impl Display for Tonemap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::None => "none",
+            Self::Reinhard => "reinhard",
+            Self::Aces => "aces",
+        })
+    }
+}
+
+/// How the linear color need to be encoded before being written to the output texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferFunction {
+    /// The output texture expect linear values, either because it is a float texture
+    /// or because the GPU will apply the sRGB encoding when writing to it.
+    Linear,
+    /// The output texture is a `unorm` texture without the sRGB suffix,
+    /// we need to apply the sRGB encoding ourself.
+    Srgb,
+}
+
+impl TransferFunction {
+    pub fn for_format(format: wgpu::TextureFormat) -> Self {
+        match format {
+            wgpu::TextureFormat::Rgba16Float | wgpu::TextureFormat::Rgba32Float => Self::Linear,
+            format if format.is_srgb() => Self::Linear,
+            _ => Self::Srgb,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct TonemapUniform {
+    mode: u32,
+    encode_srgb: u32,
+    render_scale: f32,
+    pad: u32,
+}
+
+/// The offscreen HDR texture the toy render to, with the bind group used to read it in the tonemap pass.
+pub struct HdrTarget {
+    _texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    bind_group: wgpu::BindGroup,
+}
+
+/// Pass that read the [`HdrTarget`] and write the tonemapped color to the output texture.
+///
+/// The target can be smaller or bigger than the output by the render scale,
+/// it is then interpolated to the size of the output.
+///
+/// The scale is lowered when the target wouldn't fit in the biggest texture of the GPU.
+pub struct TonemapPass {
+    tonemap: Tonemap,
+    transfer_function: TransferFunction,
+    /// The scale asked by the user.
+    asked_scale: f32,
+    /// The scale used for the current output, at most `asked_scale`.
+    render_scale: f32,
+    max_size: u32,
+    uniform_buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl TonemapPass {
+    pub fn new(
+        device: &wgpu::Device,
+        output_format: wgpu::TextureFormat,
+        tonemap: Tonemap,
+        render_scale: f32,
+    ) -> Self {
+        let transfer_function = TransferFunction::for_format(output_format);
+
+        log::info!(
+            "Output format `{output_format:?}` use the transfer function {transfer_function:?}"
+        );
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("tonemap-uniform-buffer"),
+            contents: bytemuck::bytes_of(&uniform(tonemap, transfer_function, render_scale)),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("tonemap-bind-group-layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline = build_pipeline(device, &bind_group_layout, output_format);
+
+        Self {
+            tonemap,
+            transfer_function,
+            asked_scale: render_scale,
+            render_scale,
+            max_size: device.limits().max_texture_dimension_2d,
+            uniform_buffer,
+            bind_group_layout,
+            pipeline,
+        }
+    }
+
+    pub fn tonemap(&self) -> Tonemap {
+        self.tonemap
+    }
+
+    pub fn set_tonemap(&mut self, queue: &wgpu::Queue, tonemap: Tonemap) {
+        self.tonemap = tonemap;
+        self.write_uniform(queue);
+    }
+
+    /// The scale used for the current output.
+    pub fn render_scale(&self) -> f32 {
+        self.render_scale
+    }
+
+    /// Fit the render scale to an output of `width` x `height`, return the size of its target.
+    pub fn resize(&mut self, queue: &wgpu::Queue, width: u32, height: u32) -> [u32; 2] {
+        let scale = fitted_scale(width, height, self.asked_scale, self.max_size);
+        if scale != self.render_scale {
+            if scale < self.asked_scale {
+                log::warn!(
+                    "The render scale is lowered to {scale} for a {width}x{height} output, \
+                    the GPU textures can't be bigger than {0}x{0}",
+                    self.max_size
+                );
+            }
+            self.render_scale = scale;
+            self.write_uniform(queue);
+        }
+        self.render_size(width, height)
+    }
+
+    /// Size of the target rendered for an output of `width` x `height`.
+    pub fn render_size(&self, width: u32, height: u32) -> [u32; 2] {
+        render_size(width, height, self.render_scale, self.max_size)
+    }
+
+    fn write_uniform(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::bytes_of(&uniform(
+                self.tonemap,
+                self.transfer_function,
+                self.render_scale,
+            )),
+        );
+    }
+
+    pub fn create_target(&self, device: &wgpu::Device, width: u32, height: u32) -> HdrTarget {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("hdr-texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: HDR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("tonemap-bind-group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        HdrTarget {
+            _texture: texture,
+            view,
+            bind_group,
+        }
+    }
+
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &HdrTarget,
+        output: &wgpu::TextureView,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("tonemap-pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &target.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+/// The highest scale up to `scale` keeping the target of a `width` x `height` output
+/// within `max_size`.
+fn fitted_scale(width: u32, height: u32, scale: f32, max_size: u32) -> f32 {
+    scale.min(max_size as f32 / width.max(height) as f32)
+}
+
+fn render_size(width: u32, height: u32, scale: f32, max_size: u32) -> [u32; 2] {
+    [width, height].map(|size| ((size as f32 * scale).ceil() as u32).clamp(1, max_size))
+}
+
+fn uniform(
+    tonemap: Tonemap,
+    transfer_function: TransferFunction,
+    render_scale: f32,
+) -> TonemapUniform {
+    TonemapUniform {
+        mode: tonemap.as_u32(),
+        encode_srgb: (transfer_function == TransferFunction::Srgb) as u32,
+        render_scale,
+        ..Default::default()
+    }
+}
+
+fn build_pipeline(
+    device: &wgpu::Device,
+    bind_group_layout: &wgpu::BindGroupLayout,
+    output_format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    let vs_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("tonemap-vertex-shader"),
+        source: wgpu::ShaderSource::Wgsl(Cow::from(include_str!("assets/vertex.wgsl"))),
+    });
+
+    let fs_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("tonemap-fragment-shader"),
+        source: wgpu::ShaderSource::Wgsl(Cow::from(include_str!("assets/tonemap.wgsl"))),
+    });
+
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("tonemap-pipeline-layout"),
+        bind_group_layouts: &[bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("tonemap-pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &vs_module,
+            entry_point: "main",
+            buffers: &[],
+        },
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            unclipped_depth: false,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &fs_module,
+            entry_point: "main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format: output_format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        multiview: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fitted_scale, render_size, Tonemap, TransferFunction};
+
+    #[test]
+    fn cycle_tonemap() {
+        let tonemap = Tonemap::default();
+
+        assert_eq!(tonemap, Tonemap::None);
+        assert_eq!(tonemap.next(), Tonemap::Reinhard);
+        assert_eq!(tonemap.next().next(), Tonemap::Aces);
+        assert_eq!(tonemap.next().next().next(), Tonemap::None);
+    }
+
+    #[test]
+    fn fit_render_scale() {
+        assert_eq!(fitted_scale(800, 600, 4., 8192), 4.);
+        assert_eq!(render_size(800, 600, 4., 8192), [3200, 2400]);
+
+        let scale = fitted_scale(2560, 1440, 4., 8192);
+        assert_eq!(scale, 3.2);
+        assert_eq!(render_size(2560, 1440, scale, 8192), [8192, 4608]);
+        assert_eq!(render_size(2560, 1440, 4., 8192), [8192, 5760]);
+
+        assert_eq!(render_size(1, 1, 0.25, 8192), [1, 1]);
+    }
+
+    #[test]
+    fn transfer_function() {
+        use wgpu::TextureFormat;
+
+        assert_eq!(
+            TransferFunction::for_format(TextureFormat::Bgra8UnormSrgb),
+            TransferFunction::Linear
+        );
+        assert_eq!(
+            TransferFunction::for_format(TextureFormat::Rgba16Float),
+            TransferFunction::Linear
+        );
+        assert_eq!(
+            TransferFunction::for_format(TextureFormat::Bgra8Unorm),
+            TransferFunction::Srgb
+        );
+        assert_eq!(
+            TransferFunction::for_format(TextureFormat::Rgb10a2Unorm),
+            TransferFunction::Srgb
+        );
+    }
+}
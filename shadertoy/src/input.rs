@@ -0,0 +1,93 @@
+use winit::event::{
+    ElementState, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta, VirtualKeyCode,
+    WindowEvent,
+};
+
+/// An event given to `State::input`, with only the fields used by the viewer.
+///
+/// The live events are converted from the window events, the replayed ones read from a recording.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum InputEvent {
+    Key {
+        key: VirtualKeyCode,
+        state: ElementState,
+    },
+    Character {
+        character: char,
+    },
+    Modifiers {
+        modifiers: ModifiersState,
+    },
+    CursorMoved {
+        position: [f64; 2],
+    },
+    MouseButton {
+        button: MouseButton,
+        state: ElementState,
+    },
+    MouseWheel {
+        delta: MouseScrollDelta,
+    },
+}
+
+impl InputEvent {
+    /// Return `None` for the events ignored by the viewer.
+    pub fn from_window_event(event: &WindowEvent) -> Option<Self> {
+        let event = match event {
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        virtual_keycode: Some(key),
+                        state,
+                        ..
+                    },
+                ..
+            } => Self::Key {
+                key: *key,
+                state: *state,
+            },
+            WindowEvent::ReceivedCharacter(character) => Self::Character {
+                character: *character,
+            },
+            WindowEvent::ModifiersChanged(modifiers) => Self::Modifiers {
+                modifiers: *modifiers,
+            },
+            WindowEvent::CursorMoved { position, .. } => Self::CursorMoved {
+                position: [position.x, position.y],
+            },
+            WindowEvent::MouseInput { button, state, .. } => Self::MouseButton {
+                button: *button,
+                state: *state,
+            },
+            WindowEvent::MouseWheel { delta, .. } => Self::MouseWheel { delta: *delta },
+            _ => return None,
+        };
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::event::{ModifiersState, WindowEvent};
+
+    use super::InputEvent;
+
+    #[test]
+    fn from_window_event() {
+        assert_eq!(
+            InputEvent::from_window_event(&WindowEvent::ReceivedCharacter('/')),
+            Some(InputEvent::Character { character: '/' })
+        );
+        assert_eq!(
+            InputEvent::from_window_event(&WindowEvent::ModifiersChanged(ModifiersState::CTRL)),
+            Some(InputEvent::Modifiers {
+                modifiers: ModifiersState::CTRL
+            })
+        );
+        assert_eq!(
+            InputEvent::from_window_event(&WindowEvent::Focused(true)),
+            None
+        );
+    }
+}
@@ -0,0 +1,333 @@
+use winit::event::VirtualKeyCode;
+
+use crate::post::Effect;
+
+/// What a key does in the viewer.
+///
+/// Some actions do the opposite with `Shift`, e.g. `Shift+G` select the previous favourite.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    PreviousToy,
+    NextToy,
+    PreviousCategory,
+    NextCategory,
+    Tonemap,
+    ToggleEffect(Effect),
+    /// Stack the current toy as a layer, `Shift` removes the selected or the top layer.
+    Layer,
+    /// Select the next layer to control, `Shift` the previous one, the toy being before the first.
+    SelectLayer,
+    Blend,
+    DecreaseOpacity,
+    IncreaseOpacity,
+    /// Show the frame statistics in the title bar, there is no overlay over the toy.
+    Stats,
+    ExportStats,
+    /// `Shift` saves a high resolution screenshot.
+    Screenshot,
+    Favourite,
+    /// `Shift` selects the previous favourite.
+    NextFavourite,
+    /// `Shift` selects the previous variant.
+    NextVariant,
+    Pause,
+    PanUp,
+    PanLeft,
+    PanDown,
+    PanRight,
+    RotateLeft,
+    RotateRight,
+    ZoomIn,
+    ZoomOut,
+    ResetView,
+}
+
+/// The keys bound to each action, set in the `[keys]` table of the config.
+///
+/// `Escape`, `/` and the digits, of the numpad too, are reserved to quit and to open the prompt.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Keys {
+    pub previous_toy: Vec<VirtualKeyCode>,
+    pub next_toy: Vec<VirtualKeyCode>,
+    pub previous_category: Vec<VirtualKeyCode>,
+    pub next_category: Vec<VirtualKeyCode>,
+    pub tonemap: Vec<VirtualKeyCode>,
+    /// One key per effect, toggling them in the order of [`Effect::ALL`].
+    pub effects: Vec<VirtualKeyCode>,
+    pub layer: Vec<VirtualKeyCode>,
+    pub select_layer: Vec<VirtualKeyCode>,
+    pub blend: Vec<VirtualKeyCode>,
+    pub decrease_opacity: Vec<VirtualKeyCode>,
+    pub increase_opacity: Vec<VirtualKeyCode>,
+    pub stats: Vec<VirtualKeyCode>,
+    pub export_stats: Vec<VirtualKeyCode>,
+    pub screenshot: Vec<VirtualKeyCode>,
+    pub favourite: Vec<VirtualKeyCode>,
+    pub next_favourite: Vec<VirtualKeyCode>,
+    pub next_variant: Vec<VirtualKeyCode>,
+    pub pause: Vec<VirtualKeyCode>,
+    pub pan_up: Vec<VirtualKeyCode>,
+    pub pan_left: Vec<VirtualKeyCode>,
+    pub pan_down: Vec<VirtualKeyCode>,
+    pub pan_right: Vec<VirtualKeyCode>,
+    pub rotate_left: Vec<VirtualKeyCode>,
+    pub rotate_right: Vec<VirtualKeyCode>,
+    pub zoom_in: Vec<VirtualKeyCode>,
+    pub zoom_out: Vec<VirtualKeyCode>,
+    pub reset_view: Vec<VirtualKeyCode>,
+}
+
+impl Default for Keys {
+    fn default() -> Self {
+        use VirtualKeyCode as Key;
+
+        Self {
+            previous_toy: vec![Key::Left],
+            next_toy: vec![Key::Right],
+            previous_category: vec![Key::Up],
+            next_category: vec![Key::Down],
+            tonemap: vec![Key::T],
+            effects: vec![Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6],
+            layer: vec![Key::L],
+            select_layer: vec![Key::Tab],
+            blend: vec![Key::B],
+            decrease_opacity: vec![Key::LBracket],
+            increase_opacity: vec![Key::RBracket],
+            stats: vec![Key::I],
+            export_stats: vec![Key::C],
+            screenshot: vec![Key::P],
+            favourite: vec![Key::F],
+            next_favourite: vec![Key::G],
+            next_variant: vec![Key::V],
+            pause: vec![Key::Space],
+            pan_up: vec![Key::W],
+            pan_left: vec![Key::A],
+            pan_down: vec![Key::S],
+            pan_right: vec![Key::D],
+            rotate_left: vec![Key::Q],
+            rotate_right: vec![Key::E],
+            zoom_in: vec![Key::Equals, Key::Plus, Key::NumpadAdd],
+            zoom_out: vec![Key::Minus, Key::NumpadSubtract],
+            reset_view: vec![Key::R],
+        }
+    }
+}
+
+impl Keys {
+    /// The action bound to `key`, if any.
+    pub fn action(&self, key: VirtualKeyCode) -> Option<Action> {
+        self.bindings()
+            .into_iter()
+            .find(|(_, _, keys)| keys.contains(&key))
+            .map(|(_, action, _)| action)
+    }
+
+    /// The first key bound to `action`, to show in the hints.
+    pub fn key(&self, action: Action) -> Option<VirtualKeyCode> {
+        self.bindings()
+            .into_iter()
+            .find(|(_, other, _)| *other == action)
+            .and_then(|(_, _, keys)| keys.first().copied())
+    }
+
+    /// `, press <key> <purpose>` with the key bound to `action`, nothing if it's unbound.
+    pub fn hint(&self, action: Action, purpose: &str) -> String {
+        self.key(action)
+            .map(|key| format!(", press {key:?} {purpose}"))
+            .unwrap_or_default()
+    }
+
+    /// Check that every key does a single thing.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.effects.len() <= Effect::ALL.len(),
+            "There are {} effects to toggle but {} keys are bound to them",
+            Effect::ALL.len(),
+            self.effects.len()
+        );
+
+        let bindings = self.bindings();
+        for (index, (name, _, keys)) in bindings.iter().enumerate() {
+            for key in *keys {
+                anyhow::ensure!(
+                    *key != VirtualKeyCode::Escape,
+                    "The key `Escape` of `{name}` is reserved to quit"
+                );
+                anyhow::ensure!(
+                    !opens_prompt(*key),
+                    "The key `{key:?}` of `{name}` is reserved to open the prompt"
+                );
+                if let Some((other, _, _)) = bindings[..index]
+                    .iter()
+                    .find(|(_, _, keys)| keys.contains(key))
+                {
+                    anyhow::bail!("The key `{key:?}` is bound to both `{other}` and `{name}`");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The name of each action in the config, the action and its keys.
+    fn bindings(&self) -> Vec<(&'static str, Action, &[VirtualKeyCode])> {
+        let mut bindings = vec![
+            ("previous-toy", Action::PreviousToy, &self.previous_toy[..]),
+            ("next-toy", Action::NextToy, &self.next_toy),
+            (
+                "previous-category",
+                Action::PreviousCategory,
+                &self.previous_category,
+            ),
+            ("next-category", Action::NextCategory, &self.next_category),
+            ("tonemap", Action::Tonemap, &self.tonemap),
+            ("layer", Action::Layer, &self.layer),
+            ("select-layer", Action::SelectLayer, &self.select_layer),
+            ("blend", Action::Blend, &self.blend),
+            (
+                "decrease-opacity",
+                Action::DecreaseOpacity,
+                &self.decrease_opacity,
+            ),
+            (
+                "increase-opacity",
+                Action::IncreaseOpacity,
+                &self.increase_opacity,
+            ),
+            ("stats", Action::Stats, &self.stats),
+            ("export-stats", Action::ExportStats, &self.export_stats),
+            ("screenshot", Action::Screenshot, &self.screenshot),
+            ("favourite", Action::Favourite, &self.favourite),
+            (
+                "next-favourite",
+                Action::NextFavourite,
+                &self.next_favourite,
+            ),
+            ("next-variant", Action::NextVariant, &self.next_variant),
+            ("pause", Action::Pause, &self.pause),
+            ("pan-up", Action::PanUp, &self.pan_up),
+            ("pan-left", Action::PanLeft, &self.pan_left),
+            ("pan-down", Action::PanDown, &self.pan_down),
+            ("pan-right", Action::PanRight, &self.pan_right),
+            ("rotate-left", Action::RotateLeft, &self.rotate_left),
+            ("rotate-right", Action::RotateRight, &self.rotate_right),
+            ("zoom-in", Action::ZoomIn, &self.zoom_in),
+            ("zoom-out", Action::ZoomOut, &self.zoom_out),
+            ("reset-view", Action::ResetView, &self.reset_view),
+        ];
+        bindings.extend(
+            Effect::ALL
+                .into_iter()
+                .zip(&self.effects)
+                .map(|(effect, key)| {
+                    (
+                        "effects",
+                        Action::ToggleEffect(effect),
+                        std::slice::from_ref(key),
+                    )
+                }),
+        );
+        bindings
+    }
+}
+
+/// Whether `key` types `/` or a digit, which open the prompt.
+fn opens_prompt(key: VirtualKeyCode) -> bool {
+    use VirtualKeyCode as Key;
+
+    matches!(
+        key,
+        Key::Slash
+            | Key::NumpadDivide
+            | Key::Key0
+            | Key::Key1
+            | Key::Key2
+            | Key::Key3
+            | Key::Key4
+            | Key::Key5
+            | Key::Key6
+            | Key::Key7
+            | Key::Key8
+            | Key::Key9
+            | Key::Numpad0
+            | Key::Numpad1
+            | Key::Numpad2
+            | Key::Numpad3
+            | Key::Numpad4
+            | Key::Numpad5
+            | Key::Numpad6
+            | Key::Numpad7
+            | Key::Numpad8
+            | Key::Numpad9
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::event::VirtualKeyCode as Key;
+
+    use super::{Action, Keys};
+    use crate::post::Effect;
+
+    #[test]
+    fn default_keys() {
+        let keys = Keys::default();
+
+        keys.validate().unwrap();
+        assert_eq!(keys.action(Key::Right), Some(Action::NextToy));
+        assert_eq!(keys.action(Key::NumpadAdd), Some(Action::ZoomIn));
+        assert_eq!(
+            keys.action(Key::F2),
+            Some(Action::ToggleEffect(Effect::Bloom))
+        );
+        assert_eq!(keys.action(Key::Z), None);
+    }
+
+    #[test]
+    fn invalid_keys() {
+        let mut keys = Keys {
+            pause: vec![Key::P],
+            ..Keys::default()
+        };
+        assert_eq!(
+            keys.validate().unwrap_err().to_string(),
+            "The key `P` is bound to both `screenshot` and `pause`"
+        );
+
+        keys.screenshot = vec![Key::Escape];
+        assert!(keys.validate().is_err());
+
+        keys = Keys::default();
+        keys.effects.push(Key::F7);
+        assert!(keys.validate().is_err());
+    }
+
+    #[test]
+    fn prompt_keys() {
+        for key in [Key::Key1, Key::Numpad0, Key::Slash, Key::NumpadDivide] {
+            let keys = Keys {
+                next_toy: vec![key],
+                ..Keys::default()
+            };
+            assert_eq!(
+                keys.validate().unwrap_err().to_string(),
+                format!("The key `{key:?}` of `next-toy` is reserved to open the prompt")
+            );
+        }
+    }
+
+    #[test]
+    fn hints() {
+        let mut keys = Keys::default();
+        assert_eq!(keys.key(Action::ZoomIn), Some(Key::Equals));
+        assert_eq!(
+            keys.hint(Action::Layer, "to stack the current toy"),
+            ", press L to stack the current toy"
+        );
+
+        keys.layer = vec![Key::K];
+        assert_eq!(keys.hint(Action::Layer, "to stack"), ", press K to stack");
+        keys.layer.clear();
+        assert_eq!(keys.hint(Action::Layer, "to stack"), "");
+    }
+}
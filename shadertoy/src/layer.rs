@@ -0,0 +1,537 @@
+use std::{borrow::Cow, fmt::Display, sync::Arc};
+
+use wgpu::util::DeviceExt;
+
+use crate::{
+    hdr::HDR_FORMAT,
+    pipeline::{self, ToyPipeline},
+    post::PostTargets,
+    uniform::Uniform,
+    view::View,
+};
+
+/// Number of toys that can be stacked over the current one.
+pub const MAX_LAYERS: usize = 4;
+
+/// How a layer is combined with the layers and the toy under it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BlendMode {
+    /// Cover the toy under it, following the alpha of the layer.
+    #[default]
+    Alpha,
+    Add,
+    Multiply,
+    /// Brighten the toy under it, expect colors between `0` and `1`.
+    Screen,
+}
+
+impl BlendMode {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Alpha => Self::Add,
+            Self::Add => Self::Multiply,
+            Self::Multiply => Self::Screen,
+            Self::Screen => Self::Alpha,
+        }
+    }
+
+    fn as_u32(self) -> u32 {
+        match self {
+            Self::Alpha => 0,
+            Self::Add => 1,
+            Self::Multiply => 2,
+            Self::Screen => 3,
+        }
+    }
+
+    /// The color output by `composite.wgsl` is the source, the toy under the layer the destination.
+    fn blend_state(self) -> wgpu::BlendState {
+        let color = match self {
+            Self::Alpha => wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::SrcAlpha,
+                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                operation: wgpu::BlendOperation::Add,
+            },
+            Self::Add => wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+            Self::Multiply => wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::Dst,
+                dst_factor: wgpu::BlendFactor::Zero,
+                operation: wgpu::BlendOperation::Add,
+            },
+            Self::Screen => wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::OneMinusDst,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+        };
+        // The alpha of the toy is kept.
+        let alpha = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::Zero,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        wgpu::BlendState { color, alpha }
+    }
+}
+
+impl Display for BlendMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Alpha => "alpha",
+            Self::Add => "add",
+            Self::Multiply => "multiply",
+            Self::Screen => "screen",
+        })
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct CompositeUniform {
+    opacity: f32,
+    mode: u32,
+    pad: [u32; 2],
+}
+
+/// A toy rendered over the current one, with its own uniforms.
+///
+/// The pipeline, with the textures and the streams of its channels, is shared with the toy
+/// and the other layers showing the same toy, see [`LayerStack::update_channels`].
+pub struct Layer {
+    name: String,
+    pipeline: Arc<ToyPipeline>,
+    /// The time, the resolution and the parameters of the layer, the view is applied when
+    /// rendering and the frame fields are the ones of the target rendered to.
+    uniform: Uniform,
+    view: View,
+    speed: f32,
+    paused: bool,
+    blend: BlendMode,
+    opacity: f32,
+    uniform_buffer: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+    composite_buffer: wgpu::Buffer,
+    composite_bind_group: wgpu::BindGroup,
+}
+
+impl Layer {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn blend(&self) -> BlendMode {
+        self.blend
+    }
+
+    pub fn opacity(&self) -> f32 {
+        self.opacity
+    }
+
+    pub fn view(&self) -> &View {
+        &self.view
+    }
+
+    pub fn view_mut(&mut self) -> &mut View {
+        &mut self.view
+    }
+
+    pub fn set_blend(&mut self, blend: BlendMode) {
+        self.blend = blend;
+    }
+
+    /// Set the opacity, between `0` and `1`.
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.opacity = opacity.clamp(0., 1.);
+    }
+
+    pub fn time(&self) -> f32 {
+        self.uniform.time
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// Set the parameters of the toy, from OSC.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_params(&mut self, params: [f32; 8]) {
+        self.uniform.params = params;
+    }
+
+    pub fn pipeline(&self) -> &Arc<ToyPipeline> {
+        &self.pipeline
+    }
+
+    fn composite_uniform(&self) -> CompositeUniform {
+        CompositeUniform {
+            opacity: self.opacity,
+            mode: self.blend.as_u32(),
+            ..Default::default()
+        }
+    }
+}
+
+/// The toys stacked over the current one, like the layers of a video mixer.
+///
+/// Each layer is rendered to the scratch texture of the [`PostTargets`], then composited over the
+/// toy before the effects are applied. The layers have their own time, which advances with the
+/// clock of the toy so the replays show the same frames, their own parameters and view.
+///
+/// The controls apply to the selected layer, or to the toy when none is.
+pub struct LayerStack {
+    layers: Vec<Layer>,
+    selected: Option<usize>,
+    queue: Arc<wgpu::Queue>,
+    composite_layout: wgpu::BindGroupLayout,
+    /// One per blend mode, in the order of [`BlendMode::as_u32`].
+    pipelines: Vec<wgpu::RenderPipeline>,
+}
+
+impl LayerStack {
+    /// `texture_layout` is the layout of the bind groups reading the [`PostTargets`].
+    pub fn new(
+        device: &wgpu::Device,
+        queue: Arc<wgpu::Queue>,
+        texture_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let composite_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("composite-bind-group-layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let pipelines = [
+            BlendMode::Alpha,
+            BlendMode::Add,
+            BlendMode::Multiply,
+            BlendMode::Screen,
+        ]
+        .iter()
+        .map(|blend| build_pipeline(device, &composite_layout, texture_layout, *blend))
+        .collect();
+
+        Self {
+            layers: Vec::new(),
+            selected: None,
+            queue,
+            composite_layout,
+            pipelines,
+        }
+    }
+
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    /// Stack a toy over the others, starting from the state of `uniform`.
+    pub fn push(
+        &mut self,
+        device: &wgpu::Device,
+        uniform_layout: &wgpu::BindGroupLayout,
+        name: &str,
+        pipeline: Arc<ToyPipeline>,
+        uniform: Uniform,
+        view: View,
+    ) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.layers.len() < MAX_LAYERS,
+            "There can't be more than {MAX_LAYERS} layers"
+        );
+
+        let (uniform_buffer, uniform_bind_group) =
+            crate::uniform::create_uniform_buffer(device, uniform_layout);
+        let composite_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("composite-uniform-buffer"),
+            contents: bytemuck::bytes_of(&CompositeUniform::default()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let composite_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("composite-bind-group"),
+            layout: &self.composite_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: composite_buffer.as_entire_binding(),
+            }],
+        });
+
+        let layer = Layer {
+            name: name.to_string(),
+            pipeline,
+            uniform,
+            view,
+            speed: 1.,
+            paused: false,
+            blend: BlendMode::default(),
+            opacity: 0.5,
+            uniform_buffer,
+            uniform_bind_group,
+            composite_buffer,
+            composite_bind_group,
+        };
+        self.layers.push(layer);
+        Ok(())
+    }
+
+    /// Remove the selected layer, or the top one when the toy is selected.
+    pub fn remove(&mut self) -> Option<Layer> {
+        let index = self.selected.take().or(self.layers.len().checked_sub(1))?;
+        Some(self.layers.remove(index))
+    }
+
+    /// The index of the selected layer from the bottom, `None` when the toy is selected.
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    pub fn selected_mut(&mut self) -> Option<&mut Layer> {
+        self.layers.get_mut(self.selected?)
+    }
+
+    /// Select the layer `index` from the bottom, or the toy.
+    pub fn select(&mut self, index: Option<usize>) -> anyhow::Result<()> {
+        if let Some(index) = index {
+            anyhow::ensure!(
+                index < self.layers.len(),
+                "There are only {} layers",
+                self.layers.len()
+            );
+        }
+        self.selected = index;
+        Ok(())
+    }
+
+    /// Select the layer above the selected one, or the one under it when `backward`,
+    /// going through the toy at both ends.
+    pub fn select_next(&mut self, backward: bool) {
+        self.selected = cycle(self.selected, self.layers.len(), backward);
+    }
+
+    /// Change the blend mode of the selected layer, or of the top one, to the next one.
+    pub fn cycle_blend(&mut self) {
+        if let Some(layer) = self.controlled() {
+            layer.blend = layer.blend.next();
+        }
+    }
+
+    /// Add `delta` to the opacity of the selected layer, or of the top one.
+    pub fn add_opacity(&mut self, delta: f32) {
+        if let Some(layer) = self.controlled() {
+            layer.set_opacity(layer.opacity + delta);
+        }
+    }
+
+    /// The layer changed by the blend and opacity keys.
+    fn controlled(&mut self) -> Option<&mut Layer> {
+        let index = self.selected.or(self.layers.len().checked_sub(1))?;
+        self.layers.get_mut(index)
+    }
+
+    /// Advance the time of the layers by `delta` seconds of the clock of the toy.
+    pub fn advance(&mut self, delta: f32) {
+        for layer in &mut self.layers {
+            if !layer.paused {
+                layer.uniform.time += delta * layer.speed;
+            }
+        }
+    }
+
+    /// See [`ToyPipeline::update_channels`], at the time of each layer.
+    ///
+    /// A pipeline has a single frame per streamed channel, so a pipeline shared with `toy` keeps
+    /// the time of the toy, updated by the caller, and one shared by several layers the time of
+    /// the lowest of them. Updating its streams at each time would seek the videos back and forth
+    /// on every frame.
+    pub fn update_channels(&self, encoder: &mut wgpu::CommandEncoder, toy: &Arc<ToyPipeline>) {
+        let mut updated = vec![toy];
+        for layer in &self.layers {
+            if !updated
+                .iter()
+                .any(|pipeline| Arc::ptr_eq(pipeline, &layer.pipeline))
+            {
+                layer.pipeline.update_channels(encoder, layer.uniform.time);
+                updated.push(&layer.pipeline);
+            }
+        }
+    }
+
+    /// See [`ToyPipeline::channels_submitted`].
+    pub fn channels_submitted(&self) {
+        for layer in &self.layers {
+            layer.pipeline.channels_submitted();
+        }
+    }
+
+    /// Render the layers over the input of `targets`, from the bottom to the top.
+    ///
+    /// The resolution, the scale and the offset of the layers are the ones of `frame`, the frame
+    /// rendered by the window or the tile of a screenshot. The uniforms are written to the queue
+    /// so the encoder must be submitted before the next call.
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        frame: &Uniform,
+        targets: &PostTargets,
+    ) {
+        let (scratch, scratch_bind_group) = targets.scratch();
+
+        for layer in &self.layers {
+            let mut uniform = layer.uniform;
+            uniform.resolution = frame.resolution;
+            uniform.scale = frame.scale;
+            uniform.offset = frame.offset;
+            uniform.target_resolution = frame.target_resolution;
+            layer.view.update_uniform(&mut uniform);
+            uniform.vertex_count = layer.pipeline.geometry().vertex_count() as f32;
+            self.queue
+                .write_buffer(&layer.uniform_buffer, 0, uniform.as_ref());
+            self.queue.write_buffer(
+                &layer.composite_buffer,
+                0,
+                bytemuck::bytes_of(&layer.composite_uniform()),
+            );
+
+            pipeline::render_toy(
+                encoder,
+                &layer.pipeline,
+                &layer.uniform_bind_group,
+                &scratch.view,
+            );
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("composite-pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &targets.input.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_pipeline(&self.pipelines[layer.blend.as_u32() as usize]);
+            render_pass.set_bind_group(0, &layer.composite_bind_group, &[]);
+            render_pass.set_bind_group(1, scratch_bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+}
+
+/// The selection after `selected` in a stack of `count` layers, `None` being the toy under them.
+fn cycle(selected: Option<usize>, count: usize, backward: bool) -> Option<usize> {
+    match (selected, backward) {
+        (None, false) => (count > 0).then_some(0),
+        (None, true) => count.checked_sub(1),
+        (Some(index), false) => (index + 1 < count).then_some(index + 1),
+        (Some(index), true) => index.checked_sub(1),
+    }
+}
+
+fn build_pipeline(
+    device: &wgpu::Device,
+    composite_layout: &wgpu::BindGroupLayout,
+    texture_layout: &wgpu::BindGroupLayout,
+    blend: BlendMode,
+) -> wgpu::RenderPipeline {
+    let vs_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("composite-vertex-shader"),
+        source: wgpu::ShaderSource::Wgsl(Cow::from(include_str!("assets/vertex.wgsl"))),
+    });
+
+    let fs_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("composite-fragment-shader"),
+        source: wgpu::ShaderSource::Wgsl(Cow::from(include_str!("assets/composite.wgsl"))),
+    });
+
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("composite-pipeline-layout"),
+        bind_group_layouts: &[composite_layout, texture_layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(&format!("composite-{blend}-pipeline")),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &vs_module,
+            entry_point: "main",
+            buffers: &[],
+        },
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            unclipped_depth: false,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &fs_module,
+            entry_point: "main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format: HDR_FORMAT,
+                blend: Some(blend.blend_state()),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        multiview: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cycle, BlendMode};
+
+    #[test]
+    fn cycle_blend_mode() {
+        let blend = BlendMode::default();
+
+        assert_eq!(blend, BlendMode::Alpha);
+        assert_eq!(blend.next(), BlendMode::Add);
+        assert_eq!(blend.next().next(), BlendMode::Multiply);
+        assert_eq!(blend.next().next().next(), BlendMode::Screen);
+        assert_eq!(blend.next().next().next().next(), BlendMode::Alpha);
+    }
+
+    #[test]
+    fn cycle_selection() {
+        assert_eq!(cycle(None, 0, false), None);
+        assert_eq!(cycle(None, 2, false), Some(0));
+        assert_eq!(cycle(Some(0), 2, false), Some(1));
+        assert_eq!(cycle(Some(1), 2, false), None);
+        assert_eq!(cycle(None, 2, true), Some(1));
+        assert_eq!(cycle(Some(0), 2, true), None);
+    }
+}
@@ -1,59 +1,205 @@
+mod bench;
+mod channel;
+mod cli;
+mod config;
+mod gpu;
+mod hdr;
+mod input;
+mod keys;
+mod layer;
+mod lint;
+#[cfg(not(target_arch = "wasm32"))]
+mod osc;
+mod output;
+mod pipeline;
+mod post;
+mod present;
+#[cfg(not(target_arch = "wasm32"))]
+mod record;
+mod remote;
+mod screenshot;
 mod shader_list;
 mod state;
+mod stats;
+mod stream;
+mod timing;
 mod uniform;
+mod view;
+#[cfg(target_arch = "wasm32")]
+mod web;
 
+use cfg_if::cfg_if;
+use instant::Instant;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::wasm_bindgen;
 use winit::{
+    dpi::PhysicalSize,
     event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
-    event_loop::{ControlFlow, EventLoop},
+    event_loop::{ControlFlow, EventLoopBuilder},
     window::WindowBuilder,
 };
 
+pub use cli::{Command, Options};
+use config::Config;
+use input::InputEvent;
+use present::FrameLimiter;
+use remote::RemoteEvent;
 use state::State;
 
 include!(concat!(env!("OUT_DIR"), "/toy.rs"));
 
-pub async fn run() {
-    env_logger::init();
+/// Entry point of the web build, the options are read from the query string of the page.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(start)]
+pub async fn start() {
+    run(web::options_from_url()).await
+}
+
+pub async fn run(options: Options) {
+    cfg_if! {
+        if #[cfg(target_arch = "wasm32")] {
+            std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+            console_log::init_with_level(log::Level::Info).expect("Couldn't initialize logger");
+        } else {
+            env_logger::init();
+        }
+    }
 
-    let shader_list = shader_list::ShaderList::new(
+    let mut shader_list = shader_list::ShaderList::new(
         include_str!("assets/fragment.prefix.wgsl"),
         include_str!("assets/fragment.suffix.wgsl"),
         SHADERS,
-    );
+    )
+    .with_vertex_suffix(include_str!("assets/vertex.suffix.wgsl"));
+
+    if let Some(command) = options.command {
+        let result = match command {
+            Command::Bench(bench_options) => bench::run(shader_list, bench_options).await,
+            Command::Stats(stats_options) => stats::run(&shader_list, stats_options),
+            Command::Lint(lint_options) => lint::run(&shader_list, lint_options),
+        };
+        if let Err(e) = result {
+            eprintln!("{e:#}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let config = match Config::load_user(&options.overridden_config()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{e:#}");
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(toy) = &options.toy {
+        match shader_list.search(toy).first() {
+            Some(&index) => {
+                let variant = shader_list.matching_variant(index, toy);
+                shader_list.select_variant(index, variant);
+            }
+            None => log::warn!("No toy match `{toy}`"),
+        }
+    } else if let Some(toy) = &config.toy {
+        match shader_list.position(toy) {
+            Some(index) => {
+                shader_list.select(index);
+            }
+            None => log::warn!("The last toy `{toy}` doesn't exist anymore"),
+        }
+    }
 
-    let event_loop = EventLoop::new();
-    let window = WindowBuilder::new()
+    let event_loop = EventLoopBuilder::<RemoteEvent>::with_user_event().build();
+    let mut window_builder = WindowBuilder::new();
+    if let Some(size) = options.window_size.or(config.window) {
+        window_builder = window_builder.with_inner_size(PhysicalSize::new(size.width, size.height));
+    }
+    let window = window_builder
         .build(&event_loop)
         .expect("Failed to build the window");
 
-    let mut state = State::new(window, shader_list)
+    #[cfg(target_arch = "wasm32")]
+    web::attach_canvas(&window).expect("Couldn't attach the canvas to the page");
+
+    cfg_if! {
+        if #[cfg(target_arch = "wasm32")] {
+            let output_window = None;
+        } else {
+            let output_window = options
+                .output_window
+                .then(|| output::build_window(&event_loop, options.output_monitor))
+                .transpose()
+                .expect("Failed to build the output window");
+        }
+    }
+
+    let mut state = State::new(window, output_window, shader_list, &options, config)
         .await
         .expect("Can't create state");
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(port) = options.remote_port {
+        match remote::serve(("127.0.0.1", port), event_loop.create_proxy()) {
+            Ok(address) => log::info!("Remote control listening on http://{address}"),
+            Err(e) => log::error!("Failed to start the remote control: {e:#}"),
+        }
+    }
+
+    let mut frame_limiter = FrameLimiter::new(options.max_fps, Instant::now());
+
     event_loop.run(move |event, _, control_flow| match event {
         Event::WindowEvent {
             window_id,
             ref event,
-        } if window_id == state.window().id() => {
-            if !state.input(event) {
-                match event {
-                    WindowEvent::CloseRequested
-                    | WindowEvent::KeyboardInput {
-                        input:
-                            KeyboardInput {
-                                state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Escape),
-                                ..
-                            },
-                        ..
-                    } => *control_flow = ControlFlow::Exit,
-                    WindowEvent::Resized(physical_size) => state.resize(*physical_size),
-                    WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
-                        state.resize(**new_inner_size)
-                    }
-                    _ => {}
+        } if window_id == state.window().id()
+            && !InputEvent::from_window_event(event).map_or(false, |input| state.input(input)) =>
+        {
+            match event {
+                WindowEvent::CloseRequested
+                | WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::Escape),
+                            ..
+                        },
+                    ..
+                } => *control_flow = ControlFlow::Exit,
+                WindowEvent::Resized(physical_size) => state.resize(*physical_size),
+                WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                    state.resize(**new_inner_size)
                 }
+                _ => {}
             }
         }
+        Event::WindowEvent {
+            window_id,
+            ref event,
+        } if Some(window_id) == state.output_window().map(|window| window.id()) => match event {
+            WindowEvent::CloseRequested
+            | WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::Escape),
+                        ..
+                    },
+                ..
+            } => state.close_output(),
+            WindowEvent::Resized(physical_size) => state.resize_output(*physical_size),
+            WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                state.resize_output(**new_inner_size)
+            }
+            // The keyboard shortcuts work in both windows, the mouse only in the main one.
+            WindowEvent::KeyboardInput { .. }
+            | WindowEvent::ReceivedCharacter(_)
+            | WindowEvent::ModifiersChanged(_) => {
+                if let Some(input) = InputEvent::from_window_event(event) {
+                    state.input(input);
+                }
+            }
+            _ => {}
+        },
         Event::RedrawRequested(window_id) if window_id == state.window().id() => {
             state.update();
             match state.render() {
@@ -66,8 +212,26 @@ pub async fn run() {
                 Err(e) => log::warn!("Non critical render error: {e}"),
             }
         }
+        Event::UserEvent(event) => {
+            let response = state.remote(&event.request);
+            event.reply(response);
+        }
+        Event::LoopDestroyed => {
+            if let Err(e) = state.save_config() {
+                log::error!("Failed to save the config: {e:#}");
+            }
+        }
         Event::MainEventsCleared => {
-            state.window().request_redraw();
+            #[cfg(target_arch = "wasm32")]
+            web::fit_to_container(state.window());
+
+            match frame_limiter.wait_until(Instant::now()) {
+                None => {
+                    *control_flow = ControlFlow::Poll;
+                    state.window().request_redraw();
+                }
+                Some(next_frame) => *control_flow = ControlFlow::WaitUntil(next_frame),
+            }
         }
         _ => {}
     })
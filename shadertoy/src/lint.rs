@@ -0,0 +1,751 @@
+use std::{fmt, path::Path};
+
+use anyhow::Context;
+
+use crate::{
+    channel,
+    cli::LintOptions,
+    pipeline::Geometry,
+    shader_list::{self, ShaderList},
+    stats,
+};
+
+/// The function `fragment.suffix.wgsl` calls.
+const MAIN_IMAGE: EntryPoint = EntryPoint {
+    name: "main_image",
+    signature: "fn main_image(frag_coord: vec4<f32>) -> vec4<f32>",
+    param_types: &["vec4<f32>"],
+    result: "vec4<f32>",
+    called_for: "every pixel",
+};
+/// The function `vertex.suffix.wgsl` calls.
+const VERTEX_MAIN: EntryPoint = EntryPoint {
+    name: "vertex_main",
+    signature: "fn vertex_main(vertex_index: u32) -> VertexOutput",
+    param_types: &["u32"],
+    result: "VertexOutput",
+    called_for: "every vertex",
+};
+
+/// A function of the toy called by the suffix.
+struct EntryPoint {
+    name: &'static str,
+    signature: &'static str,
+    param_types: &'static [&'static str],
+    result: &'static str,
+    called_for: &'static str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Line in the toy, starting from 1, `None` if the problem isn't on a specific line.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn error(line: Option<usize>, message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            line,
+            message,
+        }
+    }
+
+    fn warning(line: Option<usize>, message: String) -> Self {
+        Self {
+            severity: Severity::Warning,
+            line,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "{line}: ")?;
+        }
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{severity}: {}", self.message)
+    }
+}
+
+/// Lint the toys given on the command line, or the bundled ones, and print the problems found.
+pub fn run(shader_list: &ShaderList, options: LintOptions) -> anyhow::Result<()> {
+    let linter = Linter::new(
+        shader_list.prefix(),
+        shader_list.suffix(),
+        shader_list.vertex_suffix(),
+    );
+
+    let toys = if options.files.is_empty() {
+        shader_list
+            .iter_toys()
+            .map(|(name, toy)| (name.to_string(), toy.to_string()))
+            .collect::<Vec<_>>()
+    } else {
+        options
+            .files
+            .iter()
+            .map(|path| read_toy(path).map(|toy| (path.display().to_string(), toy)))
+            .collect::<anyhow::Result<_>>()?
+    };
+
+    let mut errors = 0;
+    let mut warnings = 0;
+    for (name, toy) in &toys {
+        for diagnostic in linter.lint(toy) {
+            match diagnostic.severity {
+                Severity::Error => errors += 1,
+                Severity::Warning => warnings += 1,
+            }
+            println!("{name}:{diagnostic}");
+        }
+    }
+    println!(
+        "{} toys checked: {errors} errors, {warnings} warnings",
+        toys.len()
+    );
+
+    anyhow::ensure!(errors == 0, "Some toys have errors");
+    Ok(())
+}
+
+fn read_toy(path: &Path) -> anyhow::Result<String> {
+    std::fs::read_to_string(path).with_context(|| format!("Can't read {}", path.display()))
+}
+
+/// The first line of the toy matching the predicate, starting from 1.
+fn line_of(toy: &str, predicate: impl Fn(&str) -> bool) -> Option<usize> {
+    toy.lines().position(predicate).map(|index| index + 1)
+}
+
+/// Check that the toys follow the contract of the prefix and the suffix.
+pub struct Linter {
+    prefix: &'static str,
+    suffix: &'static str,
+    vertex_suffix: &'static str,
+    /// The names declared by the prefix, a toy can't declare them again.
+    prefix_names: Vec<&'static str>,
+}
+
+impl Linter {
+    pub fn new(prefix: &'static str, suffix: &'static str, vertex_suffix: &'static str) -> Self {
+        let prefix_names = parse(&tokenize(prefix))
+            .declarations
+            .iter()
+            .map(|token| token.text)
+            .collect();
+
+        Self {
+            prefix,
+            suffix,
+            vertex_suffix,
+            prefix_names,
+        }
+    }
+
+    pub fn lint(&self, toy: &str) -> Vec<Diagnostic> {
+        let tokens = tokenize(toy);
+        let source = parse(&tokens);
+        let mut diagnostics = Vec::new();
+
+        for declaration in &source.declarations {
+            if self.is_prefix_name(declaration.text) {
+                diagnostics.push(Diagnostic::error(
+                    Some(declaration.line),
+                    format!(
+                        "`{}` is already declared by the prefix, rename it",
+                        declaration.text
+                    ),
+                ));
+            }
+        }
+
+        let entry_point = match Geometry::parse(toy) {
+            Ok(Geometry::Fullscreen) => MAIN_IMAGE,
+            Ok(Geometry::Vertices { .. }) => VERTEX_MAIN,
+            Err(e) => {
+                diagnostics.push(Diagnostic::error(
+                    line_of(toy, |line| {
+                        shader_list::vertices_declaration(line).is_some()
+                    }),
+                    format!("invalid `// vertices:` comment: {e:#}"),
+                ));
+                VERTEX_MAIN
+            }
+        };
+        match source
+            .functions
+            .iter()
+            .find(|function| function.name.text == entry_point.name)
+        {
+            Some(function) if !function.has_signature(&entry_point) => {
+                diagnostics.push(Diagnostic::error(
+                    Some(function.name.line),
+                    format!(
+                        "`{}` must have the signature `{}`",
+                        entry_point.name, entry_point.signature
+                    ),
+                ))
+            }
+            Some(_) => {}
+            None => diagnostics.push(Diagnostic::error(
+                None,
+                format!(
+                    "the toy must define `{}`, it is called for {}",
+                    entry_point.signature, entry_point.called_for
+                ),
+            )),
+        }
+
+        for function in &source.functions {
+            for local in function.params.iter().chain(&function.locals) {
+                if self.is_prefix_name(local.text) {
+                    diagnostics.push(Diagnostic::warning(
+                        Some(local.line),
+                        format!(
+                            "`{}` shadows the prefix declaration of the same name",
+                            local.text
+                        ),
+                    ));
+                }
+            }
+            for param in function.unused_params() {
+                diagnostics.push(Diagnostic::warning(
+                    Some(param.line),
+                    format!(
+                        "the parameter `{}` of `{}` is never used, prefix it with `_` if it's on purpose",
+                        param.text, function.name.text
+                    ),
+                ));
+            }
+        }
+
+        for (line, channel) in channel::channels(toy) {
+            if let Err(e) = channel {
+                diagnostics.push(Diagnostic::error(
+                    Some(line),
+                    format!("invalid channel: {e:#}"),
+                ));
+            }
+        }
+
+        let overrides = shader_list::override_names(toy);
+        for variant in shader_list::variants(toy) {
+            for (name, _) in &variant.values {
+                if !overrides.contains(name) {
+                    diagnostics.push(Diagnostic::warning(
+                        None,
+                        format!(
+                            "the variant `{}` sets `{name}`, which isn't declared with `override`",
+                            variant.name
+                        ),
+                    ));
+                }
+            }
+        }
+
+        // The errors of naga would only repeat the problems found above, less clearly.
+        if !diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.severity == Severity::Error)
+        {
+            let variants = shader_list::variants(toy);
+            if variants.is_empty() {
+                diagnostics.extend(self.compile(toy, &[]));
+            }
+            for variant in variants {
+                diagnostics.extend(self.compile(toy, &variant.values).map(|mut diagnostic| {
+                    diagnostic.message =
+                        format!("in the variant `{}`: {}", variant.name, diagnostic.message);
+                    diagnostic
+                }));
+            }
+        }
+
+        diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+        diagnostics
+    }
+
+    fn is_prefix_name(&self, name: &str) -> bool {
+        self.prefix_names.contains(&name)
+    }
+
+    /// Compile a variant of the toy with naga, report its errors on the lines of the toy
+    /// and the loops without a constant bound.
+    fn compile(&self, toy: &str, values: &[(&str, &str)]) -> Option<Diagnostic> {
+        let shader =
+            shader_list::assemble(self.prefix, self.suffix, self.vertex_suffix, toy, values);
+        let line = |location: Option<naga::SourceLocation>| {
+            let prefix_lines = self.prefix.matches('\n').count();
+            location
+                .and_then(|location| (location.line_number as usize).checked_sub(prefix_lines))
+                .filter(|&line| line >= 1 && line <= toy.lines().count())
+        };
+
+        let module = match naga::front::wgsl::parse_str(&shader) {
+            Ok(module) => module,
+            Err(e) => {
+                return Some(Diagnostic::error(
+                    line(e.location(&shader)),
+                    e.message().to_string(),
+                ))
+            }
+        };
+
+        let validation = naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        )
+        .validate(&module);
+        if let Err(e) = validation {
+            return Some(Diagnostic::error(
+                line(e.location(&shader)),
+                format!("{:#}", anyhow::Error::new(e.into_inner())),
+            ));
+        }
+
+        match stats::complexity(&module) {
+            Ok(complexity) if complexity.max_iterations.is_none() => Some(Diagnostic::warning(
+                None,
+                "a loop has no constant bound, it may stall the GPU".to_string(),
+            )),
+            Ok(_) => None,
+            Err(e) => Some(Diagnostic::error(None, format!("{e:#}"))),
+        }
+    }
+}
+
+/// A word or a symbol of the source, the comments are skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+}
+
+impl Token<'_> {
+    fn is_identifier(&self) -> bool {
+        self.text
+            .starts_with(|c: char| c.is_alphabetic() || c == '_')
+    }
+}
+
+fn tokenize(source: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut chars = source.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, c)| c);
+        match (c, next) {
+            ('\n', _) => line += 1,
+            (c, _) if c.is_whitespace() => {}
+            ('/', Some('/')) => while chars.next_if(|&(_, c)| c != '\n').is_some() {},
+            ('/', Some('*')) => {
+                chars.next();
+                let mut depth = 1;
+                while depth > 0 {
+                    match chars.next() {
+                        Some((_, '\n')) => line += 1,
+                        Some((_, '*')) if chars.next_if(|&(_, c)| c == '/').is_some() => depth -= 1,
+                        Some((_, '/')) if chars.next_if(|&(_, c)| c == '*').is_some() => depth += 1,
+                        Some(_) => {}
+                        None => break,
+                    }
+                }
+            }
+            ('-', Some('>')) => {
+                chars.next();
+                tokens.push(Token {
+                    text: &source[start..start + 2],
+                    line,
+                });
+            }
+            (c, _) if c.is_alphanumeric() || c == '_' => {
+                // The dot is part of the numbers but not of `uv.x`.
+                let is_number = c.is_ascii_digit();
+                let mut end = start + c.len_utf8();
+                while let Some((index, c)) = chars
+                    .next_if(|&(_, c)| c.is_alphanumeric() || c == '_' || (is_number && c == '.'))
+                {
+                    end = index + c.len_utf8();
+                }
+                tokens.push(Token {
+                    text: &source[start..end],
+                    line,
+                });
+            }
+            (c, _) => tokens.push(Token {
+                text: &source[start..start + c.len_utf8()],
+                line,
+            }),
+        }
+    }
+
+    tokens
+}
+
+/// The declarations of a source, found without a full parser.
+#[derive(Debug, Default)]
+struct Source<'a> {
+    /// Every name declared at the top level, functions included.
+    declarations: Vec<Token<'a>>,
+    functions: Vec<FunctionSource<'a>>,
+}
+
+#[derive(Debug)]
+struct FunctionSource<'a> {
+    name: Token<'a>,
+    params: Vec<Token<'a>>,
+    /// The type of every parameter.
+    param_types: Vec<String>,
+    result: String,
+    /// The names declared with `let`, `var` and `const` in the body.
+    locals: Vec<Token<'a>>,
+    /// The words and symbols of the body.
+    body: Vec<&'a str>,
+}
+
+impl FunctionSource<'_> {
+    /// The names of the parameters don't matter, only their types.
+    fn has_signature(&self, entry_point: &EntryPoint) -> bool {
+        fn alias(ty: &str) -> &str {
+            match ty {
+                "vec4f" => "vec4<f32>",
+                ty => ty,
+            }
+        }
+        self.param_types
+            .iter()
+            .map(|ty| alias(ty))
+            .eq(entry_point.param_types.iter().copied())
+            && alias(&self.result) == entry_point.result
+    }
+
+    fn unused_params(&self) -> impl Iterator<Item = &Token<'_>> {
+        self.params
+            .iter()
+            .filter(|param| !param.text.starts_with('_') && !self.body.contains(&param.text))
+    }
+}
+
+fn parse<'a>(tokens: &[Token<'a>]) -> Source<'a> {
+    let mut source = Source::default();
+    let mut depth = 0;
+    let mut index = 0;
+
+    while let Some(token) = tokens.get(index) {
+        index += 1;
+        match token.text {
+            "{" => depth += 1,
+            "}" => depth -= 1,
+            "fn" if depth == 0 => {
+                if let Some((function, end)) = parse_function(tokens, index) {
+                    source.declarations.push(function.name);
+                    source.functions.push(function);
+                    index = end;
+                }
+            }
+            "struct" | "const" | "override" | "alias" | "var" if depth == 0 => {
+                index = skip_template(tokens, index);
+                if let Some(name) = tokens.get(index).filter(|name| name.is_identifier()) {
+                    source.declarations.push(*name);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    source
+}
+
+/// Parse the function whose name is at `start`, return it and the index after its body.
+fn parse_function<'a>(tokens: &[Token<'a>], start: usize) -> Option<(FunctionSource<'a>, usize)> {
+    let name = *tokens.get(start).filter(|name| name.is_identifier())?;
+    let params_start = start + 2;
+    let params_end = params_start + matching(&tokens[start + 1..], "(", ")")? - 1;
+
+    let mut params = Vec::new();
+    let mut param_types = Vec::new();
+    for param in split_params(&tokens[params_start..params_end]) {
+        let param = skip_attributes(param);
+        if let [name, colon, ty @ ..] = param {
+            if name.is_identifier() && colon.text == ":" {
+                params.push(*name);
+                param_types.push(join(ty));
+            }
+        }
+    }
+
+    let body_start = params_end
+        + 1
+        + tokens[params_end + 1..]
+            .iter()
+            .position(|token| token.text == "{")?;
+    let result = match tokens.get(params_end + 1) {
+        Some(arrow) if arrow.text == "->" => {
+            join(skip_attributes(&tokens[params_end + 2..body_start]))
+        }
+        _ => String::new(),
+    };
+    let body_end = body_start + matching(&tokens[body_start..], "{", "}")?;
+    let body = &tokens[body_start + 1..body_end];
+
+    let locals = body
+        .iter()
+        .enumerate()
+        .filter(|(_, token)| matches!(token.text, "let" | "var" | "const"))
+        .filter_map(|(index, _)| {
+            let name = skip_template(body, index + 1);
+            body.get(name).filter(|name| name.is_identifier()).copied()
+        })
+        .collect();
+
+    let function = FunctionSource {
+        name,
+        params,
+        param_types,
+        result,
+        locals,
+        body: body.iter().map(|token| token.text).collect(),
+    };
+    Some((function, body_end + 1))
+}
+
+/// The index of the token closing the group opened by the first token.
+fn matching(tokens: &[Token], open: &str, close: &str) -> Option<usize> {
+    if tokens.first()?.text != open {
+        return None;
+    }
+    let mut depth = 0;
+    for (index, token) in tokens.iter().enumerate() {
+        if token.text == open {
+            depth += 1;
+        } else if token.text == close {
+            depth -= 1;
+            if depth == 0 {
+                return Some(index);
+            }
+        }
+    }
+    None
+}
+
+/// Skip the `<...>` following `var` or a type, e.g. `var<uniform>`.
+fn skip_template(tokens: &[Token], index: usize) -> usize {
+    match tokens.get(index) {
+        Some(token) if token.text == "<" => {
+            index + matching(&tokens[index..], "<", ">").map_or(0, |end| end + 1)
+        }
+        _ => index,
+    }
+}
+
+/// Skip the attributes like `@builtin(position)` at the start of a parameter or a result.
+fn skip_attributes<'a, 'b>(mut tokens: &'b [Token<'a>]) -> &'b [Token<'a>] {
+    while let [at, _, rest @ ..] = tokens {
+        if at.text != "@" {
+            break;
+        }
+        tokens = match matching(rest, "(", ")") {
+            Some(end) => &rest[end + 1..],
+            None => rest,
+        };
+    }
+    tokens
+}
+
+/// Split a parameter list on the commas outside of the `<...>` and `(...)`.
+fn split_params<'a, 'b>(tokens: &'b [Token<'a>]) -> Vec<&'b [Token<'a>]> {
+    let mut params = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, token) in tokens.iter().enumerate() {
+        match token.text {
+            "<" | "(" => depth += 1,
+            ">" | ")" => depth -= 1,
+            "," if depth == 0 => {
+                params.push(&tokens[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    if start < tokens.len() {
+        params.push(&tokens[start..]);
+    }
+    params
+}
+
+fn join(tokens: &[Token]) -> String {
+    tokens.iter().map(|token| token.text).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Diagnostic, Linter, Severity};
+
+    fn linter() -> Linter {
+        Linter::new(
+            include_str!("assets/fragment.prefix.wgsl"),
+            include_str!("assets/fragment.suffix.wgsl"),
+            include_str!("assets/vertex.suffix.wgsl"),
+        )
+    }
+
+    fn messages(toy: &str) -> Vec<String> {
+        linter()
+            .lint(toy)
+            .iter()
+            .map(Diagnostic::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn bundled_toys() {
+        let linter = linter();
+        for (name, toy) in crate::SHADERS {
+            assert_eq!(linter.lint(toy), [], "{name}");
+        }
+    }
+
+    #[test]
+    fn main_image_contract() {
+        assert_eq!(
+            messages("fn main(coord: vec4<f32>) -> vec4<f32> { return coord; }"),
+            ["error: the toy must define `fn main_image(frag_coord: vec4<f32>) -> vec4<f32>`, it is called for every pixel"]
+        );
+        assert_eq!(
+            messages("\nfn main_image(uv: vec2<f32>) -> vec4<f32> {\n    return vec4(uv, 0., 1.);\n}"),
+            ["2: error: `main_image` must have the signature `fn main_image(frag_coord: vec4<f32>) -> vec4<f32>`"]
+        );
+        assert!(messages("fn main_image(c: vec4f) -> vec4f { return c; }").is_empty());
+    }
+
+    #[test]
+    fn prefix_names() {
+        assert_eq!(
+            messages(
+                "
+// fn u() is fine in a comment
+fn texture(x: f32) -> f32 { return x; }
+fn main_image(frag_coord: vec4<f32>) -> vec4<f32> {
+    let uv_centered = frag_coord.xy;
+    return vec4(uv_centered, 0., 1.);
+}"
+            ),
+            [
+                "3: error: `texture` is already declared by the prefix, rename it",
+                "5: warning: `uv_centered` shadows the prefix declaration of the same name",
+            ]
+        );
+    }
+
+    #[test]
+    fn unused_params() {
+        assert_eq!(
+            messages(
+                "
+fn shade(color: vec3<f32>, _alpha: f32, brightness: f32) -> vec4<f32> {
+    return vec4(color, 1.);
+}
+fn main_image(frag_coord: vec4<f32>) -> vec4<f32> {
+    return shade(frag_coord.xyz, 1., 2.);
+}"
+            ),
+            ["2: warning: the parameter `brightness` of `shade` is never used, prefix it with `_` if it's on purpose"]
+        );
+    }
+
+    #[test]
+    fn variants() {
+        assert_eq!(
+            messages(
+                "// variant: dark BRIGHTNESS=.2 SIZE=2.
+override BRIGHTNESS: f32 = 1.;
+fn main_image(_frag_coord: vec4<f32>) -> vec4<f32> {
+    return vec4(BRIGHTNESS);
+}"
+            ),
+            ["warning: the variant `dark` sets `SIZE`, which isn't declared with `override`"]
+        );
+        assert_eq!(
+            messages(
+                "// variant: light
+// variant: broken COLOR=vec3(1.)
+@id(0) override COLOR: f32 = 1.;
+fn main_image(_frag_coord: vec4<f32>) -> vec4<f32> {
+    return vec4(COLOR);
+}"
+            )
+            .len(),
+            1,
+            "only the second variant is broken"
+        );
+    }
+
+    #[test]
+    fn vertex_toys() {
+        assert!(messages(
+            "// vertices: 100 lines
+fn vertex_main(vertex_index: u32) -> VertexOutput {
+    let x = f32(vertex_index) / u.vertex_count;
+    return VertexOutput(vec4(x, 0., 0., 1.), vec4(1.));
+}"
+        )
+        .is_empty());
+        assert_eq!(
+            messages("// vertices: 100\nfn main_image(c: vec4f) -> vec4f { return c; }"),
+            ["error: the toy must define `fn vertex_main(vertex_index: u32) -> VertexOutput`, it is called for every vertex"]
+        );
+        assert_eq!(
+            messages(
+                "\n// vertices: 100 quads
+fn vertex_main(_vertex_index: u32) -> VertexOutput {
+    return VertexOutput(vec4(0.), vec4(1.));
+}"
+            ),
+            ["2: error: invalid `// vertices:` comment: Unknown topology `quads`"]
+        );
+    }
+
+    #[test]
+    fn naga_errors() {
+        let diagnostics = linter().lint(
+            "fn main_image(_frag_coord: vec4<f32>) -> vec4<f32> {\n    return vec4(1.) +;\n}",
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].line, Some(2));
+    }
+
+    #[test]
+    fn unbounded_loop() {
+        assert_eq!(
+            messages(
+                "
+fn main_image(_frag_coord: vec4<f32>) -> vec4<f32> {
+    var t = 0.;
+    loop {
+        t += 0.1;
+        if t > u.time { break; }
+    }
+    return vec4(t);
+}"
+            ),
+            ["warning: a loop has no constant bound, it may stall the GPU"]
+        );
+    }
+}
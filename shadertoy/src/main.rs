@@ -1,5 +1,14 @@
+#[cfg(not(target_arch = "wasm32"))]
 fn main() {
+    use clap::Parser;
+
+    let options = shadertoy::Options::parse();
+
     tokio::runtime::Runtime::new()
         .expect("Cannot create tokio runtime")
-        .block_on(shadertoy::run());
+        .block_on(shadertoy::run(options));
 }
+
+/// The web build start from the `start` function exported by the library.
+#[cfg(target_arch = "wasm32")]
+fn main() {}
@@ -0,0 +1,554 @@
+use std::{net::UdpSocket, path::Path, sync::mpsc};
+
+use anyhow::Context;
+use instant::Instant;
+
+/// Number of custom parameters, read by the toys with `param(index)`.
+pub const PARAM_COUNT: usize = 8;
+/// The mapping used when no mapping file is given, also an example of mapping file.
+pub const DEFAULT_MAPPING: &str = include_str!("assets/osc-mapping.json");
+
+/// An OSC argument, the type tags `[` and `]` of arrays aren't supported.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Argument {
+    Int(i32),
+    Float(f32),
+    Long(i64),
+    Double(f64),
+    Time(u64),
+    String(String),
+    Blob(Vec<u8>),
+    Bool(bool),
+    Nil,
+    Impulse,
+}
+
+impl Argument {
+    fn as_f32(&self) -> Option<f32> {
+        match self {
+            Self::Int(value) => Some(*value as f32),
+            Self::Float(value) => Some(*value),
+            Self::Long(value) => Some(*value as f32),
+            Self::Double(value) => Some(*value as f32),
+            Self::Bool(value) => Some(f32::from(u8::from(*value))),
+            Self::Impulse => Some(1.),
+            Self::Time(_) | Self::String(_) | Self::Blob(_) | Self::Nil => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub address: String,
+    pub arguments: Vec<Argument>,
+}
+
+/// Decode an OSC packet, a message or a bundle of packets.
+///
+/// The time tags of the bundles are ignored, the messages are applied as soon as received.
+pub fn decode(packet: &[u8]) -> anyhow::Result<Vec<Message>> {
+    let mut messages = Vec::new();
+    decode_into(packet, &mut messages)?;
+    Ok(messages)
+}
+
+fn decode_into(packet: &[u8], messages: &mut Vec<Message>) -> anyhow::Result<()> {
+    let mut reader = Reader(packet);
+
+    if packet.starts_with(b"#bundle\0") {
+        reader.bytes(16)?;
+        while !reader.0.is_empty() {
+            let size = usize::try_from(reader.i32()?).context("Negative bundle element size")?;
+            decode_into(reader.bytes(size)?, messages)?;
+        }
+        return Ok(());
+    }
+
+    let address = reader.string()?;
+    anyhow::ensure!(address.starts_with('/'), "Invalid address `{address}`");
+    // Old implementations may omit the type tags of messages without arguments.
+    let tags = if reader.0.is_empty() {
+        ",".to_owned()
+    } else {
+        reader.string()?
+    };
+    let arguments = tags
+        .strip_prefix(',')
+        .context("The type tags must start with `,`")?
+        .chars()
+        .map(|tag| reader.argument(tag))
+        .collect::<anyhow::Result<_>>()?;
+
+    messages.push(Message { address, arguments });
+    Ok(())
+}
+
+/// Read the big-endian values of a packet, padded to 4 bytes.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        anyhow::ensure!(len <= self.0.len(), "Truncated packet");
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> anyhow::Result<[u8; N]> {
+        Ok(self.bytes(N)?.try_into().expect("The length was checked"))
+    }
+
+    fn padded(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        let bytes = self.bytes(len)?;
+        self.bytes((4 - len % 4) % 4)?;
+        Ok(bytes)
+    }
+
+    fn i32(&mut self) -> anyhow::Result<i32> {
+        Ok(i32::from_be_bytes(self.array()?))
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        let len = self
+            .0
+            .iter()
+            .position(|&byte| byte == 0)
+            .context("Unterminated string")?;
+        let bytes = self.padded(len + 1)?;
+        String::from_utf8(bytes[..len].to_vec()).context("Invalid UTF-8 string")
+    }
+
+    fn argument(&mut self, tag: char) -> anyhow::Result<Argument> {
+        Ok(match tag {
+            'i' => Argument::Int(self.i32()?),
+            'f' => Argument::Float(f32::from_be_bytes(self.array()?)),
+            'h' => Argument::Long(i64::from_be_bytes(self.array()?)),
+            'd' => Argument::Double(f64::from_be_bytes(self.array()?)),
+            't' => Argument::Time(u64::from_be_bytes(self.array()?)),
+            's' | 'S' => Argument::String(self.string()?),
+            'b' => {
+                let len = usize::try_from(self.i32()?).context("Negative blob size")?;
+                Argument::Blob(self.padded(len)?.to_vec())
+            }
+            'T' => Argument::Bool(true),
+            'F' => Argument::Bool(false),
+            'N' => Argument::Nil,
+            'I' => Argument::Impulse,
+            _ => anyhow::bail!("Unsupported type tag `{tag}`"),
+        })
+    }
+}
+
+/// Associate an OSC address to what it controls.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Mapping {
+    pub address: String,
+    pub target: Target,
+    /// Map the values from `[0, 1]` to this range, the values are used as is otherwise.
+    #[serde(default)]
+    pub range: Option<[f32; 2]>,
+    /// Time in seconds to reach about two thirds of a new value, 0 to apply it immediately.
+    #[serde(default)]
+    pub smoothing: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Target {
+    /// A custom parameter, the first numeric argument is its value.
+    Param(usize),
+    /// How fast the time pass, the first numeric argument is the speed.
+    Speed,
+    /// Switch toy when the first argument is at least 0.5, or without arguments.
+    NextToy,
+    PreviousToy,
+    /// Select a toy by its number or by a search, from the first argument.
+    SelectToy,
+}
+
+impl Mapping {
+    fn value(&self, message: &Message) -> Option<f32> {
+        let value = message.arguments.iter().find_map(Argument::as_f32)?;
+        Some(match self.range {
+            Some([min, max]) => min + value * (max - min),
+            None => value,
+        })
+    }
+}
+
+/// Parse a JSON list of [`Mapping`].
+pub fn parse_mappings(json: &str) -> anyhow::Result<Vec<Mapping>> {
+    let mappings: Vec<Mapping> = serde_json::from_str(json)?;
+
+    for mapping in &mappings {
+        if let Target::Param(index) = mapping.target {
+            anyhow::ensure!(
+                index < PARAM_COUNT,
+                "Invalid parameter {index} for `{}`, there are {PARAM_COUNT} parameters",
+                mapping.address
+            );
+        }
+    }
+    Ok(mappings)
+}
+
+pub fn load_mappings(path: &Path) -> anyhow::Result<Vec<Mapping>> {
+    let json =
+        std::fs::read_to_string(path).with_context(|| format!("Can't read {}", path.display()))?;
+    parse_mappings(&json).with_context(|| format!("Invalid mapping file {}", path.display()))
+}
+
+/// A value moving smoothly toward its target.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Smoothed {
+    value: f32,
+    target: f32,
+    smoothing: f32,
+}
+
+impl Smoothed {
+    pub fn new(value: f32) -> Self {
+        Self {
+            value,
+            target: value,
+            smoothing: 0.,
+        }
+    }
+
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    pub fn set(&mut self, target: f32, smoothing: f32) {
+        self.target = target;
+        self.smoothing = smoothing;
+        if smoothing <= 0. {
+            self.value = target;
+        }
+    }
+
+    /// Move toward the target, `dt` seconds after the previous update.
+    pub fn update(&mut self, dt: f32) {
+        self.value = if self.smoothing > 0. {
+            self.value + (self.target - self.value) * (1. - (-dt / self.smoothing).exp())
+        } else {
+            self.target
+        };
+    }
+}
+
+/// A toy switch requested by a message.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToyAction {
+    Next,
+    Previous,
+    /// A toy number or a search, like the prompt.
+    Select(String),
+}
+
+/// Apply the OSC messages to the custom parameters and the speed.
+pub struct Osc {
+    mappings: Vec<Mapping>,
+    params: [Smoothed; PARAM_COUNT],
+    /// Only set once a message changed the speed, so it can be changed otherwise until then.
+    speed: Option<Smoothed>,
+    messages: Option<mpsc::Receiver<Message>>,
+    last_update: Instant,
+}
+
+impl Osc {
+    pub fn new(mappings: Vec<Mapping>, now: Instant) -> Self {
+        Self {
+            mappings,
+            params: [Smoothed::new(0.); PARAM_COUNT],
+            speed: None,
+            messages: None,
+            last_update: now,
+        }
+    }
+
+    /// Receive the messages sent on this UDP port of localhost.
+    pub fn listen(mut self, port: u16) -> anyhow::Result<Self> {
+        let socket = UdpSocket::bind(("127.0.0.1", port))
+            .with_context(|| format!("Can't listen on the UDP port {port}"))?;
+        let (tx, rx) = mpsc::channel();
+
+        std::thread::Builder::new()
+            .name("osc".into())
+            .spawn(move || {
+                let mut buffer = [0; 65536];
+                while let Ok(len) = socket.recv(&mut buffer) {
+                    match decode(&buffer[..len]) {
+                        Ok(messages) => {
+                            if messages.into_iter().try_for_each(|m| tx.send(m)).is_err() {
+                                return;
+                            }
+                        }
+                        Err(e) => log::warn!("Invalid OSC packet: {e:#}"),
+                    }
+                }
+            })
+            .context("Failed to spawn the OSC thread")?;
+
+        self.messages = Some(rx);
+        Ok(self)
+    }
+
+    /// Apply the received messages and move the smoothed values, return the toy switches.
+    pub fn poll(&mut self, now: Instant) -> Vec<ToyAction> {
+        let messages = self
+            .messages
+            .as_ref()
+            .map(|rx| rx.try_iter().collect::<Vec<_>>())
+            .unwrap_or_default();
+        let actions = messages
+            .iter()
+            .flat_map(|message| self.apply(message))
+            .collect();
+
+        let dt = (now - self.last_update).as_secs_f32();
+        self.last_update = now;
+        for value in self.params.iter_mut().chain(&mut self.speed) {
+            value.update(dt);
+        }
+
+        actions
+    }
+
+    pub fn params(&self) -> [f32; PARAM_COUNT] {
+        self.params.map(|param| param.value())
+    }
+
+    pub fn speed(&self) -> Option<f32> {
+        self.speed.map(|speed| speed.value())
+    }
+
+    fn apply(&mut self, message: &Message) -> Vec<ToyAction> {
+        let mut actions = Vec::new();
+
+        for mapping in self
+            .mappings
+            .iter()
+            .filter(|m| m.address == message.address)
+        {
+            let value = mapping.value(message);
+            match mapping.target {
+                Target::Param(index) => {
+                    if let Some(value) = value {
+                        self.params[index].set(value, mapping.smoothing);
+                    }
+                }
+                Target::Speed => {
+                    if let Some(value) = value {
+                        let speed = self.speed.get_or_insert(Smoothed::new(1.));
+                        speed.set(value, mapping.smoothing);
+                    }
+                }
+                Target::NextToy if !value.is_some_and(|value| value < 0.5) => {
+                    actions.push(ToyAction::Next);
+                }
+                Target::PreviousToy if !value.is_some_and(|value| value < 0.5) => {
+                    actions.push(ToyAction::Previous);
+                }
+                Target::SelectToy => match message.arguments.first() {
+                    Some(Argument::String(query)) => actions.push(ToyAction::Select(query.clone())),
+                    Some(argument) => {
+                        if let Some(number) = argument.as_f32() {
+                            actions.push(ToyAction::Select(number.round().to_string()));
+                        }
+                    }
+                    None => {}
+                },
+                Target::NextToy | Target::PreviousToy => {}
+            }
+        }
+        actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::UdpSocket, time::Duration};
+
+    use instant::Instant;
+
+    use super::{
+        decode, parse_mappings, Argument, Message, Osc, Smoothed, ToyAction, DEFAULT_MAPPING,
+    };
+
+    /// Encode a string with its terminator and padding.
+    fn string(value: &str) -> Vec<u8> {
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.resize((value.len() / 4 + 1) * 4, 0);
+        bytes
+    }
+
+    fn message(address: &str, arguments: &[Argument]) -> Message {
+        Message {
+            address: address.into(),
+            arguments: arguments.to_vec(),
+        }
+    }
+
+    #[test]
+    fn decode_message() {
+        let packet = [
+            string("/param"),
+            string(",ifsT"),
+            7i32.to_be_bytes().to_vec(),
+            0.5f32.to_be_bytes().to_vec(),
+            string("ring"),
+        ]
+        .concat();
+
+        assert_eq!(
+            decode(&packet).unwrap(),
+            [message(
+                "/param",
+                &[
+                    Argument::Int(7),
+                    Argument::Float(0.5),
+                    Argument::String("ring".into()),
+                    Argument::Bool(true),
+                ]
+            )]
+        );
+    }
+
+    #[test]
+    fn decode_bundle() {
+        let first = [
+            string("/a"),
+            string(",b"),
+            3i32.to_be_bytes().to_vec(),
+            vec![1, 2, 3, 0],
+        ]
+        .concat();
+        let second = [string("/b"), string(",")].concat();
+        let inner = [
+            string("#bundle"),
+            vec![0; 8],
+            (second.len() as i32).to_be_bytes().to_vec(),
+            second,
+        ]
+        .concat();
+        let packet = [
+            string("#bundle"),
+            vec![0; 8],
+            (first.len() as i32).to_be_bytes().to_vec(),
+            first,
+            (inner.len() as i32).to_be_bytes().to_vec(),
+            inner,
+        ]
+        .concat();
+
+        assert_eq!(
+            decode(&packet).unwrap(),
+            [
+                message("/a", &[Argument::Blob(vec![1, 2, 3])]),
+                message("/b", &[]),
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_invalid() {
+        // Missing the float value.
+        assert!(decode(&[string("/a"), string(",f")].concat()).is_err());
+        assert!(decode(&[string("/a"), string(",[f]")].concat()).is_err());
+        assert!(decode(&string("a")).is_err());
+        assert!(decode(b"/a").is_err());
+    }
+
+    #[test]
+    fn default_mapping() {
+        let mappings = parse_mappings(DEFAULT_MAPPING).unwrap();
+        assert_eq!(mappings.len(), 12);
+
+        assert!(parse_mappings(r#"[{ "address": "/p", "target": { "param": 8 } }]"#).is_err());
+    }
+
+    #[test]
+    fn smoothing() {
+        let mut value = Smoothed::new(0.);
+
+        value.set(1., 1.);
+        assert_eq!(value.value(), 0.);
+        value.update(1.);
+        assert!((value.value() - (1. - (-1f32).exp())).abs() < 1e-6);
+        for _ in 0..100 {
+            value.update(1.);
+        }
+        assert!((value.value() - 1.).abs() < 1e-6);
+
+        value.set(2., 0.);
+        assert_eq!(value.value(), 2.);
+    }
+
+    #[test]
+    fn apply_messages() {
+        let start = Instant::now();
+        let mut osc = Osc::new(parse_mappings(DEFAULT_MAPPING).unwrap(), start);
+
+        assert_eq!(osc.speed(), None);
+        osc.apply(&message("/shadertoy/speed", &[Argument::Float(0.5)]));
+        osc.apply(&message("/shadertoy/param/2", &[Argument::Int(3)]));
+        osc.apply(&message("/unknown", &[Argument::Int(3)]));
+        osc.poll(start + Duration::from_secs(10));
+        assert!((osc.speed().unwrap() - 2.).abs() < 1e-3);
+        assert!((osc.params()[2] - 3.).abs() < 1e-3);
+
+        assert_eq!(
+            osc.apply(&message("/shadertoy/toy/next", &[Argument::Float(1.)])),
+            [ToyAction::Next]
+        );
+        // The release of the button.
+        assert_eq!(
+            osc.apply(&message("/shadertoy/toy/next", &[Argument::Float(0.)])),
+            []
+        );
+        assert_eq!(
+            osc.apply(&message("/shadertoy/toy/previous", &[])),
+            [ToyAction::Previous]
+        );
+        assert_eq!(
+            osc.apply(&message("/shadertoy/toy/select", &[Argument::Float(3.)])),
+            [ToyAction::Select("3".into())]
+        );
+        assert_eq!(
+            osc.apply(&message(
+                "/shadertoy/toy/select",
+                &[Argument::String("ring".into())]
+            )),
+            [ToyAction::Select("ring".into())]
+        );
+    }
+
+    #[test]
+    fn listen_udp() {
+        let port = UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let mappings = parse_mappings(r#"[{ "address": "/p", "target": { "param": 1 } }]"#);
+        let mut osc = Osc::new(mappings.unwrap(), Instant::now())
+            .listen(port)
+            .unwrap();
+
+        let packet = [string("/p"), string(",f"), 0.75f32.to_be_bytes().to_vec()].concat();
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client.send_to(&packet, ("127.0.0.1", port)).unwrap();
+
+        for _ in 0..100 {
+            osc.poll(Instant::now());
+            if osc.params()[1] != 0. {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(osc.params()[1], 0.75);
+    }
+}
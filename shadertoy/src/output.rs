@@ -0,0 +1,152 @@
+use std::sync::Arc;
+
+#[cfg(not(target_arch = "wasm32"))]
+use anyhow::Context;
+use winit::{dpi::PhysicalSize, window::Window};
+#[cfg(not(target_arch = "wasm32"))]
+use winit::{
+    event_loop::EventLoopWindowTarget,
+    window::{Fullscreen, WindowBuilder},
+};
+
+use crate::{
+    hdr::{Tonemap, TonemapPass},
+    layer::LayerStack,
+    pipeline,
+    post::{PostStack, PostTargets},
+    uniform::Uniform,
+};
+
+/// Build the output window, borderless full screen on the monitor `monitor` if given.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn build_window<T>(
+    event_loop: &EventLoopWindowTarget<T>,
+    monitor: Option<usize>,
+) -> anyhow::Result<Window> {
+    let mut builder = WindowBuilder::new().with_title("shadertoy output");
+
+    if let Some(index) = monitor {
+        let monitor = event_loop
+            .available_monitors()
+            .nth(index)
+            .with_context(|| format!("There is no monitor {index}"))?;
+        builder = builder.with_fullscreen(Some(Fullscreen::Borderless(Some(monitor))));
+    }
+
+    builder
+        .build(event_loop)
+        .context("Failed to build the output window")
+}
+
+/// A second window showing only the toy, e.g. full screen on a projector,
+/// while the main window shows the title, the prompt and the statistics.
+pub struct OutputWindow {
+    window: Window,
+    surface: wgpu::Surface,
+    device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
+    config: wgpu::SurfaceConfiguration,
+    targets: PostTargets,
+    tonemap_pass: TonemapPass,
+}
+
+impl OutputWindow {
+    pub fn new(
+        device: Arc<wgpu::Device>,
+        queue: Arc<wgpu::Queue>,
+        window: Window,
+        surface: wgpu::Surface,
+        config: wgpu::SurfaceConfiguration,
+        mut tonemap_pass: TonemapPass,
+        post: &PostStack,
+    ) -> Self {
+        surface.configure(&device, &config);
+        let [width, height] = tonemap_pass.resize(&queue, config.width, config.height);
+        let targets = post.create_targets(&device, &tonemap_pass, width, height);
+
+        Self {
+            window,
+            surface,
+            device,
+            queue,
+            config,
+            targets,
+            tonemap_pass,
+        }
+    }
+
+    pub fn window(&self) -> &Window {
+        &self.window
+    }
+
+    pub fn set_tonemap(&mut self, tonemap: Tonemap) {
+        self.tonemap_pass.set_tonemap(&self.queue, tonemap);
+    }
+
+    pub fn resize(&mut self, post: &PostStack, new_size: PhysicalSize<u32>) {
+        if new_size.width > 0 && new_size.height > 0 {
+            self.config.width = new_size.width;
+            self.config.height = new_size.height;
+            self.surface.configure(&self.device, &self.config);
+            let [width, height] =
+                self.tonemap_pass
+                    .resize(&self.queue, new_size.width, new_size.height);
+            self.targets = post.create_targets(&self.device, &self.tonemap_pass, width, height);
+        }
+    }
+
+    /// Render the toy and its layers at the resolution of this window, the rest of the uniform
+    /// is shared with the main window.
+    ///
+    /// The uniform buffers are written and submitted before the main window reuses them.
+    pub fn render(
+        &mut self,
+        pipeline: &pipeline::ToyPipeline,
+        layers: &LayerStack,
+        post: &PostStack,
+        mut uniform: Uniform,
+        uniform_buffer: &wgpu::Buffer,
+        uniform_bind_group: &wgpu::BindGroup,
+    ) {
+        let output = match self.surface.get_current_texture() {
+            Ok(output) => output,
+            Err(wgpu::SurfaceError::Lost) => {
+                self.resize(post, self.window.inner_size());
+                return;
+            }
+            Err(e) => {
+                log::warn!("Non critical render error in the output window: {e}");
+                return;
+            }
+        };
+        let view = output
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        let [width, height] = self
+            .tonemap_pass
+            .render_size(self.config.width, self.config.height);
+        uniform.resolution = [self.config.width as f32, self.config.height as f32];
+        uniform.target_resolution = [width as f32, height as f32];
+        uniform.scale = 1. / self.tonemap_pass.render_scale();
+        self.queue.write_buffer(uniform_buffer, 0, uniform.as_ref());
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("output-encoder"),
+            });
+        pipeline::render_toy(
+            &mut encoder,
+            pipeline,
+            uniform_bind_group,
+            &self.targets.input.view,
+        );
+        layers.render(&mut encoder, &uniform, &self.targets);
+        let hdr_target = post.render(&mut encoder, uniform_bind_group, &self.targets);
+        self.tonemap_pass.render(&mut encoder, hdr_target, &view);
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+        output.present();
+    }
+}
@@ -0,0 +1,575 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, VecDeque},
+    hash::{Hash, Hasher},
+    sync::{Arc, Mutex},
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::mpsc;
+
+use anyhow::Context;
+
+use crate::{channel, shader_list, stream::Stream};
+
+/// Number of pipelines kept in the cache, with their channel textures.
+const MAX_PIPELINES: usize = 24;
+
+/// The primitives drawn by a toy, declared by its `// vertices:` comment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Geometry {
+    /// A triangle covering the screen, `main_image` is called for every pixel.
+    Fullscreen,
+    /// `count` vertices placed by the `vertex_main` function of the toy, blended additively.
+    Vertices {
+        count: u32,
+        topology: wgpu::PrimitiveTopology,
+    },
+}
+
+impl Geometry {
+    /// Parse the `// vertices: <count> <topology>` comment of a toy, e.g. `// vertices: 5000 lines`.
+    /// The topology is `points`, `lines`, `line-strip`, `triangles` or `triangle-strip`,
+    /// `triangles` if omitted.
+    pub fn parse(shader: &str) -> anyhow::Result<Self> {
+        let Some(declaration) = shader_list::vertices_declaration(shader) else {
+            return Ok(Self::Fullscreen);
+        };
+
+        let mut words = declaration.split_whitespace();
+        let count = words.next().context("The vertex count is missing")?;
+        let count = count
+            .parse()
+            .with_context(|| format!("Invalid vertex count `{count}`"))?;
+        let topology = match words.next().unwrap_or("triangles") {
+            "points" => wgpu::PrimitiveTopology::PointList,
+            "lines" => wgpu::PrimitiveTopology::LineList,
+            "line-strip" => wgpu::PrimitiveTopology::LineStrip,
+            "triangles" => wgpu::PrimitiveTopology::TriangleList,
+            "triangle-strip" => wgpu::PrimitiveTopology::TriangleStrip,
+            topology => anyhow::bail!("Unknown topology `{topology}`"),
+        };
+        if let Some(word) = words.next() {
+            anyhow::bail!("Unexpected `{word}` after the topology");
+        }
+
+        Ok(Self::Vertices { count, topology })
+    }
+
+    pub fn vertex_count(self) -> u32 {
+        match self {
+            Self::Fullscreen => 3,
+            Self::Vertices { count, .. } => count,
+        }
+    }
+}
+
+/// A compiled toy, the primitives it draws and its channels.
+pub struct ToyPipeline {
+    pipeline: wgpu::RenderPipeline,
+    geometry: Geometry,
+    channels: wgpu::BindGroup,
+    /// The sequence and video channels, the pipeline is shared between the windows.
+    streams: Mutex<Vec<Stream>>,
+}
+
+impl ToyPipeline {
+    pub fn geometry(&self) -> Geometry {
+        self.geometry
+    }
+
+    /// Upload the frames of the streamed channels shown at `time`, before rendering the toy.
+    pub fn update_channels(&self, encoder: &mut wgpu::CommandEncoder, time: f32) {
+        for stream in self.streams.lock().unwrap().iter_mut() {
+            stream.update(encoder, time);
+        }
+    }
+
+    /// Must be called right after the encoder passed to [`ToyPipeline::update_channels`]
+    /// was submitted.
+    pub fn channels_submitted(&self) {
+        for stream in self.streams.lock().unwrap().iter_mut() {
+            stream.submitted();
+        }
+    }
+
+    /// Stop decoding the streamed channels once the toy isn't shown anymore,
+    /// they start again on the next [`ToyPipeline::update_channels`].
+    pub fn close_streams(&self) {
+        for stream in self.streams.lock().unwrap().iter_mut() {
+            stream.close();
+        }
+    }
+}
+
+/// Compiled toy pipelines, keyed by the hash of their shader and their target format.
+///
+/// The fullscreen vertex module is shared by every toy, so it's only created once.
+/// The textures of the channels are loaded with the pipeline.
+/// wgpu doesn't expose its backend pipeline caches yet,
+/// so the pipelines only live for the duration of the process.
+/// At most [`MAX_PIPELINES`] are kept, the least recently used are dropped first,
+/// the toys and layers shown keep theirs alive.
+///
+/// Pipelines can also be compiled on a worker thread, so heavy toys don't stall the caller.
+/// On the web, where there is no thread, they are compiled one by one on the next polls.
+/// If the worker thread panics, the pipelines are compiled synchronously from then on.
+pub struct PipelineCache {
+    builder: Arc<PipelineBuilder>,
+    pipelines: HashMap<PipelineKey, Arc<ToyPipeline>>,
+    /// The keys of `pipelines`, from the least to the most recently used.
+    recent: VecDeque<PipelineKey>,
+    /// The fragment shaders queued on the worker.
+    pending: HashMap<PipelineKey, String>,
+    worker: Option<Worker>,
+    worker_stopped: bool,
+}
+
+/// The state shared by every toy pipeline.
+struct PipelineBuilder {
+    queue: Arc<wgpu::Queue>,
+    vs_module: wgpu::ShaderModule,
+    uniform_layout: wgpu::BindGroupLayout,
+}
+
+/// A thread compiling the requested pipelines, it stops once the cache is dropped.
+#[cfg(not(target_arch = "wasm32"))]
+struct Worker {
+    requests: mpsc::Sender<(PipelineKey, String)>,
+    compiled: mpsc::Receiver<(PipelineKey, Arc<ToyPipeline>)>,
+}
+
+/// The requested pipelines, compiled one at a time when polled.
+#[cfg(target_arch = "wasm32")]
+struct Worker {
+    device: Arc<wgpu::Device>,
+    builder: Arc<PipelineBuilder>,
+    requests: std::collections::VecDeque<(PipelineKey, String)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct PipelineKey {
+    source_hash: u64,
+    format: wgpu::TextureFormat,
+}
+
+impl PipelineKey {
+    fn new(fragment_shader: &str, format: wgpu::TextureFormat) -> Self {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        fragment_shader.hash(&mut hasher);
+
+        Self {
+            source_hash: hasher.finish(),
+            format,
+        }
+    }
+}
+
+impl PipelineCache {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: Arc<wgpu::Queue>,
+        uniform_layout: wgpu::BindGroupLayout,
+    ) -> Self {
+        let vs_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("vertex-shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::from(include_str!("assets/vertex.wgsl"))),
+        });
+
+        Self {
+            builder: Arc::new(PipelineBuilder {
+                queue,
+                vs_module,
+                uniform_layout,
+            }),
+            pipelines: HashMap::new(),
+            recent: VecDeque::new(),
+            pending: HashMap::new(),
+            worker: None,
+            worker_stopped: false,
+        }
+    }
+
+    /// The layout of the uniforms of the toys, group 0 of their pipelines.
+    pub fn uniform_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.builder.uniform_layout
+    }
+
+    /// Return the pipeline if it was already compiled.
+    pub fn get(
+        &mut self,
+        fragment_shader: &str,
+        format: wgpu::TextureFormat,
+    ) -> Option<Arc<ToyPipeline>> {
+        let key = PipelineKey::new(fragment_shader, format);
+        let pipeline = self.pipelines.get(&key).cloned()?;
+        touch(&mut self.recent, key);
+        Some(pipeline)
+    }
+
+    /// Return the pipeline rendering the fragment shader to the given format,
+    /// compiling it the first time it's requested.
+    pub fn get_or_build(
+        &mut self,
+        device: &wgpu::Device,
+        fragment_shader: &str,
+        format: wgpu::TextureFormat,
+    ) -> Arc<ToyPipeline> {
+        let key = PipelineKey::new(fragment_shader, format);
+
+        if let Some(pipeline) = self.pipelines.get(&key).cloned() {
+            log::debug!("Reuse cached pipeline {key:?}");
+            touch(&mut self.recent, key);
+            return pipeline;
+        }
+
+        let pipeline = Arc::new(self.builder.build(device, fragment_shader, format));
+        self.insert(key, pipeline.clone());
+        pipeline
+    }
+
+    /// Cache a pipeline, dropping the least recently used ones beyond [`MAX_PIPELINES`].
+    fn insert(&mut self, key: PipelineKey, pipeline: Arc<ToyPipeline>) {
+        self.pipelines.insert(key, pipeline);
+        touch(&mut self.recent, key);
+        for key in evicted(&mut self.recent, MAX_PIPELINES) {
+            log::debug!("Drop the cached pipeline {key:?}");
+            self.pipelines.remove(&key);
+        }
+    }
+
+    /// Compile the pipeline on the worker thread, unless it's already compiled or queued.
+    /// Call [`PipelineCache::poll`] to collect the compiled pipelines.
+    pub fn compile_in_background(
+        &mut self,
+        device: &Arc<wgpu::Device>,
+        fragment_shader: String,
+        format: wgpu::TextureFormat,
+    ) {
+        let key = PipelineKey::new(&fragment_shader, format);
+        if self.pipelines.contains_key(&key) || self.pending.contains_key(&key) {
+            return;
+        }
+        if self.worker_stopped {
+            self.get_or_build(device, &fragment_shader, format);
+            return;
+        }
+
+        let builder = &self.builder;
+        let worker = self
+            .worker
+            .get_or_insert_with(|| Worker::spawn(device.clone(), builder.clone()));
+
+        if worker.request(key, fragment_shader.clone()) {
+            self.pending.insert(key, fragment_shader);
+        } else {
+            self.stop_worker(device);
+            self.get_or_build(device, &fragment_shader, format);
+        }
+    }
+
+    /// Add the pipelines compiled in the background to the cache.
+    pub fn poll(&mut self, device: &wgpu::Device) {
+        let Some(worker) = &mut self.worker else {
+            return;
+        };
+
+        let (compiled, running) = worker.compiled();
+        for (key, pipeline) in compiled {
+            log::debug!("Pipeline {key:?} compiled in the background");
+            self.pending.remove(&key);
+            self.insert(key, pipeline);
+        }
+        if !running {
+            self.stop_worker(device);
+        }
+    }
+
+    /// Compile the pipelines the stopped worker didn't, and every following one, synchronously.
+    fn stop_worker(&mut self, device: &wgpu::Device) {
+        log::error!(
+            "The pipeline compilation thread stopped, compiling the pipelines synchronously"
+        );
+        self.worker = None;
+        self.worker_stopped = true;
+        for (key, fragment_shader) in std::mem::take(&mut self.pending) {
+            self.get_or_build(device, &fragment_shader, key.format);
+        }
+    }
+}
+
+/// Move `key` to the most recently used end of `recent`.
+fn touch<K: PartialEq>(recent: &mut VecDeque<K>, key: K) {
+    recent.retain(|other| *other != key);
+    recent.push_back(key);
+}
+
+/// Remove the least recently used keys beyond `capacity`.
+fn evicted<K>(recent: &mut VecDeque<K>, capacity: usize) -> Vec<K> {
+    let count = recent.len().saturating_sub(capacity);
+    recent.drain(..count).collect()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Worker {
+    fn spawn(device: Arc<wgpu::Device>, builder: Arc<PipelineBuilder>) -> Self {
+        let (requests, requests_rx) = mpsc::channel::<(PipelineKey, String)>();
+        let (compiled_tx, compiled) = mpsc::channel();
+
+        std::thread::Builder::new()
+            .name("pipeline-compiler".to_string())
+            .spawn(move || {
+                for (key, fragment_shader) in requests_rx {
+                    let pipeline = builder.build(&device, &fragment_shader, key.format);
+                    if compiled_tx.send((key, Arc::new(pipeline))).is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("Failed to spawn the pipeline compilation thread");
+
+        Self { requests, compiled }
+    }
+
+    /// Return `false` if the thread stopped.
+    fn request(&mut self, key: PipelineKey, fragment_shader: String) -> bool {
+        self.requests.send((key, fragment_shader)).is_ok()
+    }
+
+    /// Return the compiled pipelines, and `false` if the thread stopped.
+    fn compiled(&mut self) -> (Vec<(PipelineKey, Arc<ToyPipeline>)>, bool) {
+        let mut compiled = Vec::new();
+        loop {
+            match self.compiled.try_recv() {
+                Ok(pipeline) => compiled.push(pipeline),
+                Err(mpsc::TryRecvError::Empty) => return (compiled, true),
+                Err(mpsc::TryRecvError::Disconnected) => return (compiled, false),
+            }
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Worker {
+    fn spawn(device: Arc<wgpu::Device>, builder: Arc<PipelineBuilder>) -> Self {
+        Self {
+            device,
+            builder,
+            requests: Default::default(),
+        }
+    }
+
+    fn request(&mut self, key: PipelineKey, fragment_shader: String) -> bool {
+        self.requests.push_back((key, fragment_shader));
+        true
+    }
+
+    /// Compile the oldest request, so a single frame doesn't compile every queued pipeline.
+    fn compiled(&mut self) -> (Vec<(PipelineKey, Arc<ToyPipeline>)>, bool) {
+        let compiled = self
+            .requests
+            .pop_front()
+            .map(|(key, fragment_shader)| {
+                let pipeline = self
+                    .builder
+                    .build(&self.device, &fragment_shader, key.format);
+                (key, Arc::new(pipeline))
+            })
+            .into_iter()
+            .collect();
+        (compiled, true)
+    }
+}
+
+impl PipelineBuilder {
+    fn build(
+        &self,
+        device: &wgpu::Device,
+        fragment_shader: &str,
+        format: wgpu::TextureFormat,
+    ) -> ToyPipeline {
+        // The vertex toys were assembled with the vertex suffix, they can't fall back to
+        // the fullscreen triangle, draw nothing instead.
+        let geometry = Geometry::parse(fragment_shader).unwrap_or_else(|e| {
+            log::error!("Invalid `// vertices:` comment: {e:#}");
+            Geometry::Vertices {
+                count: 0,
+                topology: wgpu::PrimitiveTopology::PointList,
+            }
+        });
+
+        let fs_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("fragment-shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::from(fragment_shader)),
+        });
+
+        let channels = channel::valid_channels(fragment_shader);
+        let channel_layout = channel::bind_group_layout(device, &channels);
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("render-pipeline-layout"),
+            bind_group_layouts: &[&self.uniform_layout, &channel_layout],
+            push_constant_ranges: &[],
+        });
+
+        let (vertex, topology, cull_mode, blend) = match geometry {
+            Geometry::Fullscreen => (
+                wgpu::VertexState {
+                    module: &self.vs_module,
+                    entry_point: "main",
+                    buffers: &[],
+                },
+                wgpu::PrimitiveTopology::TriangleList,
+                Some(wgpu::Face::Back),
+                wgpu::BlendState::REPLACE,
+            ),
+            Geometry::Vertices { topology, .. } => (
+                wgpu::VertexState {
+                    module: &fs_module,
+                    entry_point: "vertex",
+                    buffers: &[],
+                },
+                topology,
+                None,
+                wgpu::BlendState {
+                    color: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::One,
+                        dst_factor: wgpu::BlendFactor::One,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                    alpha: wgpu::BlendComponent::REPLACE,
+                },
+            ),
+        };
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("render-pipeline"),
+            layout: Some(&layout),
+            vertex,
+            primitive: wgpu::PrimitiveState {
+                topology,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode,
+                unclipped_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &fs_module,
+                entry_point: "main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(blend),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        });
+
+        let (channels, streams) =
+            channel::bind_group(device, &self.queue, &channel_layout, &channels);
+        ToyPipeline {
+            pipeline,
+            geometry,
+            channels,
+            streams: Mutex::new(streams),
+        }
+    }
+}
+
+/// Render the toy to the HDR target.
+///
+/// The fullscreen toys cover every pixel, the vertex toys are drawn on a black background.
+pub fn render_toy(
+    encoder: &mut wgpu::CommandEncoder,
+    pipeline: &ToyPipeline,
+    uniform_bind_group: &wgpu::BindGroup,
+    target: &wgpu::TextureView,
+) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("render-pass"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: target,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: match pipeline.geometry {
+                    Geometry::Fullscreen => wgpu::LoadOp::Load,
+                    Geometry::Vertices { .. } => wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                },
+                store: true,
+            },
+        })],
+        depth_stencil_attachment: None,
+    });
+
+    render_pass.set_pipeline(&pipeline.pipeline);
+    render_pass.set_bind_group(0, uniform_bind_group, &[]);
+    render_pass.set_bind_group(1, &pipeline.channels, &[]);
+    render_pass.draw(0..pipeline.geometry.vertex_count(), 0..1);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::{evicted, touch, Geometry, PipelineKey};
+
+    #[test]
+    fn least_recently_used() {
+        let mut recent = VecDeque::new();
+        for key in [1, 2, 3] {
+            touch(&mut recent, key);
+        }
+        touch(&mut recent, 1);
+        assert_eq!(recent, [2, 3, 1]);
+
+        touch(&mut recent, 4);
+        assert_eq!(evicted(&mut recent, 2), [2, 3]);
+        assert_eq!(recent, [1, 4]);
+        assert!(evicted(&mut recent, 2).is_empty());
+    }
+
+    #[test]
+    fn pipeline_key() {
+        let format = wgpu::TextureFormat::Rgba16Float;
+        let key = PipelineKey::new("fn main() {}", format);
+
+        assert_eq!(key, PipelineKey::new("fn main() {}", format));
+        assert_ne!(key, PipelineKey::new("fn main() { }", format));
+        assert_ne!(
+            key,
+            PipelineKey::new("fn main() {}", wgpu::TextureFormat::Rgba8Unorm)
+        );
+    }
+
+    #[test]
+    fn geometry() {
+        assert_eq!(
+            Geometry::parse("fn main_image() {}").unwrap(),
+            Geometry::Fullscreen
+        );
+        assert_eq!(
+            Geometry::parse("// tags: dots\n// vertices: 5000 points\n").unwrap(),
+            Geometry::Vertices {
+                count: 5000,
+                topology: wgpu::PrimitiveTopology::PointList
+            }
+        );
+        assert_eq!(
+            Geometry::parse("  // vertices: 300").unwrap(),
+            Geometry::Vertices {
+                count: 300,
+                topology: wgpu::PrimitiveTopology::TriangleList
+            }
+        );
+        assert!(Geometry::parse("// vertices: many lines").is_err());
+        assert!(Geometry::parse("// vertices: 10 quads").is_err());
+        assert!(Geometry::parse("// vertices: 10 lines strip").is_err());
+    }
+}
@@ -0,0 +1,434 @@
+use std::{borrow::Cow, fmt::Display};
+
+use crate::hdr::{HdrTarget, TonemapPass, HDR_FORMAT};
+
+/// A full-screen effect applied to the output of the toy, before the tonemap.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Effect {
+    Pixelate,
+    Bloom,
+    ChromaticAberration,
+    Scanlines,
+    Vignette,
+    FilmGrain,
+}
+
+impl Effect {
+    /// Every effect, in their default order in the chain.
+    pub const ALL: [Self; 6] = [
+        Self::Pixelate,
+        Self::Bloom,
+        Self::ChromaticAberration,
+        Self::Scanlines,
+        Self::Vignette,
+        Self::FilmGrain,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Pixelate => "pixelate",
+            Self::Bloom => "bloom",
+            Self::ChromaticAberration => "chromatic-aberration",
+            Self::Scanlines => "scanlines",
+            Self::Vignette => "vignette",
+            Self::FilmGrain => "film-grain",
+        }
+    }
+
+    /// The distance in window pixels of the farthest input read around a pixel,
+    /// from the constants of the WGSL of the effect.
+    fn radius(self, resolution: [f32; 2]) -> f32 {
+        match self {
+            // Half of `PIXEL_SIZE`, from a pixel to the center of its square.
+            Self::Pixelate => 4.,
+            Self::Bloom => 24.,
+            // `ABERRATION` times the distance from the center to a corner.
+            Self::ChromaticAberration => 0.008 * resolution[0].hypot(resolution[1]) / 2.,
+            Self::Scanlines | Self::Vignette | Self::FilmGrain => 0.,
+        }
+    }
+
+    /// The WGSL of the pass, the effect appended to the post prefix.
+    fn source(self) -> String {
+        let effect = match self {
+            Self::Pixelate => include_str!("assets/post/pixelate.wgsl"),
+            Self::Bloom => include_str!("assets/post/bloom.wgsl"),
+            Self::ChromaticAberration => include_str!("assets/post/chromatic-aberration.wgsl"),
+            Self::Scanlines => include_str!("assets/post/scanlines.wgsl"),
+            Self::Vignette => include_str!("assets/post/vignette.wgsl"),
+            Self::FilmGrain => include_str!("assets/post/film-grain.wgsl"),
+        };
+        format!("{}\n{effect}", include_str!("assets/post.prefix.wgsl"))
+    }
+}
+
+impl Display for Effect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The order of the effects and whether they are enabled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EffectChain {
+    effects: Vec<(Effect, bool)>,
+}
+
+impl EffectChain {
+    /// Enable `enabled` in this order, the other effects follow disabled in their default order.
+    pub fn new(enabled: &[Effect]) -> Self {
+        let mut effects = Vec::new();
+        for &effect in enabled.iter().chain(&Effect::ALL) {
+            if !effects.iter().any(|(other, _)| *other == effect) {
+                effects.push((effect, enabled.contains(&effect)));
+            }
+        }
+        Self { effects }
+    }
+
+    pub fn toggle(&mut self, effect: Effect) {
+        for (other, enabled) in &mut self.effects {
+            if *other == effect {
+                *enabled = !*enabled;
+            }
+        }
+    }
+
+    /// The enabled effects, in the order they are applied.
+    pub fn enabled(&self) -> impl Iterator<Item = Effect> + '_ {
+        self.effects
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(effect, _)| *effect)
+    }
+}
+
+/// The textures the toy and the effects render to.
+///
+/// The effects read the previous texture and write to the next one,
+/// the last one written is read by the tonemap pass.
+pub struct PostTargets {
+    /// The texture the toy renders to.
+    pub input: HdrTarget,
+    chain: [HdrTarget; 2],
+    /// Read the input and the two textures of the chain.
+    bind_groups: [wgpu::BindGroup; 3],
+}
+
+impl PostTargets {
+    /// A texture unused until the effects are applied, with the bind group reading it.
+    pub fn scratch(&self) -> (&HdrTarget, &wgpu::BindGroup) {
+        (&self.chain[0], &self.bind_groups[1])
+    }
+}
+
+/// The chain of full-screen effects applied after the toy.
+///
+/// The pipelines of every effect are built once, the effects can be enabled or reordered at
+/// runtime. The passes read the first fields of the toy uniforms, so the effects follow the
+/// tiles of the screenshots; the tiles are rendered with a [`PostStack::margin`] so the effects
+/// reading the neighbour pixels don't show their edges.
+pub struct PostStack {
+    chain: EffectChain,
+    /// One per effect, in the order of [`Effect::ALL`].
+    pipelines: Vec<wgpu::RenderPipeline>,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+}
+
+impl PostStack {
+    pub fn new(
+        device: &wgpu::Device,
+        uniform_layout: &wgpu::BindGroupLayout,
+        effects: &[Effect],
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("post-bind-group-layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("post-sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let pipelines = Effect::ALL
+            .iter()
+            .map(|effect| build_pipeline(device, uniform_layout, &bind_group_layout, *effect))
+            .collect();
+
+        Self {
+            chain: EffectChain::new(effects),
+            pipelines,
+            bind_group_layout,
+            sampler,
+        }
+    }
+
+    /// The layout of the bind groups reading the textures of [`PostTargets`].
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    /// The enabled effects, in the order they are applied.
+    pub fn effects(&self) -> Vec<Effect> {
+        self.chain.enabled().collect()
+    }
+
+    /// Enable `effects` in this order, disable the others.
+    pub fn set_effects(&mut self, effects: &[Effect]) {
+        self.chain = EffectChain::new(effects);
+    }
+
+    pub fn toggle(&mut self, effect: Effect) {
+        self.chain.toggle(effect);
+    }
+
+    /// The distance in window pixels of the farthest input read around a pixel by the enabled
+    /// effects, each one reads the output of the previous one.
+    pub fn margin(&self, resolution: [f32; 2]) -> f32 {
+        self.chain
+            .enabled()
+            .map(|effect| effect.radius(resolution))
+            .sum()
+    }
+
+    pub fn create_targets(
+        &self,
+        device: &wgpu::Device,
+        tonemap_pass: &TonemapPass,
+        width: u32,
+        height: u32,
+    ) -> PostTargets {
+        let input = tonemap_pass.create_target(device, width, height);
+        let chain = [(); 2].map(|_| tonemap_pass.create_target(device, width, height));
+        let bind_groups = [&input, &chain[0], &chain[1]].map(|target| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("post-bind-group"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&target.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+            })
+        });
+
+        PostTargets {
+            input,
+            chain,
+            bind_groups,
+        }
+    }
+
+    /// Apply the enabled effects to the input of `targets`, return the target to tonemap.
+    pub fn render<'a>(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        uniform_bind_group: &wgpu::BindGroup,
+        targets: &'a PostTargets,
+    ) -> &'a HdrTarget {
+        let mut output = &targets.input;
+        let mut bind_group = &targets.bind_groups[0];
+
+        for (index, effect) in self.chain.enabled().enumerate() {
+            let target = &targets.chain[index % 2];
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("post-pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_pipeline(&self.pipelines[effect as usize]);
+            render_pass.set_bind_group(0, uniform_bind_group, &[]);
+            render_pass.set_bind_group(1, bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+
+            output = target;
+            bind_group = &targets.bind_groups[index % 2 + 1];
+        }
+
+        output
+    }
+}
+
+fn build_pipeline(
+    device: &wgpu::Device,
+    uniform_layout: &wgpu::BindGroupLayout,
+    bind_group_layout: &wgpu::BindGroupLayout,
+    effect: Effect,
+) -> wgpu::RenderPipeline {
+    let vs_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("post-vertex-shader"),
+        source: wgpu::ShaderSource::Wgsl(Cow::from(include_str!("assets/vertex.wgsl"))),
+    });
+
+    let fs_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(&format!("post-{effect}-shader")),
+        source: wgpu::ShaderSource::Wgsl(Cow::from(effect.source())),
+    });
+
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("post-pipeline-layout"),
+        bind_group_layouts: &[uniform_layout, bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(&format!("post-{effect}-pipeline")),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &vs_module,
+            entry_point: "main",
+            buffers: &[],
+        },
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            unclipped_depth: false,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &fs_module,
+            entry_point: "main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format: HDR_FORMAT,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        multiview: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Effect, EffectChain};
+
+    #[test]
+    fn effect_chain() {
+        let mut chain = EffectChain::new(&[]);
+        assert_eq!(chain.enabled().count(), 0);
+
+        chain.toggle(Effect::Vignette);
+        chain.toggle(Effect::Bloom);
+        assert_eq!(
+            chain.enabled().collect::<Vec<_>>(),
+            [Effect::Bloom, Effect::Vignette]
+        );
+        chain.toggle(Effect::Vignette);
+        assert_eq!(chain.enabled().collect::<Vec<_>>(), [Effect::Bloom]);
+
+        // The configured order is kept when toggling.
+        let mut chain = EffectChain::new(&[Effect::FilmGrain, Effect::Pixelate, Effect::FilmGrain]);
+        assert_eq!(
+            chain.enabled().collect::<Vec<_>>(),
+            [Effect::FilmGrain, Effect::Pixelate]
+        );
+        chain.toggle(Effect::Scanlines);
+        chain.toggle(Effect::FilmGrain);
+        chain.toggle(Effect::FilmGrain);
+        assert_eq!(
+            chain.enabled().collect::<Vec<_>>(),
+            [Effect::FilmGrain, Effect::Pixelate, Effect::Scanlines]
+        );
+    }
+
+    #[test]
+    fn effect_names() {
+        for effect in Effect::ALL {
+            let parsed: Effect = serde_json::from_str(&format!("\"{effect}\"")).unwrap();
+            assert_eq!(parsed, effect);
+            assert_eq!(
+                clap::ValueEnum::to_possible_value(&effect)
+                    .unwrap()
+                    .get_name(),
+                effect.to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn effects_compile() {
+        for effect in Effect::ALL {
+            let module = naga::front::wgsl::parse_str(&effect.source())
+                .unwrap_or_else(|e| panic!("{effect}: {e}"));
+            naga::valid::Validator::new(
+                naga::valid::ValidationFlags::all(),
+                naga::valid::Capabilities::empty(),
+            )
+            .validate(&module)
+            .unwrap_or_else(|e| panic!("{effect}: {e:?}"));
+        }
+    }
+
+    #[test]
+    fn radius() {
+        let resolution = [800., 600.];
+        assert_eq!(Effect::Vignette.radius(resolution), 0.);
+        assert_eq!(Effect::ChromaticAberration.radius(resolution), 4.);
+        for effect in Effect::ALL {
+            let source = effect.source();
+            let constant = |name: &str| {
+                source
+                    .lines()
+                    .find_map(|line| line.strip_prefix(&format!("const {name}: f32 = ")))
+                    .map(|value| value.trim_end_matches(';').parse::<f32>().unwrap())
+            };
+            match effect {
+                Effect::Pixelate => assert_eq!(
+                    Some(effect.radius(resolution)),
+                    constant("PIXEL_SIZE").map(|size| size / 2.)
+                ),
+                Effect::Bloom => {
+                    assert_eq!(Some(effect.radius(resolution)), constant("BLOOM_RADIUS"))
+                }
+                Effect::ChromaticAberration => assert_eq!(constant("ABERRATION"), Some(0.008)),
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::{ops::RangeInclusive, time::Duration};
+
+use anyhow::Context;
+
+use instant::Instant;
+
+/// The present modes that can be requested from the command line or the config.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    clap::ValueEnum,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum PresentMode {
+    /// Wait for the vertical blank, no tearing (always supported).
+    #[default]
+    Fifo,
+    /// Replace the pending frame with the newest one, no tearing.
+    Mailbox,
+    /// Present as soon as possible, may tear.
+    Immediate,
+}
+
+impl PresentMode {
+    /// Select the requested present mode if the surface support it,
+    /// otherwise fallback to the closest one available, see [`wgpu_present::select`].
+    pub fn select(self, supported: &[wgpu::PresentMode]) -> wgpu::PresentMode {
+        let requested = match self {
+            Self::Fifo => wgpu::PresentMode::Fifo,
+            Self::Mailbox => wgpu::PresentMode::Mailbox,
+            Self::Immediate => wgpu::PresentMode::Immediate,
+        };
+        wgpu_present::select(requested, supported)
+    }
+}
+
+/// Frame rates accepted by `--max-fps`.
+pub const MAX_FPS_RANGE: RangeInclusive<f32> = 1.0..=1000.;
+
+/// Parse the `--max-fps` option, see [`MAX_FPS_RANGE`].
+pub fn parse_max_fps(s: &str) -> anyhow::Result<f32> {
+    let fps: f32 = s.parse().with_context(|| format!("`{s}` isn't a number"))?;
+    anyhow::ensure!(
+        MAX_FPS_RANGE.contains(&fps),
+        "The frame rate must be between {} and {}, not {fps}",
+        MAX_FPS_RANGE.start(),
+        MAX_FPS_RANGE.end()
+    );
+    Ok(fps)
+}
+
+/// Pace the redraw requests to a target frame rate.
+#[derive(Debug, Clone, Copy)]
+pub struct FrameLimiter {
+    frame_duration: Option<Duration>,
+    next_frame: Instant,
+}
+
+impl FrameLimiter {
+    pub fn new(target_fps: Option<f32>, now: Instant) -> Self {
+        Self {
+            frame_duration: target_fps
+                .filter(|fps| *fps > 0.)
+                .and_then(|fps| Duration::try_from_secs_f64(1. / f64::from(fps)).ok()),
+            next_frame: now,
+        }
+    }
+
+    /// Return `None` when a new frame should be rendered,
+    /// otherwise the instant at which the next frame is due.
+    pub fn wait_until(&mut self, now: Instant) -> Option<Instant> {
+        let frame_duration = self.frame_duration?;
+
+        if now < self.next_frame {
+            return Some(self.next_frame);
+        }
+
+        // Schedule from the previous deadline to keep a steady pace,
+        // unless we are already late for the next one.
+        self.next_frame += frame_duration;
+        if self.next_frame < now {
+            self.next_frame = now + frame_duration;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use instant::Instant;
+
+    use super::{parse_max_fps, FrameLimiter, PresentMode};
+
+    #[test]
+    fn select_present_mode() {
+        use wgpu::PresentMode as Mode;
+
+        let all = [Mode::Fifo, Mode::Mailbox, Mode::Immediate];
+        let fifo_only = [Mode::Fifo];
+        let no_mailbox = [Mode::Fifo, Mode::Immediate];
+
+        assert_eq!(PresentMode::Fifo.select(&all), Mode::Fifo);
+        assert_eq!(PresentMode::Mailbox.select(&all), Mode::Mailbox);
+        assert_eq!(PresentMode::Immediate.select(&all), Mode::Immediate);
+
+        assert_eq!(PresentMode::Mailbox.select(&fifo_only), Mode::Fifo);
+        assert_eq!(PresentMode::Immediate.select(&fifo_only), Mode::Fifo);
+
+        assert_eq!(PresentMode::Mailbox.select(&no_mailbox), Mode::Fifo);
+        assert_eq!(
+            PresentMode::Immediate.select(&[Mode::Fifo, Mode::Mailbox]),
+            Mode::Mailbox
+        );
+    }
+
+    #[test]
+    fn unlimited() {
+        let now = Instant::now();
+        let mut limiter = FrameLimiter::new(None, now);
+
+        assert_eq!(limiter.wait_until(now), None);
+        assert_eq!(limiter.wait_until(now), None);
+
+        for fps in [0., -1., f32::NAN, f32::MIN_POSITIVE] {
+            let mut limiter = FrameLimiter::new(Some(fps), now);
+            assert_eq!(limiter.wait_until(now), None, "{fps}");
+            assert_eq!(limiter.wait_until(now), None, "{fps}");
+        }
+    }
+
+    #[test]
+    fn max_fps() {
+        assert_eq!(parse_max_fps("60").unwrap(), 60.);
+        for fps in ["0", "-30", "inf", "NaN", "0.0001", "fast"] {
+            assert!(parse_max_fps(fps).is_err(), "{fps}");
+        }
+    }
+
+    #[test]
+    fn limited() {
+        let start = Instant::now();
+        let frame = Duration::from_millis(100);
+        let mut limiter = FrameLimiter::new(Some(10.), start);
+
+        assert_eq!(limiter.wait_until(start), None);
+        assert_eq!(limiter.wait_until(start), Some(start + frame));
+        assert_eq!(limiter.wait_until(start + frame / 2), Some(start + frame));
+        assert_eq!(limiter.wait_until(start + frame), None);
+        assert_eq!(limiter.wait_until(start + frame), Some(start + frame * 2));
+        // Too late for the next frame, reschedule from now.
+        assert_eq!(limiter.wait_until(start + frame * 5), None);
+        assert_eq!(
+            limiter.wait_until(start + frame * 5),
+            Some(start + frame * 6)
+        );
+    }
+}
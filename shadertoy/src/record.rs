@@ -0,0 +1,229 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::Context;
+
+use crate::{
+    input::InputEvent,
+    osc::{ToyAction, PARAM_COUNT},
+    remote::Request,
+};
+
+/// The OSC values applied before a frame, recorded as a whole since they are smoothed over time.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct OscFrame {
+    pub params: [f32; PARAM_COUNT],
+    pub speed: Option<f32>,
+    pub actions: Vec<ToyAction>,
+}
+
+/// A line of a recording, in JSON.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Entry {
+    /// The toy and the size of the window when the recording started, always the first entry.
+    Start { toy: String, size: [u32; 2] },
+    /// An input event, given to the viewer before the next frame.
+    Input(InputEvent),
+    /// A request of the remote control, handled before the next frame.
+    Remote(Request),
+    /// The OSC values of the next frame.
+    Osc(OscFrame),
+    /// A frame rendered at the time `time` of the toy, in seconds.
+    Frame { time: f32 },
+}
+
+/// Write the inputs and the frames to a JSON lines file.
+pub struct Recorder<W: Write> {
+    writer: W,
+}
+
+impl Recorder<BufWriter<File>> {
+    pub fn create(path: &Path) -> anyhow::Result<Self> {
+        let file =
+            File::create(path).with_context(|| format!("Can't create {}", path.display()))?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn start(&mut self, toy: &str, size: [u32; 2]) -> std::io::Result<()> {
+        self.write(&Entry::Start {
+            toy: toy.to_owned(),
+            size,
+        })
+    }
+
+    pub fn input(&mut self, event: InputEvent) -> std::io::Result<()> {
+        self.write(&Entry::Input(event))
+    }
+
+    pub fn remote(&mut self, request: &Request) -> std::io::Result<()> {
+        self.write(&Entry::Remote(request.clone()))
+    }
+
+    pub fn osc(&mut self, osc: &OscFrame) -> std::io::Result<()> {
+        self.write(&Entry::Osc(osc.clone()))
+    }
+
+    /// Record a frame and flush, so the recording is complete even if the viewer crashes.
+    pub fn frame(&mut self, time: f32) -> std::io::Result<()> {
+        self.write(&Entry::Frame { time })?;
+        self.writer.flush()
+    }
+
+    fn write(&mut self, entry: &Entry) -> std::io::Result<()> {
+        serde_json::to_writer(&mut self.writer, entry)?;
+        self.writer.write_all(b"\n")
+    }
+}
+
+/// A recording played back frame by frame.
+pub struct Replay {
+    toy: String,
+    size: [u32; 2],
+    entries: VecDeque<Entry>,
+    /// Number of frames already played.
+    frame: usize,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path).with_context(|| format!("Can't open {}", path.display()))?;
+        Self::parse(BufReader::new(file))
+            .with_context(|| format!("Invalid recording {}", path.display()))
+    }
+
+    pub fn parse(reader: impl BufRead) -> anyhow::Result<Self> {
+        let mut entries = VecDeque::new();
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry = serde_json::from_str(&line)
+                .with_context(|| format!("Invalid entry on line {}", number + 1))?;
+            entries.push_back(entry);
+        }
+
+        match entries.pop_front() {
+            Some(Entry::Start { toy, size }) => Ok(Self {
+                toy,
+                size,
+                entries,
+                frame: 0,
+            }),
+            _ => anyhow::bail!("The recording doesn't start with a `start` entry"),
+        }
+    }
+
+    /// The toy shown when the recording started.
+    pub fn toy(&self) -> &str {
+        &self.toy
+    }
+
+    /// The size of the window when the recording started.
+    pub fn size(&self) -> [u32; 2] {
+        self.size
+    }
+
+    /// Number of frames already played.
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// The inputs, remote requests and OSC values to apply before the next frame
+    /// and the time of this frame, `None` once the recording is over.
+    pub fn next_frame(&mut self) -> Option<(Vec<Entry>, f32)> {
+        let mut entries = Vec::new();
+        while let Some(entry) = self.entries.pop_front() {
+            match entry {
+                Entry::Frame { time } => {
+                    self.frame += 1;
+                    return Some((entries, time));
+                }
+                Entry::Start { .. } => log::warn!("Ignoring a `start` entry in the recording"),
+                entry => entries.push(entry),
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::event::{ElementState, MouseScrollDelta, VirtualKeyCode};
+
+    use super::{Entry, InputEvent, OscFrame, Recorder, Replay};
+    use crate::{osc::ToyAction, remote::Request};
+
+    #[test]
+    fn record_and_replay() {
+        let mut recorder = Recorder::new(Vec::new());
+        recorder.start("ring", [800, 600]).unwrap();
+        recorder.frame(0.).unwrap();
+        let inputs = [
+            InputEvent::Character { character: '3' },
+            InputEvent::Key {
+                key: VirtualKeyCode::Return,
+                state: ElementState::Pressed,
+            },
+            InputEvent::CursorMoved {
+                position: [12.5, 40.],
+            },
+            InputEvent::MouseWheel {
+                delta: MouseScrollDelta::LineDelta(0., -2.),
+            },
+        ];
+        for input in inputs {
+            recorder.input(input).unwrap();
+        }
+        let request = Request::SelectToy { toy: "neon".into() };
+        recorder.remote(&request).unwrap();
+        let osc = OscFrame {
+            params: [0.5; 8],
+            speed: Some(2.),
+            actions: vec![ToyAction::Next],
+        };
+        recorder.osc(&osc).unwrap();
+        recorder.frame(0.5).unwrap();
+
+        let recording = String::from_utf8(recorder.writer).unwrap();
+        assert_eq!(recording.lines().count(), 9);
+
+        let mut replay = Replay::parse(recording.as_bytes()).unwrap();
+        assert_eq!(replay.toy(), "ring");
+        assert_eq!(replay.size(), [800, 600]);
+        assert_eq!(replay.next_frame(), Some((vec![], 0.)));
+        assert_eq!(
+            replay.next_frame(),
+            Some((
+                inputs
+                    .into_iter()
+                    .map(Entry::Input)
+                    .chain([Entry::Remote(request), Entry::Osc(osc)])
+                    .collect(),
+                0.5
+            ))
+        );
+        assert_eq!(replay.frame(), 2);
+        assert_eq!(replay.next_frame(), None);
+    }
+
+    #[test]
+    fn invalid_recording() {
+        assert!(Replay::parse(r#"{"type": "frame", "time": 0}"#.as_bytes()).is_err());
+        assert!(Replay::parse(
+            "{\"type\": \"start\", \"toy\": \"ring\", \"size\": [1, 1]}\nnot json".as_bytes()
+        )
+        .is_err());
+    }
+}
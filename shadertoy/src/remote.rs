@@ -0,0 +1,513 @@
+use std::sync::mpsc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    net::{SocketAddr, ToSocketAddrs},
+    time::Duration,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use anyhow::Context;
+#[cfg(not(target_arch = "wasm32"))]
+use winit::event_loop::EventLoopProxy;
+
+use crate::{hdr::Tonemap, layer::BlendMode, post::Effect};
+
+/// How long a client wait for the viewer to answer, a high resolution screenshot can be slow.
+#[cfg(not(target_arch = "wasm32"))]
+const REPLY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A command sent by a remote client, e.g. `{"command": "select_toy", "toy": "ring"}`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Request {
+    /// The number, name, category and tags of every toy.
+    ListToys,
+    /// The current toy, time, tonemap, layers, effects and view.
+    Status,
+    /// Select a toy by its number, starting from 1, or by the best match of a search.
+    SelectToy {
+        toy: String,
+    },
+    /// Select the layer controlled by the next requests by its number, starting from 1 at the
+    /// bottom, or the toy under the layers with 0.
+    SelectLayer {
+        layer: usize,
+    },
+    /// Change the tonemap, the enabled effects, the speed of the time and the view,
+    /// the parameters not given are unchanged.
+    ///
+    /// The speed and the view are the ones of the selected layer, the blend mode and the opacity
+    /// can only be changed with a layer selected.
+    SetParameters {
+        tonemap: Option<Tonemap>,
+        effects: Option<Vec<Effect>>,
+        speed: Option<f64>,
+        zoom: Option<f64>,
+        center: Option<[f64; 2]>,
+        angle: Option<f64>,
+        blend: Option<BlendMode>,
+        opacity: Option<f32>,
+    },
+    Pause,
+    Resume,
+    /// Save a screenshot `scale` times bigger than the window, 1 by default.
+    Screenshot {
+        scale: Option<u32>,
+    },
+}
+
+impl Request {
+    /// Check the values a JSON number can hold but the viewer can't use.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if let Self::SetParameters {
+            speed,
+            zoom,
+            center,
+            angle,
+            opacity,
+            ..
+        } = self
+        {
+            let values = [
+                *speed,
+                *zoom,
+                center.map(|c| c[0]),
+                center.map(|c| c[1]),
+                *angle,
+            ];
+            anyhow::ensure!(
+                values.into_iter().flatten().all(f64::is_finite),
+                "The speed, zoom, center and angle must be finite"
+            );
+            anyhow::ensure!(
+                zoom.map_or(true, |zoom| zoom > 0.),
+                "The zoom must be positive"
+            );
+            anyhow::ensure!(
+                opacity.map_or(true, |opacity| (0. ..=1.).contains(&opacity)),
+                "The opacity must be between 0 and 1"
+            );
+        }
+        Ok(())
+    }
+}
+
+/// The answer to a request, `{"ok": ...}` or `{"error": "..."}`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Response {
+    Ok(serde_json::Value),
+    Error(String),
+}
+
+impl From<anyhow::Result<serde_json::Value>> for Response {
+    fn from(result: anyhow::Result<serde_json::Value>) -> Self {
+        match result {
+            Ok(value) => Self::Ok(value),
+            Err(e) => Self::Error(format!("{e:#}")),
+        }
+    }
+}
+
+/// A request sent to the event loop, answered with [`RemoteEvent::reply`].
+#[derive(Debug)]
+pub struct RemoteEvent {
+    pub request: Request,
+    reply: mpsc::Sender<Response>,
+}
+
+impl RemoteEvent {
+    pub fn reply(self, response: Response) {
+        // The client may have given up waiting.
+        let _ = self.reply.send(response);
+    }
+}
+
+/// Deliver the requests to the viewer.
+#[cfg(not(target_arch = "wasm32"))]
+pub trait Dispatch: Clone + Send + 'static {
+    /// Return `false` if the viewer is closed.
+    fn dispatch(&self, event: RemoteEvent) -> bool;
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Dispatch for EventLoopProxy<RemoteEvent> {
+    fn dispatch(&self, event: RemoteEvent) -> bool {
+        self.send_event(event).is_ok()
+    }
+}
+
+/// Serve the remote control on `address`, return the address actually bound.
+///
+/// - `GET /toys` and `GET /status` answer the `list_toys` and `status` requests,
+/// - `POST /command` answer the request sent as body,
+/// - `/ws` upgrade to a WebSocket, every text message is a request answered by a response.
+///
+/// Requests whose `Host` or `Origin` isn't localhost are forbidden,
+/// so web pages can't drive the viewer from the browser of the user.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn serve(address: impl ToSocketAddrs, dispatcher: impl Dispatch) -> anyhow::Result<SocketAddr> {
+    let server = tiny_http::Server::http(address)
+        .map_err(|e| anyhow::anyhow!(e))
+        .context("Can't start the remote control server")?;
+    let address = server
+        .server_addr()
+        .to_ip()
+        .context("The remote control server isn't bound to an IP address")?;
+
+    std::thread::Builder::new()
+        .name("remote-control".into())
+        .spawn(move || {
+            for request in server.incoming_requests() {
+                handle_http(request, &dispatcher);
+            }
+        })
+        .context("Failed to spawn the remote control thread")?;
+
+    Ok(address)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn handle_http(mut request: tiny_http::Request, dispatcher: &impl Dispatch) {
+    use tiny_http::Method;
+
+    if !is_local(&request) {
+        let _ = request.respond(tiny_http::Response::empty(403));
+        return;
+    }
+
+    let route = (request.method().clone(), request.url().to_owned());
+    let response = match (&route.0, route.1.as_str()) {
+        (Method::Get, "/ws") => return upgrade(request, dispatcher.clone()),
+        (Method::Get, "/toys") => send(dispatcher, Request::ListToys),
+        (Method::Get, "/status") => send(dispatcher, Request::Status),
+        (Method::Post, "/command") => {
+            let mut body = String::new();
+            match request.as_reader().read_to_string(&mut body) {
+                Ok(_) => handle_message(dispatcher, &body),
+                Err(e) => Response::Error(format!("Can't read the request: {e}")),
+            }
+        }
+        _ => {
+            let _ = request.respond(tiny_http::Response::empty(404));
+            return;
+        }
+    };
+
+    let status = match response {
+        Response::Ok(_) => 200,
+        Response::Error(_) => 400,
+    };
+    let _ = request.respond(
+        tiny_http::Response::from_string(to_json(&response))
+            .with_status_code(status)
+            .with_header(header("Content-Type", "application/json")),
+    );
+}
+
+/// Answer the WebSocket handshake and handle the messages on a new thread.
+#[cfg(not(target_arch = "wasm32"))]
+fn upgrade(request: tiny_http::Request, dispatcher: impl Dispatch) {
+    use tungstenite::{handshake::derive_accept_key, protocol::Role, Message, WebSocket};
+
+    let key = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Sec-WebSocket-Key"))
+        .map(|header| derive_accept_key(header.value.as_bytes()));
+    let Some(accept) = key else {
+        let _ = request.respond(tiny_http::Response::empty(400));
+        return;
+    };
+
+    let response = tiny_http::Response::empty(101)
+        .with_header(header("Upgrade", "websocket"))
+        .with_header(header("Connection", "Upgrade"))
+        .with_header(header("Sec-WebSocket-Accept", &accept));
+    let stream = request.upgrade("websocket", response);
+
+    std::thread::spawn(move || {
+        let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+        // Pings and close frames are answered by `read`, which fails once the connection is closed.
+        while let Ok(message) = socket.read() {
+            if let Message::Text(text) = message {
+                let response = handle_message(&dispatcher, &text);
+                if socket.send(Message::Text(to_json(&response))).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+}
+
+/// Whether the `Host` and `Origin` headers, when given, are localhost.
+#[cfg(not(target_arch = "wasm32"))]
+fn is_local(request: &tiny_http::Request) -> bool {
+    request.headers().iter().all(|header| {
+        if header.field.equiv("Host") {
+            is_localhost(header.value.as_str())
+        } else if header.field.equiv("Origin") {
+            header
+                .value
+                .as_str()
+                .split_once("://")
+                .is_some_and(|(_, host)| is_localhost(host))
+        } else {
+            true
+        }
+    })
+}
+
+/// Whether `host`, followed by an optional port, is localhost.
+#[cfg(not(target_arch = "wasm32"))]
+fn is_localhost(host: &str) -> bool {
+    let host = match host.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    ["localhost", "127.0.0.1", "::1"]
+        .iter()
+        .any(|local| host.eq_ignore_ascii_case(local))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn handle_message(dispatcher: &impl Dispatch, message: &str) -> Response {
+    match serde_json::from_str(message) {
+        Ok(request) => send(dispatcher, request),
+        Err(e) => Response::Error(format!("Invalid request: {e}")),
+    }
+}
+
+/// Send the request to the viewer and wait for its answer.
+#[cfg(not(target_arch = "wasm32"))]
+fn send(dispatcher: &impl Dispatch, request: Request) -> Response {
+    let (reply, answer) = mpsc::channel();
+
+    if !dispatcher.dispatch(RemoteEvent { request, reply }) {
+        return Response::Error("The viewer is closed".into());
+    }
+    answer
+        .recv_timeout(REPLY_TIMEOUT)
+        .unwrap_or_else(|_| Response::Error("The viewer didn't answer".into()))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn to_json(response: &Response) -> String {
+    serde_json::to_string(response).expect("Failed to serialize the response")
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn header(field: &str, value: &str) -> tiny_http::Header {
+    tiny_http::Header::from_bytes(field, value).expect("Invalid header")
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+    };
+
+    use serde_json::json;
+    use tungstenite::Message;
+
+    use super::{is_localhost, serve, Dispatch, RemoteEvent, Request, Response};
+    use crate::{hdr::Tonemap, post::Effect};
+
+    /// Answer the requests right away, like the viewer would.
+    #[derive(Clone)]
+    struct Viewer;
+
+    impl Dispatch for Viewer {
+        fn dispatch(&self, event: RemoteEvent) -> bool {
+            let response = match &event.request {
+                Request::ListToys => Response::Ok(json!(["ring"])),
+                Request::SelectToy { toy } => Response::Ok(json!({ "toy": toy })),
+                request => Response::Error(format!("Unexpected request {request:?}")),
+            };
+            event.reply(response);
+            true
+        }
+    }
+
+    #[test]
+    fn parse_requests() {
+        assert_eq!(
+            serde_json::from_str::<Request>(r#"{"command": "select_toy", "toy": "ring"}"#).unwrap(),
+            Request::SelectToy { toy: "ring".into() }
+        );
+        assert_eq!(
+            serde_json::from_str::<Request>(
+                r#"{"command": "set_parameters", "tonemap": "aces", "effects": ["bloom", "film-grain"], "zoom": 2}"#
+            )
+            .unwrap(),
+            Request::SetParameters {
+                tonemap: Some(Tonemap::Aces),
+                effects: Some(vec![Effect::Bloom, Effect::FilmGrain]),
+                speed: None,
+                zoom: Some(2.),
+                center: None,
+                angle: None,
+                blend: None,
+                opacity: None,
+            }
+        );
+        assert_eq!(
+            serde_json::from_str::<Request>(r#"{"command": "select_layer", "layer": 2}"#).unwrap(),
+            Request::SelectLayer { layer: 2 }
+        );
+        assert!(serde_json::from_str::<Request>(r#"{"command": "explode"}"#).is_err());
+
+        let set_parameters = |json: &str| serde_json::from_str::<Request>(json).unwrap();
+        set_parameters(r#"{"command": "set_parameters", "zoom": 2, "center": [0, 1]}"#)
+            .validate()
+            .unwrap();
+        assert!(
+            set_parameters(r#"{"command": "set_parameters", "zoom": 0}"#)
+                .validate()
+                .is_err()
+        );
+        // JSON has no infinity, but other clients may build the requests.
+        assert!(Request::SetParameters {
+            tonemap: None,
+            effects: None,
+            speed: None,
+            zoom: None,
+            center: Some([0., f64::NAN]),
+            angle: None,
+            blend: None,
+            opacity: None,
+        }
+        .validate()
+        .is_err());
+        set_parameters(r#"{"command": "set_parameters", "blend": "screen", "opacity": 0.5}"#)
+            .validate()
+            .unwrap();
+        assert!(
+            set_parameters(r#"{"command": "set_parameters", "opacity": 2}"#)
+                .validate()
+                .is_err()
+        );
+        assert!(serde_json::from_str::<Request>(
+            r#"{"command": "select_toy", "toy": "ring", "now": true}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn response_json() {
+        assert_eq!(
+            serde_json::to_value(Response::Ok(json!({ "paused": true }))).unwrap(),
+            json!({ "ok": { "paused": true } })
+        );
+        assert_eq!(
+            serde_json::to_value(Response::Error("No toy match".into())).unwrap(),
+            json!({ "error": "No toy match" })
+        );
+    }
+
+    #[test]
+    fn http() {
+        let address = serve("127.0.0.1:0", Viewer).unwrap();
+
+        let get = |request: &str| {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream.write_all(request.as_bytes()).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+
+        let response = get("GET /toys HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+        assert!(response.ends_with(r#"{"ok":["ring"]}"#), "{response}");
+
+        let body = r#"{"command": "resume"}"#;
+        let response = get(&format!(
+            "POST /command HTTP/1.1\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        ));
+        assert!(response.starts_with("HTTP/1.1 400"), "{response}");
+        assert!(
+            response.contains(r#"{"error":"Unexpected request Resume"}"#),
+            "{response}"
+        );
+
+        let response = get("GET /nothing HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 404"), "{response}");
+    }
+
+    #[test]
+    fn foreign_origin() {
+        let address = serve("127.0.0.1:0", Viewer).unwrap();
+
+        let request = |headers: &str| {
+            let body = r#"{"command": "list_toys"}"#;
+            let mut stream = TcpStream::connect(address).unwrap();
+            write!(
+                stream,
+                "POST /command HTTP/1.1\r\nConnection: close\r\n{headers}Content-Length: {}\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+
+        let response = request("Origin: http://evil.example\r\n");
+        assert!(response.starts_with("HTTP/1.1 403"), "{response}");
+        let response = request("Host: evil.example:8080\r\n");
+        assert!(response.starts_with("HTTP/1.1 403"), "{response}");
+        let response = request(&format!(
+            "Host: {address}\r\nOrigin: http://localhost:{}\r\n",
+            address.port()
+        ));
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+
+        let stream = TcpStream::connect(address).unwrap();
+        let request = tungstenite::http::Request::builder()
+            .uri(format!("ws://{address}/ws"))
+            .header("Host", address.to_string())
+            .header("Origin", "https://evil.example")
+            .header("Connection", "Upgrade")
+            .header("Upgrade", "websocket")
+            .header("Sec-WebSocket-Version", "13")
+            .header(
+                "Sec-WebSocket-Key",
+                tungstenite::handshake::client::generate_key(),
+            )
+            .body(())
+            .unwrap();
+        assert!(tungstenite::client(request, stream).is_err());
+
+        assert!(is_localhost("localhost"));
+        assert!(is_localhost("[::1]:8080"));
+        assert!(!is_localhost("localhost.evil.example"));
+    }
+
+    #[test]
+    fn websocket() {
+        let address = serve("127.0.0.1:0", Viewer).unwrap();
+        let stream = TcpStream::connect(address).unwrap();
+        let (mut socket, _) = tungstenite::client(format!("ws://{address}/ws"), stream).unwrap();
+
+        socket
+            .send(Message::Text(
+                r#"{"command": "select_toy", "toy": "3"}"#.into(),
+            ))
+            .unwrap();
+        assert_eq!(
+            socket.read().unwrap(),
+            Message::Text(r#"{"ok":{"toy":"3"}}"#.into())
+        );
+
+        socket.send(Message::Text("not json".into())).unwrap();
+        let Message::Text(text) = socket.read().unwrap() else {
+            panic!("Expected a text message");
+        };
+        assert!(text.starts_with(r#"{"error":"Invalid request"#), "{text}");
+
+        socket.close(None).unwrap();
+    }
+}
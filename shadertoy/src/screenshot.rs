@@ -0,0 +1,315 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::Context;
+
+use crate::{
+    hdr::{Tonemap, TonemapPass},
+    layer::LayerStack,
+    pipeline,
+    post::{PostStack, PostTargets},
+    uniform::Uniform,
+};
+
+/// Maximum size of the tiles, the frame is rendered tile by tile to allow screenshots
+/// bigger than the maximum texture size.
+const TILE_SIZE: u32 = 2048;
+const OUTPUT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+const BYTES_PER_PIXEL: u32 = 4;
+
+/// A region of the final image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Tile {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+/// Split the image in rows of tiles, from top to bottom.
+fn tile_rows(width: u32, height: u32, tile_size: u32) -> impl Iterator<Item = Vec<Tile>> {
+    (0..height).step_by(tile_size as usize).map(move |y| {
+        (0..width)
+            .step_by(tile_size as usize)
+            .map(|x| Tile {
+                x,
+                y,
+                width: tile_size.min(width - x),
+                height: tile_size.min(height - y),
+            })
+            .collect()
+    })
+}
+
+/// Everything needed to render the current toy.
+pub struct Screenshot<'a> {
+    pub device: &'a wgpu::Device,
+    pub queue: &'a wgpu::Queue,
+    pub pipeline: &'a pipeline::ToyPipeline,
+    pub uniform_buffer: &'a wgpu::Buffer,
+    pub uniform_bind_group: &'a wgpu::BindGroup,
+    pub layers: &'a LayerStack,
+    pub post: &'a PostStack,
+    pub tonemap: Tonemap,
+}
+
+/// The textures and buffer used to render a tile and read it back.
+///
+/// The tile is rendered with a margin on every side, so the effects reading the neighbour pixels
+/// see the same image as on the other side of the edge, and only the inside is read back.
+struct TileTarget {
+    margin: u32,
+    tonemap_pass: TonemapPass,
+    targets: PostTargets,
+    output: wgpu::Texture,
+    output_view: wgpu::TextureView,
+    readback_buffer: wgpu::Buffer,
+}
+
+impl Screenshot<'_> {
+    /// Render the toy `factor` times bigger than the uniform resolution and save it as a PNG.
+    ///
+    /// The toy keep seeing the original resolution and a scaled down fragment coordinate,
+    /// so the image look the same, only with more details.
+    /// The rows of tiles are streamed to the file as soon as they are rendered,
+    /// so the whole image never need to be in memory.
+    pub fn save(&self, uniform: Uniform, factor: u32, path: &Path) -> anyhow::Result<()> {
+        let width = uniform.resolution[0] as u32 * factor;
+        let height = uniform.resolution[1] as u32 * factor;
+        anyhow::ensure!(width > 0 && height > 0, "Can't save an empty screenshot");
+
+        let max_size = self.device.limits().max_texture_dimension_2d;
+        // A single tile has no seam. The margin covers the reach of the effects in the pixels of
+        // the screenshot, plus one for the filtering, but leaves room for the tile.
+        let radius = self
+            .post
+            .margin([uniform.resolution[0], uniform.resolution[1]]);
+        let margin = if width.max(height) > TILE_SIZE.min(max_size) && radius > 0. {
+            ((radius * factor as f32).ceil() as u32 + 1).min(max_size / 4)
+        } else {
+            0
+        };
+        let tile_size = TILE_SIZE.min(max_size - 2 * margin).min(width.max(height));
+        let target = self.create_tile_target(tile_size, margin);
+
+        let file =
+            File::create(path).with_context(|| format!("Can't create {}", path.display()))?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+        let mut writer = encoder.write_header()?;
+        let mut stream = writer.stream_writer()?;
+
+        let row_bytes = (width * BYTES_PER_PIXEL) as usize;
+        let padded_bytes_per_row = padded_bytes_per_row(tile_size) as usize;
+
+        for tiles in tile_rows(width, height, tile_size) {
+            let rows = tiles[0].height as usize;
+            let mut pixels = vec![0u8; row_bytes * rows];
+
+            for tile in tiles {
+                let mut uniform = uniform;
+                uniform.scale = 1. / factor as f32;
+                uniform.offset =
+                    [tile.x, tile.y].map(|v| (v as f32 - margin as f32) / factor as f32);
+                uniform.target_resolution = [(tile_size + 2 * margin) as f32; 2];
+                self.render_tile(&target, &uniform, tile);
+
+                let slice = target.readback_buffer.slice(..);
+                let (tx, rx) = std::sync::mpsc::channel();
+                slice.map_async(wgpu::MapMode::Read, move |res| {
+                    let _ = tx.send(res);
+                });
+                self.device.poll(wgpu::Maintain::Wait);
+                rx.recv()
+                    .context("The readback buffer was dropped")?
+                    .context("Can't map the readback buffer")?;
+
+                {
+                    let view = slice.get_mapped_range();
+                    let tile_bytes = (tile.width * BYTES_PER_PIXEL) as usize;
+                    let offset = (tile.x * BYTES_PER_PIXEL) as usize;
+
+                    for (row, src) in view.chunks(padded_bytes_per_row).take(rows).enumerate() {
+                        let dst = row * row_bytes + offset;
+                        pixels[dst..dst + tile_bytes].copy_from_slice(&src[..tile_bytes]);
+                    }
+                }
+                target.readback_buffer.unmap();
+            }
+
+            stream.write_all(&pixels)?;
+        }
+
+        stream.finish()?;
+        Ok(())
+    }
+
+    fn create_tile_target(&self, tile_size: u32, margin: u32) -> TileTarget {
+        // The screenshots are always rendered at full scale.
+        let tonemap_pass = TonemapPass::new(self.device, OUTPUT_FORMAT, self.tonemap, 1.);
+        let size = tile_size + 2 * margin;
+        let targets = self
+            .post
+            .create_targets(self.device, &tonemap_pass, size, size);
+
+        let output = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("screenshot-texture"),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: OUTPUT_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let output_view = output.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let readback_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("screenshot-readback-buffer"),
+            size: u64::from(padded_bytes_per_row(tile_size)) * u64::from(tile_size),
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        TileTarget {
+            margin,
+            tonemap_pass,
+            targets,
+            output,
+            output_view,
+            readback_buffer,
+        }
+    }
+
+    fn render_tile(&self, target: &TileTarget, uniform: &Uniform, tile: Tile) {
+        self.queue
+            .write_buffer(self.uniform_buffer, 0, uniform.as_ref());
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("screenshot-encoder"),
+            });
+
+        pipeline::render_toy(
+            &mut encoder,
+            self.pipeline,
+            self.uniform_bind_group,
+            &target.targets.input.view,
+        );
+        self.layers.render(&mut encoder, uniform, &target.targets);
+        let hdr_target = self
+            .post
+            .render(&mut encoder, self.uniform_bind_group, &target.targets);
+        target
+            .tonemap_pass
+            .render(&mut encoder, hdr_target, &target.output_view);
+
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                origin: wgpu::Origin3d {
+                    x: target.margin,
+                    y: target.margin,
+                    z: 0,
+                },
+                ..target.output.as_image_copy()
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &target.readback_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row(
+                        target.output.width() - 2 * target.margin,
+                    )),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: tile.width,
+                height: tile.height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+    }
+}
+
+/// The rows copied to a buffer must be aligned to [`wgpu::COPY_BYTES_PER_ROW_ALIGNMENT`].
+pub fn padded_bytes_per_row(width: u32) -> u32 {
+    let bytes_per_row = width * BYTES_PER_PIXEL;
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    bytes_per_row + (align - bytes_per_row % align) % align
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{padded_bytes_per_row, tile_rows, Tile};
+
+    #[test]
+    fn tiles() {
+        let rows = tile_rows(5, 3, 2).collect::<Vec<_>>();
+
+        assert_eq!(
+            rows,
+            [
+                vec![
+                    Tile {
+                        x: 0,
+                        y: 0,
+                        width: 2,
+                        height: 2
+                    },
+                    Tile {
+                        x: 2,
+                        y: 0,
+                        width: 2,
+                        height: 2
+                    },
+                    Tile {
+                        x: 4,
+                        y: 0,
+                        width: 1,
+                        height: 2
+                    },
+                ],
+                vec![
+                    Tile {
+                        x: 0,
+                        y: 2,
+                        width: 2,
+                        height: 1
+                    },
+                    Tile {
+                        x: 2,
+                        y: 2,
+                        width: 2,
+                        height: 1
+                    },
+                    Tile {
+                        x: 4,
+                        y: 2,
+                        width: 1,
+                        height: 1
+                    },
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn padding() {
+        assert_eq!(padded_bytes_per_row(1), 256);
+        assert_eq!(padded_bytes_per_row(64), 256);
+        assert_eq!(padded_bytes_per_row(65), 512);
+    }
+}
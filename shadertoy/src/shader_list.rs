@@ -1,56 +1,574 @@
+use crate::channel;
+
 type RawList = &'static [(&'static str, &'static str)];
 
-#[derive(Debug, Clone, Copy)]
+/// Prefix of the comment listing the tags of a toy, e.g. `// tags: fractal, color`.
+const TAGS_PREFIX: &str = "// tags:";
+/// Prefix of the comment declaring a variant of a toy, e.g. `// variant: rgb PALETTE=true`,
+/// the values can't contain spaces.
+const VARIANT_PREFIX: &str = "// variant:";
+/// Prefix of the comment turning a toy into a vertex toy, e.g. `// vertices: 5000 lines`,
+/// see [`crate::pipeline::Geometry`].
+const VERTICES_PREFIX: &str = "// vertices:";
+
+/// A named set of values for the `override` constants of a toy,
+/// the constants not listed keep their default value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant<'a> {
+    pub name: &'a str,
+    pub values: Vec<(&'a str, &'a str)>,
+}
+
+#[derive(Debug, Clone)]
 pub struct ShaderList {
     prefix: &'static str,
     suffix: &'static str,
+    /// The suffix of the vertex toys, calling their `vertex_main` function.
+    vertex_suffix: &'static str,
     shaders: RawList,
     current_shader: usize,
+    /// Index of the variant of the current shader, 0 if it has none.
+    current_variant: usize,
+    /// Indexes of the favourite shaders, sorted.
+    favourites: Vec<usize>,
 }
 
 impl ShaderList {
     pub const fn new(prefix: &'static str, suffix: &'static str, shaders: RawList) -> Self {
-        assert!(shaders.len() > 0);
+        assert!(!shaders.is_empty());
 
         Self {
             prefix,
             suffix,
+            vertex_suffix: "",
             shaders,
             current_shader: 0,
+            current_variant: 0,
+            favourites: Vec::new(),
         }
     }
 
+    pub const fn with_vertex_suffix(mut self, vertex_suffix: &'static str) -> Self {
+        self.vertex_suffix = vertex_suffix;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.shaders.len()
+    }
+
+    /// Select the next shader of the current category, wrapping around.
     pub fn next_shader(&mut self) -> (&'static str, String) {
         self.increment_index();
         self.current_shader()
     }
 
+    /// Select the previous shader of the current category, wrapping around.
     pub fn previous_shader(&mut self) -> (&'static str, String) {
         self.decrement_index();
         self.current_shader()
     }
 
+    /// Select the first shader of the next category, wrapping around.
+    pub fn next_category(&mut self) -> (&'static str, String) {
+        let categories = self.categories();
+        let position = self.category_position(&categories);
+        self.select_category(categories[(position + 1) % categories.len()])
+    }
+
+    /// Select the first shader of the previous category, wrapping around.
+    pub fn previous_category(&mut self) -> (&'static str, String) {
+        let categories = self.categories();
+        let position = self.category_position(&categories);
+        self.select_category(categories[(position + categories.len() - 1) % categories.len()])
+    }
+
+    /// The category of a shader, the directory it is in, empty for the shaders at the root.
+    pub fn category(&self, index: usize) -> &'static str {
+        self.shaders[index]
+            .0
+            .rsplit_once('/')
+            .map_or("", |(category, _)| category)
+    }
+
+    pub fn current_category(&self) -> &'static str {
+        self.category(self.current_shader)
+    }
+
+    /// The categories, in the order of their first shader.
+    pub fn categories(&self) -> Vec<&'static str> {
+        let mut categories = Vec::new();
+        for index in 0..self.shaders.len() {
+            let category = self.category(index);
+            if !categories.contains(&category) {
+                categories.push(category);
+            }
+        }
+        categories
+    }
+
+    /// Iterate over every variant of every shader, starting from the first one,
+    /// the variants are named like `ring.wgsl (rgb)`.
+    pub fn iter(&self) -> impl Iterator<Item = (String, String)> + '_ {
+        (0..self.shaders.len()).flat_map(move |index| {
+            let (name, body) = self.shaders[index];
+            let names = match variants(body).as_slice() {
+                [] => vec![name.to_string()],
+                variants => variants
+                    .iter()
+                    .map(|variant| format!("{name} ({})", variant.name))
+                    .collect(),
+            };
+            names
+                .into_iter()
+                .enumerate()
+                .map(move |(variant, name)| (name, self.shader_variant(index, variant).1))
+        })
+    }
+
+    /// Iterate over the toys without the prefix and the suffix.
+    pub fn iter_toys(&self) -> impl Iterator<Item = (&'static str, &'static str)> + '_ {
+        self.shaders.iter().copied()
+    }
+
+    pub fn prefix(&self) -> &'static str {
+        self.prefix
+    }
+
+    pub fn suffix(&self) -> &'static str {
+        self.suffix
+    }
+
+    pub fn vertex_suffix(&self) -> &'static str {
+        self.vertex_suffix
+    }
+
+    pub fn current_index(&self) -> usize {
+        self.current_shader
+    }
+
+    pub fn current_shader_name(&self) -> &'static str {
+        self.shaders[self.current_shader].0
+    }
+
     pub fn current_shader(&self) -> (&'static str, String) {
-        let (shader_name, shader_body) = self.shaders[self.current_shader];
+        self.shader_variant(self.current_shader, self.current_variant)
+    }
+
+    /// The name of the variant of the current shader, `None` if it has no variants.
+    pub fn current_variant_name(&self) -> Option<&'static str> {
+        self.variant_name(self.current_shader, self.current_variant)
+    }
+
+    /// The name of a variant of the shader at the given index, `None` if it has no variants.
+    pub fn variant_name(&self, index: usize, variant: usize) -> Option<&'static str> {
+        variants(self.shaders.get(index)?.1)
+            .get(variant)
+            .map(|variant| variant.name)
+    }
+
+    /// Select the next variant of the current shader, wrapping around.
+    pub fn next_variant(&mut self) -> (&'static str, String) {
+        let count = variants(self.shaders[self.current_shader].1).len().max(1);
+        self.current_variant = (self.current_variant + 1) % count;
+        self.current_shader()
+    }
+
+    /// Select the previous variant of the current shader, wrapping around.
+    pub fn previous_variant(&mut self) -> (&'static str, String) {
+        let count = variants(self.shaders[self.current_shader].1).len().max(1);
+        self.current_variant = (self.current_variant + count - 1) % count;
+        self.current_shader()
+    }
 
-        let full_shader = [self.prefix, shader_body, self.suffix].join("");
+    /// Return the shader [`ShaderList::next_shader`] would select, without selecting it.
+    pub fn peek_next_shader(&self) -> (&'static str, String) {
+        self.shader_variant(self.neighbour(1), 0)
+    }
+
+    /// Return the shader [`ShaderList::previous_shader`] would select, without selecting it.
+    pub fn peek_previous_shader(&self) -> (&'static str, String) {
+        self.shader_variant(self.neighbour(self.shaders.len() - 1), 0)
+    }
+
+    /// Select the shader at the given index, if it exists.
+    pub fn select(&mut self, index: usize) -> Option<(&'static str, String)> {
+        (index < self.shaders.len()).then(|| {
+            self.set_current(index);
+            self.current_shader()
+        })
+    }
+
+    /// Select a variant of the shader at the given index, if they exist.
+    pub fn select_variant(
+        &mut self,
+        index: usize,
+        variant: usize,
+    ) -> Option<(&'static str, String)> {
+        let count = variants(self.shaders.get(index)?.1).len().max(1);
+        (variant < count).then(|| {
+            self.set_current(index);
+            self.current_variant = variant;
+            self.current_shader()
+        })
+    }
+
+    pub fn shader_name(&self, index: usize) -> Option<&'static str> {
+        self.shaders.get(index).map(|(name, _)| *name)
+    }
+
+    /// The tags listed in the `// tags:` comments of the shader.
+    pub fn tags(&self, index: usize) -> impl Iterator<Item = &'static str> {
+        self.shaders[index]
+            .1
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix(TAGS_PREFIX))
+            .flat_map(|tags| tags.split(','))
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+    }
+
+    /// Return the indexes of the shaders whose name, category, tags or variants
+    /// fuzzy match the query, best match first.
+    pub fn search(&self, query: &str) -> Vec<usize> {
+        let mut matches = (0..self.shaders.len())
+            .filter_map(|index| {
+                self.search_score(index, query)
+                    .map(|(score, _)| (score, index))
+            })
+            .collect::<Vec<_>>();
+
+        matches.sort_by(|(a_score, a_index), (b_score, b_index)| {
+            b_score.cmp(a_score).then(a_index.cmp(b_index))
+        });
+        matches.into_iter().map(|(_, index)| index).collect()
+    }
+
+    /// The variant of the shader matching the query better than its name, category and tags,
+    /// 0 otherwise.
+    pub fn matching_variant(&self, index: usize, query: &str) -> usize {
+        self.search_score(index, query)
+            .map_or(0, |(_, variant)| variant)
+    }
+
+    /// The best score of the shader for the query, with the variant that scored it.
+    fn search_score(&self, index: usize, query: &str) -> Option<(u32, usize)> {
+        let name = self.shaders[index].0.trim_end_matches(".wgsl");
+        let toy = std::iter::once(name)
+            .chain(Some(self.category(index)).filter(|category| !category.is_empty()))
+            .chain(self.tags(index))
+            .filter_map(|candidate| fuzzy_score(query, candidate))
+            .max()
+            .map(|score| (score, 0));
+        let variant = variants(self.shaders[index].1)
+            .iter()
+            .enumerate()
+            .filter_map(|(variant, candidate)| {
+                fuzzy_score(query, candidate.name).map(|score| (score, variant))
+            })
+            // The first variant wins the ties.
+            .min_by_key(|&(score, variant)| (std::cmp::Reverse(score), variant));
+
+        match (toy, variant) {
+            (Some(toy), Some(variant)) if variant.0 > toy.0 => Some(variant),
+            (Some(toy), _) => Some(toy),
+            (None, variant) => variant,
+        }
+    }
+
+    pub fn is_favourite(&self) -> bool {
+        self.favourites.binary_search(&self.current_shader).is_ok()
+    }
+
+    /// Add or remove the current shader from the favourites,
+    /// return `true` if it's now a favourite.
+    pub fn toggle_favourite(&mut self) -> bool {
+        match self.favourites.binary_search(&self.current_shader) {
+            Ok(pos) => {
+                self.favourites.remove(pos);
+                false
+            }
+            Err(pos) => {
+                self.favourites.insert(pos, self.current_shader);
+                true
+            }
+        }
+    }
+
+    /// Select the first favourite after the current shader, wrapping around.
+    pub fn next_favourite(&mut self) -> Option<(&'static str, String)> {
+        let index = self
+            .favourites
+            .iter()
+            .find(|&&index| index > self.current_shader)
+            .or_else(|| self.favourites.first())
+            .copied()?;
+        self.select(index)
+    }
+
+    /// Select the last favourite before the current shader, wrapping around.
+    pub fn previous_favourite(&mut self) -> Option<(&'static str, String)> {
+        let index = self
+            .favourites
+            .iter()
+            .rev()
+            .find(|&&index| index < self.current_shader)
+            .or_else(|| self.favourites.last())
+            .copied()?;
+        self.select(index)
+    }
+
+    /// Index of the shader named exactly `name`, e.g. `fractal/mandelbrot.wgsl`.
+    ///
+    /// The names saved before the toys were sorted in categories, e.g. `mandelbrot.wgsl`,
+    /// match the only shader with this file name.
+    pub fn position(&self, name: &str) -> Option<usize> {
+        if let Some(index) = self.shaders.iter().position(|(shader, _)| *shader == name) {
+            return Some(index);
+        }
+        if name.contains('/') {
+            return None;
+        }
+
+        let mut matches = self
+            .shaders
+            .iter()
+            .enumerate()
+            .filter(|(_, (shader, _))| shader.rsplit('/').next() == Some(name))
+            .map(|(index, _)| index);
+        match (matches.next(), matches.next()) {
+            (Some(index), None) => Some(index),
+            (Some(_), Some(_)) => {
+                log::warn!("The shader `{name}` is ambiguous, give its category");
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Replace the favourites by the shaders named `names`, unknown names are ignored.
+    pub fn set_favourites<'a>(&mut self, names: impl IntoIterator<Item = &'a str>) {
+        let mut favourites = Vec::new();
+        for name in names {
+            match self.position(name) {
+                Some(index) => favourites.push(index),
+                None => log::warn!("Unknown favourite shader `{name}`"),
+            }
+        }
+
+        favourites.sort_unstable();
+        favourites.dedup();
+        self.favourites = favourites;
+    }
+
+    /// Names of the favourite shaders, in the order of the list.
+    pub fn favourite_names(&self) -> Vec<&'static str> {
+        self.favourites
+            .iter()
+            .map(|&index| self.shaders[index].0)
+            .collect()
+    }
+
+    fn shader_variant(&self, index: usize, variant: usize) -> (&'static str, String) {
+        let (shader_name, shader_body) = self.shaders[index];
+
+        let values = variants(shader_body)
+            .get(variant)
+            .map(|variant| variant.values.clone())
+            .unwrap_or_default();
+        let full_shader = assemble(
+            self.prefix,
+            self.suffix,
+            self.vertex_suffix,
+            shader_body,
+            &values,
+        );
         (shader_name, full_shader)
     }
 
+    /// Select a shader with its first variant.
+    fn set_current(&mut self, index: usize) {
+        self.current_shader = index;
+        self.current_variant = 0;
+    }
+
     fn increment_index(&mut self) -> usize {
-        self.current_shader = (self.current_shader + 1) % self.shaders.len();
+        self.set_current(self.neighbour(1));
         self.current_shader
     }
 
     fn decrement_index(&mut self) -> usize {
-        self.current_shader = self.current_shader.wrapping_sub(1) % self.shaders.len();
+        self.set_current(self.neighbour(self.shaders.len() - 1));
         self.current_shader
     }
+
+    /// The first shader of the current category found `step` by `step`, wrapping around.
+    fn neighbour(&self, step: usize) -> usize {
+        let category = self.current_category();
+        let len = self.shaders.len();
+        (1..=len)
+            .map(|count| (self.current_shader + count * step) % len)
+            .find(|&index| self.category(index) == category)
+            .unwrap_or(self.current_shader)
+    }
+
+    fn category_position(&self, categories: &[&str]) -> usize {
+        let current = self.current_category();
+        categories
+            .iter()
+            .position(|&category| category == current)
+            .unwrap_or_default()
+    }
+
+    fn select_category(&mut self, category: &str) -> (&'static str, String) {
+        let index = (0..self.shaders.len())
+            .find(|&index| self.category(index) == category)
+            .unwrap_or(self.current_shader);
+        self.set_current(index);
+        self.current_shader()
+    }
+}
+
+/// The variants declared by the `// variant:` comments of a toy, in order.
+pub fn variants(body: &str) -> Vec<Variant<'_>> {
+    body.lines()
+        .filter_map(|line| line.trim().strip_prefix(VARIANT_PREFIX))
+        .filter_map(|variant| {
+            let mut words = variant.split_whitespace();
+            let name = words.next()?;
+            let values = words
+                .filter_map(|value| {
+                    let value = value.split_once('=');
+                    if value.is_none() {
+                        log::warn!("Ignoring the value of the variant `{name}` without `=`");
+                    }
+                    value
+                })
+                .collect();
+            Some(Variant { name, values })
+        })
+        .collect()
+}
+
+/// The shader of a toy: the prefix, the toy with the values of a variant,
+/// the bindings of its channels and the suffix of its kind.
+pub fn assemble(
+    prefix: &str,
+    suffix: &str,
+    vertex_suffix: &str,
+    body: &str,
+    values: &[(&str, &str)],
+) -> String {
+    let suffix = match vertices_declaration(body) {
+        Some(_) => vertex_suffix,
+        None => suffix,
+    };
+    [
+        prefix,
+        &specialise(body, values),
+        &channel::bindings(body),
+        suffix,
+    ]
+    .join("")
+}
+
+/// The text after `// vertices:`, `None` for the fullscreen toys.
+pub fn vertices_declaration(body: &str) -> Option<&str> {
+    body.lines()
+        .find_map(|line| line.trim().strip_prefix(VERTICES_PREFIX))
+}
+
+/// The names of the `override` constants declared by a toy.
+pub fn override_names(body: &str) -> Vec<&str> {
+    body.lines()
+        .filter_map(override_declaration)
+        .filter_map(|declaration| declaration.split([':', '=', ';', ' ']).next())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// The declaration following `override`, e.g. `RINGS: f32 = 8.;`,
+/// the `@id(n)` attribute is allowed but ignored.
+fn override_declaration(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    let line = match line.strip_prefix("@id(") {
+        Some(attribute) => attribute.split_once(')')?.1.trim_start(),
+        None => line,
+    };
+    line.strip_prefix("override ")
+}
+
+/// Replace the `override` declarations by `const` ones, with the values of the variant or
+/// the default values, as wgpu can't set pipeline-overridable constants yet.
+/// The ids of `@id(n) override` are dropped, no pipeline sets the constants by id.
+pub fn specialise(body: &str, values: &[(&str, &str)]) -> String {
+    let mut specialised = String::with_capacity(body.len());
+    for line in body.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        let Some(declaration) = override_declaration(trimmed) else {
+            specialised.push_str(line);
+            continue;
+        };
+
+        let (lhs, default) = match declaration.split_once('=') {
+            Some((lhs, default)) => (lhs, Some(default.trim().trim_end_matches(';').trim())),
+            None => (declaration.trim().trim_end_matches(';'), None),
+        };
+        let name = lhs.split([':', ' ']).next().unwrap_or_default();
+        let value = values
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| *value)
+            .or(default);
+
+        match value {
+            Some(value) => {
+                let indent = &line[..line.len() - trimmed.len()];
+                specialised.push_str(&format!("{indent}const {} = {value};", lhs.trim()));
+                if line.ends_with('\n') {
+                    specialised.push('\n');
+                }
+            }
+            // Without a value naga reports the declaration, the linter explains it.
+            None => specialised.push_str(line),
+        }
+    }
+    specialised
+}
+
+/// Score how well the query match the candidate, the higher the better.
+///
+/// Every character of the query must appear in order in the candidate (ignoring the case),
+/// consecutive characters and characters at the start of a word score higher.
+fn fuzzy_score(query: &str, candidate: &str) -> Option<u32> {
+    let query = query.trim().to_lowercase();
+    let candidate = candidate.to_lowercase();
+    let mut candidate_chars = candidate.char_indices().peekable();
+    let mut score = 0;
+    let mut previous_match = None;
+
+    for query_char in query.chars() {
+        let (pos, _) = candidate_chars.find(|(_, c)| *c == query_char)?;
+
+        score += 1;
+        if previous_match.map_or(pos == 0, |previous| previous + 1 == pos) {
+            score += 4;
+        }
+        if pos == 0 || candidate[..pos].ends_with(['-', '_', ' ', '/']) {
+            score += 2;
+        }
+        previous_match = Some(pos);
+    }
+
+    if candidate == query {
+        score += 8;
+    }
+    Some(score)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::ShaderList;
+    use super::{fuzzy_score, override_names, specialise, variants, ShaderList, Variant};
 
     const SHADER: ShaderList =
         ShaderList::new("p", "s", &[("1", "a"), ("2", "b"), ("3", "c"), ("4", "d")]);
@@ -95,4 +613,225 @@ mod tests {
         compare_shader!(list.previous_shader(), "1", "pas");
         compare_shader!(list.previous_shader(), "4", "pds");
     }
+
+    #[test]
+    fn select() {
+        let mut list = SHADER;
+
+        assert_eq!(list.select(2), Some(("3", "pcs".to_string())));
+        assert_eq!(list.select(4), None);
+        assert_eq!(list.current_shader_name(), "3");
+    }
+
+    #[test]
+    fn tags() {
+        let list = ShaderList::new(
+            "",
+            "",
+            &[
+                ("1", "// tags: fractal, color\nfn a() {}"),
+                ("2", "fn b() {}"),
+            ],
+        );
+
+        assert_eq!(list.tags(0).collect::<Vec<_>>(), ["fractal", "color"]);
+        assert_eq!(list.tags(1).count(), 0);
+    }
+
+    #[test]
+    fn fuzzy() {
+        assert_eq!(fuzzy_score("", "ring"), Some(0));
+        assert_eq!(fuzzy_score("rgn", "ring"), None);
+        assert!(fuzzy_score("ring", "ring") > fuzzy_score("ring", "multi-ring"));
+        assert!(fuzzy_score("mr", "multi-ring") > fuzzy_score("mr", "mandelbrot"));
+        assert!(fuzzy_score("NEON", "rgb-neon").is_some());
+    }
+
+    #[test]
+    fn search() {
+        let list = ShaderList::new(
+            "",
+            "",
+            &[
+                ("mandelbrot.wgsl", "// tags: fractal"),
+                ("ring.wgsl", ""),
+                ("multi-ring.wgsl", ""),
+                ("frac-rgb.wgsl", "// tags: fractal, color"),
+            ],
+        );
+
+        assert_eq!(list.search("ring"), [1, 2]);
+        assert_eq!(list.search("fractal"), [0, 3]);
+        assert_eq!(list.search("wgsl"), [] as [usize; 0]);
+    }
+
+    #[test]
+    fn categories() {
+        let mut list = ShaderList::new(
+            "",
+            "",
+            &[
+                ("default.wgsl", ""),
+                ("fractal/mandelbrot.wgsl", ""),
+                ("fractal/frac-rgb.wgsl", ""),
+                ("ring/ring.wgsl", ""),
+                ("zoom.wgsl", ""),
+            ],
+        );
+
+        assert_eq!(list.categories(), ["", "fractal", "ring"]);
+        assert_eq!(list.category(2), "fractal");
+
+        assert_eq!(list.next_shader().0, "zoom.wgsl");
+        assert_eq!(list.next_shader().0, "default.wgsl");
+        assert_eq!(list.next_category().0, "fractal/mandelbrot.wgsl");
+        assert_eq!(list.peek_previous_shader().0, "fractal/frac-rgb.wgsl");
+        assert_eq!(list.next_shader().0, "fractal/frac-rgb.wgsl");
+        assert_eq!(list.next_shader().0, "fractal/mandelbrot.wgsl");
+        assert_eq!(list.next_category().0, "ring/ring.wgsl");
+        assert_eq!(list.next_shader().0, "ring/ring.wgsl");
+        assert_eq!(list.next_category().0, "default.wgsl");
+        assert_eq!(list.previous_category().0, "ring/ring.wgsl");
+
+        assert_eq!(list.search("fractal"), [1, 2]);
+    }
+
+    #[test]
+    fn shader_variants() {
+        const TOY: &str = "// variant: blue\n// variant: rgb PALETTE=true RINGS=4.\n\
+            override PALETTE: bool = false;\n    override RINGS: f32 = 8.;\nfn main() {}";
+        let mut list = ShaderList::new("", "", &[("neon.wgsl", TOY), ("ring.wgsl", "")]);
+
+        assert_eq!(
+            variants(TOY),
+            [
+                Variant {
+                    name: "blue",
+                    values: vec![],
+                },
+                Variant {
+                    name: "rgb",
+                    values: vec![("PALETTE", "true"), ("RINGS", "4.")],
+                },
+            ]
+        );
+        assert_eq!(override_names(TOY), ["PALETTE", "RINGS"]);
+
+        assert_eq!(list.current_variant_name(), Some("blue"));
+        assert!(list
+            .current_shader()
+            .1
+            .ends_with("const PALETTE: bool = false;\n    const RINGS: f32 = 8.;\nfn main() {}"));
+        let (_, rgb) = list.next_variant();
+        assert_eq!(list.current_variant_name(), Some("rgb"));
+        assert!(rgb.contains("const PALETTE: bool = true;\n    const RINGS: f32 = 4.;\n"));
+        assert_eq!(list.next_variant().1, list.iter().next().unwrap().1);
+        assert_eq!(
+            list.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+            ["neon.wgsl (blue)", "neon.wgsl (rgb)", "ring.wgsl"]
+        );
+        assert_eq!(list.iter().nth(1).unwrap().1, rgb);
+
+        list.previous_variant();
+        list.next_shader();
+        assert_eq!(list.current_variant_name(), None);
+        assert_eq!(list.next_variant().0, "ring.wgsl");
+        assert_eq!(list.previous_shader().0, "neon.wgsl");
+        assert_eq!(list.current_variant_name(), Some("blue"));
+
+        assert_eq!(
+            specialise("override SIZE: f32;\n", &[]),
+            "override SIZE: f32;\n",
+            "no value to specialise with"
+        );
+        assert_eq!(
+            specialise("@id(0) override SIZE: f32 = 1.;\n", &[("SIZE", "2.")]),
+            "const SIZE: f32 = 2.;\n"
+        );
+        assert_eq!(override_names("  @id(3) override SIZE = 1.;"), ["SIZE"]);
+    }
+
+    #[test]
+    fn search_variants() {
+        let mut list = ShaderList::new(
+            "",
+            "",
+            &[
+                ("ring.wgsl", "// variant: blue\n// variant: crimson"),
+                ("neon.wgsl", ""),
+            ],
+        );
+
+        assert_eq!(list.search("crimson"), [0]);
+        assert_eq!(list.matching_variant(0, "crimson"), 1);
+        assert_eq!(list.matching_variant(0, "ring"), 0);
+        list.select_variant(0, 1);
+        assert_eq!(list.current_variant_name(), Some("crimson"));
+        assert_eq!(list.select_variant(1, 1), None);
+    }
+
+    #[test]
+    fn favourites() {
+        let mut list = SHADER;
+
+        assert_eq!(list.next_favourite(), None);
+
+        list.select(3);
+        assert!(list.toggle_favourite());
+        list.select(1);
+        assert!(list.toggle_favourite());
+        assert!(list.is_favourite());
+
+        assert_eq!(list.next_favourite().unwrap().0, "4");
+        assert_eq!(list.next_favourite().unwrap().0, "2");
+        assert_eq!(list.previous_favourite().unwrap().0, "4");
+
+        assert!(!list.toggle_favourite());
+        assert_eq!(list.next_favourite().unwrap().0, "2");
+    }
+
+    #[test]
+    fn persist_favourites() {
+        let mut list = SHADER;
+        list.set_favourites(["3", "unknown", "1", "3"]);
+
+        assert_eq!(list.favourite_names(), ["1", "3"]);
+    }
+
+    #[test]
+    fn position_of_old_names() {
+        let list = ShaderList::new(
+            "p",
+            "s",
+            &[
+                ("fractal/mandelbrot.wgsl", "a"),
+                ("fractal/ring.wgsl", "b"),
+                ("neon/ring.wgsl", "c"),
+            ],
+        );
+
+        assert_eq!(list.position("fractal/mandelbrot.wgsl"), Some(0));
+        assert_eq!(list.position("mandelbrot.wgsl"), Some(0));
+        // Two categories have a `ring.wgsl`.
+        assert_eq!(list.position("ring.wgsl"), None);
+        assert_eq!(list.position("neon/mandelbrot.wgsl"), None);
+    }
+
+    #[test]
+    fn peek_shader() {
+        let list = SHADER;
+
+        assert_eq!(list.peek_next_shader(), ("2", "pbs".to_string()));
+        assert_eq!(list.peek_previous_shader(), ("4", "pds".to_string()));
+        assert_eq!(list.current_shader_name(), "1");
+    }
+
+    #[test]
+    fn vertex_suffix() {
+        let mut list = ShaderList::new("p", "s", &[("1", "a"), ("2", "// vertices: 30 lines\n")])
+            .with_vertex_suffix("v");
+
+        assert_eq!(list.current_shader().1, "pas");
+        assert_eq!(list.next_shader().1, "p// vertices: 30 lines\nv");
+    }
 }
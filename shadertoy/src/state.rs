@@ -1,69 +1,192 @@
-use std::{borrow::Cow, time::Instant};
+use std::sync::Arc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::{fs::File, io::BufWriter, path::PathBuf};
 
 use anyhow::Context;
+use instant::{Instant, SystemTime};
+use serde_json::json;
 use wgpu::SurfaceError;
 use winit::{
     dpi::PhysicalSize,
-    event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event::{ElementState, ModifiersState, MouseButton, MouseScrollDelta, VirtualKeyCode},
     window::Window,
 };
 
-use crate::{shader_list::ShaderList, uniform::Uniform};
+use crate::{
+    cli::Options,
+    config::{Config, WindowSize},
+    gpu,
+    hdr::{Tonemap, TonemapPass, HDR_FORMAT},
+    input::InputEvent,
+    keys::Action,
+    layer::LayerStack,
+    output::OutputWindow,
+    pipeline::{self, PipelineCache},
+    post::{Effect, PostStack, PostTargets},
+    present::PresentMode,
+    remote::{Request, Response},
+    screenshot::Screenshot,
+    shader_list::ShaderList,
+    timing::{Clock, FrameStats, GpuTimer},
+    uniform::Uniform,
+    view::View,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    osc::{self, Osc, ToyAction},
+    record::{Entry, OscFrame, Recorder, Replay},
+};
+
+/// Zoom factor applied by one step of the mouse wheel or one press of `+` and `-`.
+const ZOOM_STEP: f64 = 1.25;
+/// Part of the window height moved by one press of `W`, `A`, `S` or `D`.
+const PAN_STEP: f64 = 0.1;
+/// Rotation applied by one press of `Q` or `E`.
+const ROTATE_STEP: f64 = std::f64::consts::PI / 12.;
+/// Opacity of the top layer changed by one press of `[` or `]`.
+const OPACITY_STEP: f32 = 0.1;
 
 pub struct State {
     window: Window,
     pub size: PhysicalSize<u32>,
 
     shader_list: ShaderList,
+    /// Saved on exit with the current toy, window size and favourites.
+    user_config: Config,
 
     surface: wgpu::Surface,
-    device: wgpu::Device,
-    queue: wgpu::Queue,
+    device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
 
     config: wgpu::SurfaceConfiguration,
 
-    time: Instant,
+    clock: Clock,
 
     _sampler: wgpu::Sampler,
-    pipeline: wgpu::RenderPipeline,
+    pipeline_cache: PipelineCache,
+    pipeline: Arc<pipeline::ToyPipeline>,
+    /// The toy waiting for its pipeline to be compiled, the previous one is shown meanwhile.
+    pending_shader: Option<String>,
+    /// The text typed to search a toy by name or tag, or to jump to a toy by its number.
+    prompt: Option<String>,
+
+    targets: PostTargets,
+    /// The toys stacked over the current one.
+    layers: LayerStack,
+    post: PostStack,
+    tonemap_pass: TonemapPass,
+    /// The optional second window, showing the toy without the title bar of the main window.
+    output: Option<OutputWindow>,
+
+    frame_stats: FrameStats,
+    gpu_timer: GpuTimer,
+    show_stats: bool,
+
+    modifiers: ModifiersState,
+    cursor: [f64; 2],
+    dragging: bool,
+    view: View,
+    screenshot_scale: u32,
+    #[cfg(not(target_arch = "wasm32"))]
+    osc: Option<Osc>,
+    #[cfg(not(target_arch = "wasm32"))]
+    recorder: Option<Recorder<BufWriter<File>>>,
+    /// While a recording is replayed the inputs of the user are ignored.
+    #[cfg(not(target_arch = "wasm32"))]
+    replay: Option<Replay>,
+    /// The directory the replayed frames are saved to.
+    #[cfg(not(target_arch = "wasm32"))]
+    replay_frames: Option<PathBuf>,
+    /// The state left by a replay isn't the user's, so the config isn't saved on exit.
+    #[cfg(not(target_arch = "wasm32"))]
+    replayed: bool,
 
     uniform: Uniform,
     uniform_buffer: wgpu::Buffer,
-    uniform_bind_group_layout: wgpu::BindGroupLayout,
     uniform_bind_group: wgpu::BindGroup,
 }
 
 impl State {
-    pub async fn new(window: Window, shader_list: ShaderList) -> anyhow::Result<Self> {
+    pub async fn new(
+        window: Window,
+        output_window: Option<Window>,
+        mut shader_list: ShaderList,
+        options: &Options,
+        user_config: Config,
+    ) -> anyhow::Result<Self> {
         let size = window.inner_size();
+        let present_mode = options.present_mode.unwrap_or(user_config.present_mode);
+        let render_scale = options.render_scale.unwrap_or(user_config.render_scale);
 
-        let (adapter, surface, device, queue) = get_surface_device_queue(&window).await?;
+        shader_list.set_favourites(user_config.favourites.iter().map(String::as_str));
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let replay = options
+            .replay
+            .as_deref()
+            .map(|path| start_replay(path, &window, &mut shader_list))
+            .transpose()?;
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(dir) = &options.replay_frames {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Can't create {}", dir.display()))?;
+        }
 
-        let config = gen_config(adapter, &surface, &size)?;
+        let (adapter, surface, output_surface, device, queue) =
+            get_surface_device_queue(&window, output_window.as_ref()).await?;
+        let device = Arc::new(device);
+        let queue = Arc::new(queue);
+
+        let config = gen_config(&adapter, &surface, &size, present_mode)?;
 
         surface.configure(&device, &config);
 
-        let (uniform, uniform_buffer, uniform_bind_group_layout, uniform_bind_group) =
+        let (mut uniform, uniform_buffer, uniform_bind_group_layout, uniform_bind_group) =
             crate::uniform::setup_uniform(&device);
 
+        let post = PostStack::new(&device, &uniform_bind_group_layout, &options.effects);
+        let layers = LayerStack::new(&device, queue.clone(), post.bind_group_layout());
+
+        let output = match (output_window, output_surface) {
+            (Some(window), Some(surface)) => {
+                let config = gen_config(&adapter, &surface, &window.inner_size(), present_mode)?;
+                let tonemap_pass =
+                    TonemapPass::new(&device, config.format, Tonemap::default(), render_scale);
+                Some(OutputWindow::new(
+                    device.clone(),
+                    queue.clone(),
+                    window,
+                    surface,
+                    config,
+                    tonemap_pass,
+                    &post,
+                ))
+            }
+            _ => None,
+        };
+
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
 
-        let (shader_name, fragment_shader) = shader_list.current_shader();
+        let (_, fragment_shader) = shader_list.current_shader();
 
-        window.set_title(shader_name);
+        let mut tonemap_pass =
+            TonemapPass::new(&device, config.format, Tonemap::default(), render_scale);
+        let [width, height] = tonemap_pass.resize(&queue, config.width, config.height);
+        uniform.scale = 1. / tonemap_pass.render_scale();
+        let targets = post.create_targets(&device, &tonemap_pass, width, height);
 
-        let pipeline = build_pipeline(
-            &device,
-            &[&uniform_bind_group_layout],
-            config.format,
-            fragment_shader,
-        );
+        let gpu_timer = GpuTimer::new(&device, &queue);
+
+        let mut pipeline_cache =
+            PipelineCache::new(&device, queue.clone(), uniform_bind_group_layout);
+        let pipeline = pipeline_cache.get_or_build(&device, &fragment_shader, HDR_FORMAT);
 
-        Ok(Self {
+        let mut state = Self {
             window,
             size,
 
             shader_list,
+            user_config,
 
             surface,
             device,
@@ -71,64 +194,871 @@ impl State {
 
             config,
 
-            time: Instant::now(),
+            clock: Clock::new(Instant::now()),
 
             _sampler: sampler,
+            pipeline_cache,
             pipeline,
+            pending_shader: None,
+            prompt: None,
+
+            targets,
+            layers,
+            post,
+            tonemap_pass,
+            output,
+
+            frame_stats: FrameStats::new(Instant::now()),
+            gpu_timer,
+            show_stats: false,
+
+            modifiers: ModifiersState::empty(),
+            cursor: [0.; 2],
+            dragging: false,
+            view: View::default(),
+            screenshot_scale: options.screenshot_scale,
+            #[cfg(not(target_arch = "wasm32"))]
+            osc: options.osc_port.and_then(|port| {
+                listen_osc(port, options.osc_mapping.as_deref())
+                    .map_err(|e| log::error!("Failed to start the OSC input: {e:#}"))
+                    .ok()
+            }),
+            #[cfg(not(target_arch = "wasm32"))]
+            recorder: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            replayed: replay.is_some(),
+            #[cfg(not(target_arch = "wasm32"))]
+            replay,
+            #[cfg(not(target_arch = "wasm32"))]
+            replay_frames: options.replay_frames.clone(),
 
             uniform,
             uniform_bind_group,
-            uniform_bind_group_layout,
             uniform_buffer,
-        })
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = &options.record {
+            let mut recorder = Recorder::create(path)?;
+            recorder
+                .start(
+                    state.shader_list.current_shader_name(),
+                    [size.width, size.height],
+                )
+                .with_context(|| format!("Can't write to {}", path.display()))?;
+            log::info!("Recording the inputs to {}", path.display());
+            state.recorder = Some(recorder);
+        }
+        state.update_title();
+        state.prefetch_neighbours();
+
+        Ok(state)
     }
 
     pub fn window(&self) -> &Window {
         &self.window
     }
 
-    pub fn input(&mut self, event: &WindowEvent) -> bool {
+    pub fn output_window(&self) -> Option<&Window> {
+        self.output.as_ref().map(OutputWindow::window)
+    }
+
+    pub fn resize_output(&mut self, new_size: PhysicalSize<u32>) {
+        if let Some(output) = &mut self.output {
+            output.resize(&self.post, new_size);
+        }
+    }
+
+    pub fn close_output(&mut self) {
+        self.output = None;
+    }
+
+    /// Handle an event of the user, return `true` if it is consumed.
+    ///
+    /// The events are recorded, or ignored while a recording is replayed.
+    pub fn input(&mut self, event: InputEvent) -> bool {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            if self.replay.is_some() {
+                return false;
+            }
+            self.record(|recorder| recorder.input(event));
+        }
+
+        self.handle_input(event)
+    }
+
+    /// Handle a request of the remote control.
+    ///
+    /// The requests are recorded, or refused while a recording is replayed.
+    pub fn remote(&mut self, request: &Request) -> Response {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            if self.replay.is_some() {
+                return Response::Error("A recording is being replayed".into());
+            }
+            self.record(|recorder| recorder.remote(request));
+        }
+
+        self.handle_remote(request)
+    }
+
+    fn handle_input(&mut self, event: InputEvent) -> bool {
+        // Track the modifiers even while the prompt captures the keyboard,
+        // so they aren't stale once it's closed.
+        if let InputEvent::Modifiers { modifiers } = event {
+            self.modifiers = modifiers;
+            return false;
+        }
+        if self.prompt.is_some() {
+            return self.prompt_input(event);
+        }
+
         match event {
-            WindowEvent::KeyboardInput {
-                input:
-                    KeyboardInput {
-                        state: ElementState::Pressed,
-                        virtual_keycode: Some(VirtualKeyCode::Left),
-                        ..
-                    },
-                ..
+            // `/` open the search prompt, a digit start typing the number of a toy.
+            InputEvent::Character { character }
+                if character == '/' || character.is_ascii_digit() =>
+            {
+                self.prompt = Some(character.to_string().replace('/', ""));
+                self.update_title();
+                true
+            }
+            InputEvent::Key {
+                key,
+                state: ElementState::Pressed,
+            } => match self.user_config.keys.action(key) {
+                Some(action) => {
+                    self.run_action(action);
+                    true
+                }
+                None => false,
+            },
+            InputEvent::CursorMoved { position: [x, y] } => {
+                if self.dragging {
+                    let delta = [x - self.cursor[0], y - self.cursor[1]];
+                    let resolution = self.uniform.resolution;
+                    self.view_mut().pan(delta, resolution);
+                }
+                self.cursor = [x, y];
+                self.dragging
+            }
+            InputEvent::MouseButton {
+                button: MouseButton::Left,
+                state,
             } => {
+                self.dragging = state == ElementState::Pressed;
+                true
+            }
+            InputEvent::MouseWheel { delta } => {
+                let steps = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => f64::from(y),
+                    MouseScrollDelta::PixelDelta(position) => position.y / 50.,
+                };
+                let (cursor, resolution) = (self.cursor, self.uniform.resolution);
+                self.view_mut()
+                    .zoom_at(ZOOM_STEP.powf(steps), cursor, resolution);
+                self.update_title();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Do what the key pressed is bound to.
+    fn run_action(&mut self, action: Action) {
+        let resolution = self.uniform.resolution;
+        let [width, height] = resolution.map(f64::from);
+        let pan = height * PAN_STEP;
+        let center = [width / 2., height / 2.];
+
+        match action {
+            Action::PreviousToy => {
                 let shader = self.shader_list.previous_shader();
                 self.update_shader(shader);
-                true
             }
-            WindowEvent::KeyboardInput {
-                input:
-                    KeyboardInput {
-                        state: ElementState::Pressed,
-                        virtual_keycode: Some(VirtualKeyCode::Right),
-                        ..
-                    },
-                ..
-            } => {
+            Action::NextToy => {
                 let shader = self.shader_list.next_shader();
                 self.update_shader(shader);
-                true
             }
-            _ => false,
+            Action::PreviousCategory => {
+                let shader = self.shader_list.previous_category();
+                self.update_shader(shader);
+            }
+            Action::NextCategory => {
+                let shader = self.shader_list.next_category();
+                self.update_shader(shader);
+            }
+            Action::Tonemap => self.set_tonemap(self.tonemap_pass.tonemap().next()),
+            Action::ToggleEffect(effect) => self.post.toggle(effect),
+            Action::Layer => {
+                if self.modifiers.shift() {
+                    match self.layers.remove() {
+                        Some(layer) => self.close_hidden_streams(layer.pipeline()),
+                        None => log::info!(
+                            "No layer{}",
+                            self.user_config
+                                .keys
+                                .hint(Action::Layer, "to stack the current toy")
+                        ),
+                    }
+                } else if let Err(e) = self.push_layer() {
+                    log::error!("Can't add a layer: {e:#}");
+                }
+            }
+            Action::SelectLayer => self.layers.select_next(self.modifiers.shift()),
+            Action::Blend => self.layers.cycle_blend(),
+            Action::DecreaseOpacity => self.layers.add_opacity(-OPACITY_STEP),
+            Action::IncreaseOpacity => self.layers.add_opacity(OPACITY_STEP),
+            Action::Stats => self.show_stats = !self.show_stats,
+            Action::ExportStats => {
+                if let Err(e) = self.export_stats() {
+                    log::error!("Failed to export the frame statistics: {e:#}");
+                }
+            }
+            Action::Screenshot => {
+                let factor = if self.modifiers.shift() {
+                    self.screenshot_scale
+                } else {
+                    1
+                };
+                if let Err(e) = self.save_screenshot(factor) {
+                    log::error!("Failed to save the screenshot: {e:#}");
+                }
+            }
+            Action::Favourite => {
+                self.shader_list.toggle_favourite();
+                if let Err(e) = self.save_config() {
+                    log::error!("Failed to save the favourites: {e:#}");
+                }
+            }
+            Action::NextFavourite => {
+                let shader = if self.modifiers.shift() {
+                    self.shader_list.previous_favourite()
+                } else {
+                    self.shader_list.next_favourite()
+                };
+                match shader {
+                    Some(shader) => self.update_shader(shader),
+                    None => log::info!(
+                        "No favourite toy{}",
+                        self.user_config
+                            .keys
+                            .hint(Action::Favourite, "to add the current one")
+                    ),
+                }
+            }
+            Action::NextVariant => {
+                let shader = if self.modifiers.shift() {
+                    self.shader_list.previous_variant()
+                } else {
+                    self.shader_list.next_variant()
+                };
+                // The variants of a toy share the view.
+                self.set_shader(shader);
+            }
+            Action::Pause => self.set_paused(!self.is_paused()),
+            Action::PanUp => self.view_mut().pan([0., pan], resolution),
+            Action::PanLeft => self.view_mut().pan([pan, 0.], resolution),
+            Action::PanDown => self.view_mut().pan([0., -pan], resolution),
+            Action::PanRight => self.view_mut().pan([-pan, 0.], resolution),
+            Action::RotateLeft => self.view_mut().rotate(-ROTATE_STEP),
+            Action::RotateRight => self.view_mut().rotate(ROTATE_STEP),
+            Action::ZoomIn => self.view_mut().zoom_at(ZOOM_STEP, center, resolution),
+            Action::ZoomOut => self.view_mut().zoom_at(1. / ZOOM_STEP, center, resolution),
+            Action::ResetView => *self.view_mut() = View::default(),
         }
+
+        self.update_title();
     }
 
-    fn update_shader(&mut self, shader: (&str, String)) {
-        let (title, content) = shader;
+    /// Handle the events while the prompt is open, every keyboard event is captured.
+    fn prompt_input(&mut self, event: InputEvent) -> bool {
+        let Some(prompt) = &mut self.prompt else {
+            return false;
+        };
+
+        match event {
+            InputEvent::Character { character } if !character.is_control() => {
+                prompt.push(character)
+            }
+            InputEvent::Key {
+                key,
+                state: ElementState::Pressed,
+            } => match key {
+                VirtualKeyCode::Back => {
+                    prompt.pop();
+                }
+                VirtualKeyCode::Escape => self.prompt = None,
+                VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => {
+                    let target = self.prompt_target();
+                    self.prompt = None;
+                    match target.and_then(|(index, variant)| {
+                        self.shader_list.select_variant(index, variant)
+                    }) {
+                        Some(shader) => self.update_shader(shader),
+                        None => log::info!("No toy match the prompt"),
+                    }
+                }
+                _ => {}
+            },
+            InputEvent::Character { .. } | InputEvent::Key { .. } => {}
+            _ => return false,
+        }
+
+        self.update_title();
+        true
+    }
+
+    /// The toy and its variant selected by the prompt.
+    fn prompt_target(&self) -> Option<(usize, usize)> {
+        find_toy(&self.shader_list, self.prompt.as_deref()?)
+    }
+
+    fn handle_remote(&mut self, request: &Request) -> Response {
+        let response = match request {
+            Request::ListToys => Ok(self.toys()),
+            Request::Status => Ok(self.status()),
+            Request::SelectToy { toy } => find_toy(&self.shader_list, toy)
+                .and_then(|(index, variant)| self.shader_list.select_variant(index, variant))
+                .map(|shader| {
+                    self.update_shader(shader);
+                    self.status()
+                })
+                .with_context(|| format!("No toy match `{toy}`")),
+            Request::SelectLayer { layer } => self
+                .layers
+                .select(layer.checked_sub(1))
+                .map(|()| self.status()),
+            Request::SetParameters {
+                tonemap,
+                effects,
+                speed,
+                zoom,
+                center,
+                angle,
+                blend,
+                opacity,
+            } => request
+                .validate()
+                .and_then(|()| {
+                    anyhow::ensure!(
+                        (blend.is_none() && opacity.is_none()) || self.layers.selected().is_some(),
+                        "Select a layer to change its blend mode or its opacity"
+                    );
+                    Ok(())
+                })
+                .map(|()| {
+                    if let Some(tonemap) = tonemap {
+                        self.set_tonemap(*tonemap);
+                    }
+                    if let Some(effects) = effects {
+                        self.post.set_effects(effects);
+                    }
+                    if let Some(speed) = speed {
+                        self.set_speed(*speed);
+                    }
+                    if let Some(layer) = self.layers.selected_mut() {
+                        if let Some(blend) = blend {
+                            layer.set_blend(*blend);
+                        }
+                        if let Some(opacity) = opacity {
+                            layer.set_opacity(*opacity);
+                        }
+                    }
+                    let view = self.view_mut();
+                    view.zoom = zoom.unwrap_or(view.zoom);
+                    view.center = center.unwrap_or(view.center);
+                    view.angle = angle.unwrap_or(view.angle);
+                    self.status()
+                }),
+            Request::Pause | Request::Resume => {
+                self.set_paused(*request == Request::Pause);
+                Ok(self.status())
+            }
+            Request::Screenshot { scale } => {
+                let factor = scale.unwrap_or(1);
+                if (1..=16).contains(&factor) {
+                    self.save_screenshot(factor)
+                        .map(|path| json!({ "path": path }))
+                } else {
+                    Err(anyhow::anyhow!("The scale must be between 1 and 16"))
+                }
+            }
+        };
+
+        self.update_title();
+        response.into()
+    }
+
+    fn toys(&self) -> serde_json::Value {
+        (0..self.shader_list.len())
+            .map(|index| {
+                json!({
+                    "number": index + 1,
+                    "name": self.shader_list.shader_name(index),
+                    "category": self.shader_list.category(index),
+                    "tags": self.shader_list.tags(index).collect::<Vec<_>>(),
+                })
+            })
+            .collect()
+    }
 
-        self.window.set_title(title);
-        self.pipeline = build_pipeline(
+    fn status(&self) -> serde_json::Value {
+        let layers = self
+            .layers
+            .layers()
+            .iter()
+            .map(|layer| {
+                json!({
+                    "toy": layer.name(),
+                    "blend": layer.blend().to_string(),
+                    "opacity": layer.opacity(),
+                    "time": layer.time(),
+                    "paused": layer.is_paused(),
+                    "speed": layer.speed(),
+                    "view": {
+                        "zoom": layer.view().zoom,
+                        "center": layer.view().center,
+                        "angle": layer.view().angle,
+                    },
+                })
+            })
+            .collect::<Vec<_>>();
+        json!({
+            "toy": {
+                "number": self.shader_list.current_index() + 1,
+                "name": self.shader_list.current_shader_name(),
+                "category": self.shader_list.current_category(),
+                "variant": self.shader_list.current_variant_name(),
+                "loading": self.pending_shader.is_some(),
+            },
+            "time": self.clock.elapsed(Instant::now()).as_secs_f64(),
+            "paused": self.clock.is_paused(),
+            "speed": self.clock.speed(),
+            "tonemap": self.tonemap_pass.tonemap().to_string(),
+            "effects": self.post.effects().iter().map(Effect::to_string).collect::<Vec<_>>(),
+            "layers": layers,
+            "selected_layer": self.layers.selected().map_or(0, |index| index + 1),
+            "view": {
+                "zoom": self.view.zoom,
+                "center": self.view.center,
+                "angle": self.view.angle,
+            },
+        })
+    }
+
+    /// Apply the OSC messages received since the previous frame.
+    ///
+    /// The values are recorded, the messages are ignored while a recording is replayed.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn update_osc(&mut self) {
+        let Some(osc) = &mut self.osc else {
+            return;
+        };
+        if self.replay.is_some() {
+            return;
+        }
+
+        let frame = OscFrame {
+            actions: osc.poll(Instant::now()),
+            params: osc.params(),
+            speed: osc.speed(),
+        };
+        self.record(|recorder| recorder.osc(&frame));
+        self.apply_osc(frame);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn apply_osc(&mut self, frame: OscFrame) {
+        match self.layers.selected_mut() {
+            Some(layer) => layer.set_params(frame.params),
+            None => self.uniform.params = frame.params,
+        }
+        if let Some(speed) = frame.speed {
+            self.set_speed(f64::from(speed));
+        }
+
+        for action in frame.actions {
+            let shader = match &action {
+                ToyAction::Next => Some(self.shader_list.next_shader()),
+                ToyAction::Previous => Some(self.shader_list.previous_shader()),
+                ToyAction::Select(query) => find_toy(&self.shader_list, query)
+                    .and_then(|(index, variant)| self.shader_list.select_variant(index, variant)),
+            };
+            match shader {
+                Some(shader) => self.update_shader(shader),
+                None => log::info!("No toy match the OSC message {action:?}"),
+            }
+        }
+    }
+
+    /// Save the config with the current toy, window size and favourites.
+    /// Nothing is saved once a recording was replayed.
+    pub fn save_config(&mut self) -> anyhow::Result<()> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.replayed {
+            log::info!("A recording was replayed, the config isn't saved");
+            return Ok(());
+        }
+
+        self.user_config.toy = Some(self.shader_list.current_shader_name().to_string());
+        self.user_config.window = Some(WindowSize {
+            width: self.size.width,
+            height: self.size.height,
+        });
+        self.user_config.favourites = self
+            .shader_list
+            .favourite_names()
+            .into_iter()
+            .map(String::from)
+            .collect();
+        self.user_config.save_user()
+    }
+
+    /// Stack the current toy over the others, with the current view.
+    fn push_layer(&mut self) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.pending_shader.is_none(),
+            "The toy is still being compiled"
+        );
+        self.layers.push(
             &self.device,
-            &[&self.uniform_bind_group_layout],
-            self.config.format,
-            content,
+            self.pipeline_cache.uniform_layout(),
+            self.shader_list.current_shader_name(),
+            self.pipeline.clone(),
+            self.uniform,
+            self.view,
+        )
+    }
+
+    /// Apply the tonemap to both windows.
+    fn set_tonemap(&mut self, tonemap: Tonemap) {
+        self.tonemap_pass.set_tonemap(&self.queue, tonemap);
+        if let Some(output) = &mut self.output {
+            output.set_tonemap(tonemap);
+        }
+    }
+
+    /// The time of the next frame, read from the recording being replayed or from the clock.
+    fn frame_time(&mut self) -> f32 {
+        #[cfg(not(target_arch = "wasm32"))]
+        match self.replay.as_mut().map(Replay::next_frame) {
+            Some(Some((entries, time))) => {
+                for entry in entries {
+                    match entry {
+                        Entry::Input(event) => {
+                            self.handle_input(event);
+                        }
+                        Entry::Remote(request) => {
+                            self.handle_remote(&request);
+                        }
+                        Entry::Osc(frame) => self.apply_osc(frame),
+                        Entry::Start { .. } | Entry::Frame { .. } => {}
+                    }
+                }
+                return time;
+            }
+            Some(None) => {
+                log::info!("End of the replay");
+                self.replay = None;
+                self.update_title();
+            }
+            None => {}
+        }
+
+        self.clock.elapsed(Instant::now()).as_secs_f32()
+    }
+
+    /// Stop recording on the first error, the recording is useless once an entry is missing.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn record(
+        &mut self,
+        write: impl FnOnce(&mut Recorder<BufWriter<File>>) -> std::io::Result<()>,
+    ) {
+        if let Some(Err(e)) = self.recorder.as_mut().map(write) {
+            log::error!("Failed to record the inputs, the recording is stopped: {e}");
+            self.recorder = None;
+        }
+    }
+
+    fn is_replaying(&self) -> bool {
+        cfg_if::cfg_if! {
+            if #[cfg(target_arch = "wasm32")] {
+                false
+            } else {
+                self.replay.is_some()
+            }
+        }
+    }
+
+    /// Whether the selected layer, or the toy, is paused.
+    fn is_paused(&self) -> bool {
+        match self.layers.selected() {
+            Some(index) => self.layers.layers()[index].is_paused(),
+            None => self.clock.is_paused(),
+        }
+    }
+
+    /// Pause the selected layer, or the toy and the layers following its clock.
+    fn set_paused(&mut self, paused: bool) {
+        if let Some(layer) = self.layers.selected_mut() {
+            layer.set_paused(paused);
+        } else if paused {
+            self.clock.pause(Instant::now());
+        } else {
+            self.clock.resume(Instant::now());
+        }
+    }
+
+    /// Change the speed of the time of the selected layer, or of the toy.
+    fn set_speed(&mut self, speed: f64) {
+        match self.layers.selected_mut() {
+            Some(layer) => layer.set_speed(speed as f32),
+            None => self.clock.set_speed(speed, Instant::now()),
+        }
+    }
+
+    /// The view moved by the controls, the one of the selected layer or of the toy.
+    fn view_mut(&mut self) -> &mut View {
+        match self.layers.selected_mut() {
+            Some(layer) => layer.view_mut(),
+            None => &mut self.view,
+        }
+    }
+
+    fn update_shader(&mut self, shader: (&str, String)) {
+        self.view = View::default();
+        self.set_shader(shader);
+    }
+
+    /// Show the shader once its pipeline is compiled, keeping the view.
+    fn set_shader(&mut self, shader: (&str, String)) {
+        let (_, content) = shader;
+
+        // Compile right away while replaying, the frames mustn't depend on the compilation time.
+        let pipeline = if self.is_replaying() {
+            Some(
+                self.pipeline_cache
+                    .get_or_build(&self.device, &content, HDR_FORMAT),
+            )
+        } else {
+            self.pipeline_cache.get(&content, HDR_FORMAT)
+        };
+        match pipeline {
+            Some(pipeline) => {
+                self.show_pipeline(pipeline);
+                self.pending_shader = None;
+            }
+            None => {
+                self.pipeline_cache.compile_in_background(
+                    &self.device,
+                    content.clone(),
+                    HDR_FORMAT,
+                );
+                self.pending_shader = Some(content);
+            }
+        }
+
+        self.update_title();
+        self.prefetch_neighbours();
+    }
+
+    /// Compile the toys around the current one, so they are ready when switching to them.
+    fn prefetch_neighbours(&mut self) {
+        for (_, content) in [
+            self.shader_list.peek_next_shader(),
+            self.shader_list.peek_previous_shader(),
+        ] {
+            self.pipeline_cache
+                .compile_in_background(&self.device, content, HDR_FORMAT);
+        }
+    }
+
+    /// Swap to the pending toy once its pipeline is compiled.
+    fn poll_pending_shader(&mut self) {
+        self.pipeline_cache.poll(&self.device);
+
+        let Some(content) = &self.pending_shader else {
+            return;
+        };
+        if let Some(pipeline) = self.pipeline_cache.get(content, HDR_FORMAT) {
+            self.show_pipeline(pipeline);
+            self.pending_shader = None;
+            self.update_title();
+        }
+    }
+
+    /// Render `pipeline` instead of the current toy.
+    fn show_pipeline(&mut self, pipeline: Arc<pipeline::ToyPipeline>) {
+        let previous = std::mem::replace(&mut self.pipeline, pipeline);
+        self.close_hidden_streams(&previous);
+    }
+
+    /// Stop the streams of a toy that was removed from the screen, unless it's still a layer or
+    /// the current toy. The cached and prefetched pipelines keep no stream open.
+    fn close_hidden_streams(&self, pipeline: &Arc<pipeline::ToyPipeline>) {
+        let shown = Arc::ptr_eq(pipeline, &self.pipeline)
+            || self
+                .layers
+                .layers()
+                .iter()
+                .any(|layer| Arc::ptr_eq(layer.pipeline(), pipeline));
+        if !shown {
+            pipeline.close_streams();
+        }
+    }
+
+    fn update_title(&self) {
+        if let Some(prompt) = &self.prompt {
+            let target = match self.prompt_target() {
+                Some((index, variant)) => {
+                    let name = self.shader_list.shader_name(index).unwrap_or_default();
+                    match self.shader_list.variant_name(index, variant) {
+                        Some(variant) => format!("{name}, variant: {variant}"),
+                        None => name.to_string(),
+                    }
+                }
+                None => "no match".to_string(),
+            };
+            self.window.set_title(&format!("> {prompt} ({target})"));
+            return;
+        }
+
+        let mut title = format!(
+            "{}{} (tonemap: {})",
+            if self.shader_list.is_favourite() {
+                "* "
+            } else {
+                ""
+            },
+            self.shader_list.current_shader_name(),
+            self.tonemap_pass.tonemap()
         );
+        if let Some(variant) = self.shader_list.current_variant_name() {
+            title = format!("{title} (variant: {variant})");
+        }
+        if !self.layers.layers().is_empty() {
+            let layers = self
+                .layers
+                .layers()
+                .iter()
+                .enumerate()
+                .map(|(index, layer)| {
+                    format!(
+                        "{}{} {} {:.0}%{}",
+                        if self.layers.selected() == Some(index) {
+                            "> "
+                        } else {
+                            ""
+                        },
+                        layer.name(),
+                        layer.blend(),
+                        layer.opacity() * 100.,
+                        if layer.is_paused() { " paused" } else { "" }
+                    )
+                })
+                .collect::<Vec<_>>();
+            title = format!("{title} (layers: {})", layers.join(", "));
+        }
+        let effects = self.post.effects();
+        if !effects.is_empty() {
+            let effects = effects.iter().map(Effect::to_string).collect::<Vec<_>>();
+            title = format!("{title} (effects: {})", effects.join(", "));
+        }
+        // The view moved by the controls.
+        let view = match self.layers.selected() {
+            Some(index) => self.layers.layers()[index].view(),
+            None => &self.view,
+        };
+        if view.zoom != 1. {
+            title = format!("{title} (zoom: {:.3e})", view.zoom);
+        }
+        if view.angle != 0. {
+            title = format!("{title} (rotation: {:.0}°)", view.angle.to_degrees());
+        }
+        if self.pending_shader.is_some() {
+            title = format!("{title} [loading]");
+        }
+        if self.clock.is_paused() {
+            title = format!("{title} [paused]");
+        }
+        if self.is_replaying() {
+            title = format!("{title} [replay]");
+        }
+        if self.show_stats {
+            title = format!("{title} | {}", self.frame_stats);
+        }
+        self.window.set_title(&title);
+    }
+
+    fn export_stats(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            !self.is_replaying(),
+            "The statistics aren't exported while replaying"
+        );
+        let timestamp = unix_timestamp();
+        let path = format!("frame-stats-{timestamp}.csv");
+        let file = std::fs::File::create(&path).with_context(|| format!("Can't create {path}"))?;
+
+        self.frame_stats
+            .write_csv(std::io::BufWriter::new(file))
+            .with_context(|| format!("Can't write to {path}"))?;
+
+        log::info!("Frame statistics exported to {path}");
+        Ok(())
+    }
+
+    /// Save a screenshot in the current directory, return its path.
+    fn save_screenshot(&self, factor: u32) -> anyhow::Result<String> {
+        anyhow::ensure!(
+            !self.is_replaying(),
+            "The screenshots aren't saved while replaying, use `--replay-frames`"
+        );
+        anyhow::ensure!(
+            self.pending_shader.is_none(),
+            "The toy is still being compiled"
+        );
+
+        let timestamp = unix_timestamp();
+        let name = self.shader_list.current_shader_name();
+        let path = format!(
+            "screenshot-{}-{timestamp}.png",
+            name.trim_end_matches(".wgsl").replace('/', "-")
+        );
+
+        self.screenshot()
+            .save(self.uniform, factor, path.as_ref())?;
+
+        log::info!(
+            "Screenshot of {name} saved to {path} ({}x{})",
+            self.size.width * factor,
+            self.size.height * factor
+        );
+        Ok(path)
+    }
+
+    /// Save the frame just rendered from a recording, `--screenshot-scale` times bigger.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_replay_frame(&self) -> anyhow::Result<()> {
+        let (Some(dir), Some(replay)) = (&self.replay_frames, &self.replay) else {
+            return Ok(());
+        };
+
+        let path = dir.join(format!("frame-{:05}.png", replay.frame() - 1));
+        self.screenshot()
+            .save(self.uniform, self.screenshot_scale, &path)
+    }
+
+    fn screenshot(&self) -> Screenshot<'_> {
+        Screenshot {
+            device: &self.device,
+            queue: &self.queue,
+            pipeline: &self.pipeline,
+            uniform_buffer: &self.uniform_buffer,
+            uniform_bind_group: &self.uniform_bind_group,
+            layers: &self.layers,
+            post: &self.post,
+            tonemap: self.tonemap_pass.tonemap(),
+        }
     }
 
     pub fn update(&mut self) {
@@ -140,12 +1070,38 @@ impl State {
             self.size = new_size;
             self.config.width = new_size.width;
             self.config.height = new_size.height;
+            let [width, height] =
+                self.tonemap_pass
+                    .resize(&self.queue, new_size.width, new_size.height);
+            self.uniform.scale = 1. / self.tonemap_pass.render_scale();
             self.uniform.resolution = [new_size.width as f32, new_size.height as f32];
+            self.uniform.target_resolution = [width as f32, height as f32];
             self.surface.configure(&self.device, &self.config);
+            self.targets =
+                self.post
+                    .create_targets(&self.device, &self.tonemap_pass, width, height);
         }
     }
 
     pub fn render(&mut self) -> Result<(), SurfaceError> {
+        self.device.poll(wgpu::Maintain::Poll);
+        self.poll_pending_shader();
+        if let Some(gpu_time) = self.gpu_timer.poll() {
+            self.frame_stats.gpu(gpu_time);
+        }
+
+        let start = Instant::now();
+        if self.frame_stats.should_report(start) {
+            log::info!(
+                "{}: {}",
+                self.shader_list.current_shader_name(),
+                self.frame_stats
+            );
+            if self.show_stats {
+                self.update_title();
+            }
+        }
+
         let output = self.surface.get_current_texture()?;
         let view = output
             .texture
@@ -156,166 +1112,197 @@ impl State {
                 label: Some("render-encoder"),
             });
 
-        self.uniform.time = self.time.elapsed().as_secs_f32();
+        #[cfg(not(target_arch = "wasm32"))]
+        self.update_osc();
+        let time = self.frame_time();
+        #[cfg(not(target_arch = "wasm32"))]
+        self.record(|recorder| recorder.frame(time));
+        self.layers.advance((time - self.uniform.time).max(0.));
+        self.uniform.time = time;
+        self.view.update_uniform(&mut self.uniform);
+        self.uniform.vertex_count = self.pipeline.geometry().vertex_count() as f32;
 
         self.queue
             .write_buffer(&self.uniform_buffer, 0, self.uniform.as_ref());
 
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("render-pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        // load: wgpu::LoadOp::Clear(wgpu::Color {
-                        //     r: 1.,
-                        //     g: 1.,
-                        //     b: 1.,
-                        //     a: 1.,
-                        // }),
-                        load: wgpu::LoadOp::Load,
-                        store: true,
-                    },
-                })],
-                depth_stencil_attachment: None,
-            });
-
-            render_pass.set_pipeline(&self.pipeline);
-            render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
-            render_pass.draw(0..3, 0..1);
+        self.pipeline
+            .update_channels(&mut encoder, self.uniform.time);
+        self.layers.update_channels(&mut encoder, &self.pipeline);
+        if self.gpu_timer.begin(&mut encoder) {
+            self.frame_stats.measure_gpu();
         }
 
+        pipeline::render_toy(
+            &mut encoder,
+            &self.pipeline,
+            &self.uniform_bind_group,
+            &self.targets.input.view,
+        );
+        self.layers
+            .render(&mut encoder, &self.uniform, &self.targets);
+
+        let hdr_target = self
+            .post
+            .render(&mut encoder, &self.uniform_bind_group, &self.targets);
+        self.tonemap_pass.render(&mut encoder, hdr_target, &view);
+
+        self.gpu_timer.end(&mut encoder);
+
         self.queue.submit(std::iter::once(encoder.finish()));
+        self.frame_stats.frame(start, Instant::now());
+        self.gpu_timer.submitted(&self.queue);
+        self.pipeline.channels_submitted();
+        self.layers.channels_submitted();
 
         output.present();
 
+        if let Some(output) = &mut self.output {
+            output.render(
+                &self.pipeline,
+                &self.layers,
+                &self.post,
+                self.uniform,
+                &self.uniform_buffer,
+                &self.uniform_bind_group,
+            );
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Err(e) = self.save_replay_frame() {
+            log::error!("Failed to save the replayed frame, no longer saving them: {e:#}");
+            self.replay_frames = None;
+        }
+
         Ok(())
     }
 }
 
+/// Create the surfaces of the main and output windows from the same instance,
+/// so they can share the device.
 async fn get_surface_device_queue(
     window: &Window,
-) -> anyhow::Result<(wgpu::Adapter, wgpu::Surface, wgpu::Device, wgpu::Queue)> {
-    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-        backends: wgpu::Backends::all(),
-        dx12_shader_compiler: Default::default(),
-    });
+    output_window: Option<&Window>,
+) -> anyhow::Result<(
+    wgpu::Adapter,
+    wgpu::Surface,
+    Option<wgpu::Surface>,
+    wgpu::Device,
+    wgpu::Queue,
+)> {
+    let instance = gpu::create_instance();
 
     let surface =
         unsafe { instance.create_surface(window) }.context("Failed to create the surface")?;
+    let output_surface = output_window
+        .map(|window| unsafe { instance.create_surface(window) })
+        .transpose()
+        .context("Failed to create the surface of the output window")?;
 
-    let adapter = instance
-        .request_adapter(&wgpu::RequestAdapterOptionsBase {
-            power_preference: wgpu::PowerPreference::default(),
-            force_fallback_adapter: false,
-            compatible_surface: Some(&surface),
-        })
-        .await
-        .context("Can't request a compatible adapter")?;
-
-    let adapter_info = adapter.get_info();
-
-    log::info!(
-        "Requested adapter: `{}` with driver `{}`",
-        adapter_info.name,
-        adapter_info.driver
-    );
-
-    let (device, queue) = adapter
-        .request_device(
-            &wgpu::DeviceDescriptor {
-                label: Some("wgpu-device"),
-                features: wgpu::Features::empty(),
-                limits: wgpu::Limits::default(),
-            },
-            None,
-        )
-        .await
-        .context("Can't request compatible device")?;
+    let adapter = gpu::request_adapter(&instance, Some(&surface)).await?;
+    if let Some(output_surface) = &output_surface {
+        anyhow::ensure!(
+            adapter.is_surface_supported(output_surface),
+            "The adapter `{}` can't present to the output window",
+            adapter.get_info().name
+        );
+    }
+    let (device, queue) = gpu::request_device(&adapter).await?;
 
-    Ok((adapter, surface, device, queue))
+    Ok((adapter, surface, output_surface, device, queue))
 }
 
 fn gen_config(
-    adapter: wgpu::Adapter,
+    adapter: &wgpu::Adapter,
     surface: &wgpu::Surface,
     size: &PhysicalSize<u32>,
+    present_mode: PresentMode,
 ) -> anyhow::Result<wgpu::SurfaceConfiguration> {
-    let caps = surface.get_capabilities(&adapter);
+    let caps = surface.get_capabilities(adapter);
 
+    // Prefer a sRGB surface so the GPU handle the encoding,
+    // otherwise the tonemap pass will apply the transfer function itself.
     let surface_format = caps
         .formats
         .iter()
         .find(|f| f.is_srgb())
+        .or_else(|| caps.formats.first())
         .copied()
         .context("Can't find a compatible surface format")?;
 
+    let present_mode = present_mode.select(&caps.present_modes);
+    let alpha_mode = caps
+        .alpha_modes
+        .first()
+        .copied()
+        .context("Can't find a compatible surface alpha mode")?;
+
+    log::info!("Using surface format `{surface_format:?}` with present mode {present_mode:?}");
+
     Ok(wgpu::SurfaceConfiguration {
         usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
         format: surface_format,
         width: size.width,
         height: size.height,
-        present_mode: caps.present_modes[0],
-        alpha_mode: caps.alpha_modes[0],
+        present_mode,
+        alpha_mode,
         view_formats: vec![],
     })
 }
 
-fn build_pipeline(
-    device: &wgpu::Device,
-    bind_group_layouts: &[&wgpu::BindGroupLayout],
-    texture_format: wgpu::TextureFormat,
-    fragment_shader: String,
-) -> wgpu::RenderPipeline {
-    let vs_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-        label: Some("vertex-shader"),
-        source: wgpu::ShaderSource::Wgsl(Cow::from(include_str!("assets/vertex.wgsl"))),
-    });
-
-    let fs_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-        label: Some("fragment-shader"),
-        source: wgpu::ShaderSource::Wgsl(Cow::from(fragment_shader)),
-    });
-
-    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-        label: Some("render-pipeline-layout"),
-        bind_group_layouts,
-        push_constant_ranges: &[],
-    });
-
-    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        label: Some("render-pipeline"),
-        layout: Some(&layout),
-        vertex: wgpu::VertexState {
-            module: &vs_module,
-            entry_point: "main",
-            buffers: &[],
-        },
-        primitive: wgpu::PrimitiveState {
-            topology: wgpu::PrimitiveTopology::TriangleList,
-            strip_index_format: None,
-            front_face: wgpu::FrontFace::Ccw,
-            cull_mode: Some(wgpu::Face::Back),
-            unclipped_depth: false,
-            polygon_mode: wgpu::PolygonMode::Fill,
-            conservative: false,
-        },
-        depth_stencil: None,
-        multisample: wgpu::MultisampleState {
-            count: 1,
-            mask: !0,
-            alpha_to_coverage_enabled: false,
-        },
-        fragment: Some(wgpu::FragmentState {
-            module: &fs_module,
-            entry_point: "main",
-            targets: &[Some(wgpu::ColorTargetState {
-                format: texture_format,
-                blend: Some(wgpu::BlendState::REPLACE),
-                write_mask: wgpu::ColorWrites::ALL,
-            })],
-        }),
-        multiview: None,
-    })
+/// Find a toy by its number, starting from 1, or by the best match of a search,
+/// with the variant matching the search.
+fn find_toy(shader_list: &ShaderList, query: &str) -> Option<(usize, usize)> {
+    let query = query.trim();
+
+    if !query.is_empty() && query.chars().all(|c| c.is_ascii_digit()) {
+        query
+            .parse::<usize>()
+            .ok()?
+            .checked_sub(1)
+            .filter(|&index| index < shader_list.len())
+            .map(|index| (index, 0))
+    } else {
+        let index = shader_list.search(query).first().copied()?;
+        Some((index, shader_list.matching_variant(index, query)))
+    }
+}
+
+/// Load a recording, select its toy and resize the window to its size.
+#[cfg(not(target_arch = "wasm32"))]
+fn start_replay(
+    path: &std::path::Path,
+    window: &Window,
+    shader_list: &mut ShaderList,
+) -> anyhow::Result<Replay> {
+    let replay = Replay::load(path)?;
+
+    let index = shader_list
+        .position(replay.toy())
+        .with_context(|| format!("The toy `{}` of the recording doesn't exist", replay.toy()))?;
+    shader_list.select(index);
+    let [width, height] = replay.size();
+    window.set_inner_size(PhysicalSize::new(width, height));
+
+    log::info!("Replaying {}", path.display());
+    Ok(replay)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn listen_osc(port: u16, mapping: Option<&std::path::Path>) -> anyhow::Result<Osc> {
+    let mappings = match mapping {
+        Some(path) => osc::load_mappings(path)?,
+        None => osc::parse_mappings(osc::DEFAULT_MAPPING)?,
+    };
+    let osc = Osc::new(mappings, Instant::now()).listen(port)?;
+
+    log::info!("Listening for OSC messages on the UDP port {port}");
+    Ok(osc)
+}
+
+/// Seconds since the unix epoch, used to name the exported files.
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
 }
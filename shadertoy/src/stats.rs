@@ -0,0 +1,540 @@
+use std::{collections::BTreeSet, io::Write};
+
+use anyhow::Context;
+use naga::{
+    AddressSpace, BinaryOperator, Block, ConstantInner, Expression, Function, Handle, Module,
+    ScalarValue, ShaderStage, Statement, TypeInner,
+};
+
+use crate::{bench::ReportFormat, cli::StatsOptions, shader_list::ShaderList};
+
+/// Iterations per pixel above which a toy may trigger a GPU timeout on slow hardware.
+const ITERATIONS_WARNING: u64 = 1000;
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+struct ToyStats {
+    name: String,
+    #[serde(flatten)]
+    complexity: Option<Complexity>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// What the fragment shader of a toy does, or its vertex shader for the vertex toys,
+/// counting only the functions it calls.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct Complexity {
+    /// Functions called from the entry point, directly or not.
+    functions: usize,
+    /// Deepest nesting of loops, following the function calls.
+    loop_depth: usize,
+    /// Greatest bound of the loops, the bound of a loop being the smallest constant
+    /// its exit conditions compare an integer counter to.
+    max_loop_bound: Option<i64>,
+    /// Iterations per pixel, or per vertex, of the most nested loops,
+    /// `None` if a loop has no constant bound.
+    pub max_iterations: Option<u64>,
+    texture_samples: usize,
+    /// Fields of the uniform read by the toy.
+    uniforms: Vec<String>,
+    warnings: Vec<String>,
+}
+
+/// Parse every assembled toy with naga and print a complexity report on stdout.
+pub fn run(shader_list: &ShaderList, options: StatsOptions) -> anyhow::Result<()> {
+    let stats = shader_list
+        .iter()
+        .map(|(name, shader)| match analyze(&shader) {
+            Ok(complexity) => ToyStats {
+                name,
+                complexity: Some(complexity),
+                error: None,
+            },
+            Err(e) => ToyStats {
+                name,
+                complexity: None,
+                error: Some(format!("{e:#}")),
+            },
+        })
+        .collect::<Vec<_>>();
+
+    let stdout = std::io::stdout().lock();
+    match options.format {
+        ReportFormat::Table => write_table(&stats, stdout),
+        ReportFormat::Json => {
+            serde_json::to_writer_pretty(stdout, &stats).map_err(std::io::Error::from)
+        }
+    }
+    .context("Can't write the report")
+}
+
+fn analyze(shader: &str) -> anyhow::Result<Complexity> {
+    let module = naga::front::wgsl::parse_str(shader).map_err(|e| anyhow::anyhow!("{e}"))?;
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(&module)
+    .map_err(|e| anyhow::anyhow!("{}", e.into_inner()))?;
+
+    complexity(&module)
+}
+
+/// Analyze a module already validated.
+pub fn complexity(module: &Module) -> anyhow::Result<Complexity> {
+    // The vertex toys do their work in the vertex shader, their fragment shader only
+    // returns the color of the vertex.
+    let entry_point = [ShaderStage::Vertex, ShaderStage::Fragment]
+        .iter()
+        .find_map(|&stage| {
+            module
+                .entry_points
+                .iter()
+                .find(|entry_point| entry_point.stage == stage)
+        })
+        .context("The shader has no entry point")?;
+
+    let mut analyzer = Analyzer {
+        module,
+        functions: BTreeSet::new(),
+        bounds: Vec::new(),
+        texture_samples: 0,
+        uniforms: BTreeSet::new(),
+    };
+    analyzer.scan(&entry_point.function);
+    let loops = analyzer.block(&entry_point.function, &entry_point.function.body);
+
+    let mut complexity = Complexity {
+        functions: analyzer.functions.len(),
+        loop_depth: loops.depth,
+        max_loop_bound: analyzer.bounds.iter().copied().max(),
+        max_iterations: loops.iterations,
+        texture_samples: analyzer.texture_samples,
+        uniforms: analyzer
+            .uniforms
+            .into_iter()
+            .map(|(_, name)| name)
+            .collect(),
+        warnings: Vec::new(),
+    };
+    match complexity.max_iterations {
+        None => complexity
+            .warnings
+            .push("loop without a constant bound".to_string()),
+        Some(iterations) if iterations >= ITERATIONS_WARNING => complexity
+            .warnings
+            .push(format!("up to {iterations} iterations per pixel")),
+        Some(_) => {}
+    }
+
+    Ok(complexity)
+}
+
+/// The loops of a block of code.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Loops {
+    depth: usize,
+    /// Iterations of the most nested loops, `None` if unknown.
+    iterations: Option<u64>,
+}
+
+impl Loops {
+    const NONE: Self = Self {
+        depth: 0,
+        iterations: Some(1),
+    };
+
+    /// The worst of both.
+    fn max(self, other: Self) -> Self {
+        Self {
+            depth: self.depth.max(other.depth),
+            iterations: self.iterations.zip(other.iterations).map(|(a, b)| a.max(b)),
+        }
+    }
+}
+
+/// Walk the functions called from the entry point.
+struct Analyzer<'a> {
+    module: &'a Module,
+    functions: BTreeSet<Handle<Function>>,
+    /// The constant bound of every loop.
+    bounds: Vec<i64>,
+    texture_samples: usize,
+    /// The index and name of the uniform fields read.
+    uniforms: BTreeSet<(u32, String)>,
+}
+
+impl Analyzer<'_> {
+    /// Count the texture samples and the uniform fields read by a function.
+    fn scan(&mut self, function: &Function) {
+        for (_, expression) in function.expressions.iter() {
+            match expression {
+                Expression::ImageSample { .. } => self.texture_samples += 1,
+                Expression::AccessIndex { base, index } => {
+                    if let Some(name) = self.uniform_field(function, *base, *index) {
+                        self.uniforms.insert((*index, name));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn block(&mut self, function: &Function, block: &Block) -> Loops {
+        block
+            .iter()
+            .map(|statement| self.statement(function, statement))
+            .fold(Loops::NONE, Loops::max)
+    }
+
+    fn statement(&mut self, function: &Function, statement: &Statement) -> Loops {
+        match statement {
+            Statement::Block(block) => self.block(function, block),
+            Statement::If { accept, reject, .. } => self
+                .block(function, accept)
+                .max(self.block(function, reject)),
+            Statement::Switch { cases, .. } => cases
+                .iter()
+                .map(|case| self.block(function, &case.body))
+                .fold(Loops::NONE, Loops::max),
+            Statement::Loop {
+                body,
+                continuing,
+                break_if,
+            } => {
+                let bound = self.loop_bound(function, body, *break_if);
+                if let Some(bound) = bound {
+                    self.bounds.push(bound);
+                }
+                let inner = self
+                    .block(function, body)
+                    .max(self.block(function, continuing));
+                Loops {
+                    depth: inner.depth + 1,
+                    iterations: bound
+                        .zip(inner.iterations)
+                        .map(|(bound, inner)| (bound.max(0) as u64).saturating_mul(inner)),
+                }
+            }
+            Statement::Call {
+                function: handle, ..
+            } => {
+                // WGSL forbids recursion, so this always terminates.
+                let callee = &self.module.functions[*handle];
+                if self.functions.insert(*handle) {
+                    self.scan(callee);
+                }
+                self.block(callee, &callee.body)
+            }
+            _ => Loops::NONE,
+        }
+    }
+
+    /// The smallest integer constant compared to in the conditions exiting the loop,
+    /// e.g. `MAX_ITER` in `for (var i = 0; i < MAX_ITER; i++)`.
+    fn loop_bound(
+        &self,
+        function: &Function,
+        body: &Block,
+        break_if: Option<Handle<Expression>>,
+    ) -> Option<i64> {
+        let mut exits = Vec::new();
+        exit_conditions(body, &mut exits);
+
+        exits
+            .into_iter()
+            .chain(break_if)
+            .filter_map(|condition| self.compared_constant(function, condition))
+            .min()
+    }
+
+    /// The greatest integer constant compared to in a condition.
+    fn compared_constant(&self, function: &Function, condition: Handle<Expression>) -> Option<i64> {
+        match &function.expressions[condition] {
+            Expression::Unary { expr, .. } => self.compared_constant(function, *expr),
+            Expression::Binary { op, left, right } => {
+                if matches!(
+                    op,
+                    BinaryOperator::Less
+                        | BinaryOperator::LessEqual
+                        | BinaryOperator::Greater
+                        | BinaryOperator::GreaterEqual
+                        | BinaryOperator::NotEqual
+                ) {
+                    [*left, *right]
+                        .into_iter()
+                        .filter_map(|operand| self.integer_constant(function, operand))
+                        .max()
+                } else {
+                    [*left, *right]
+                        .into_iter()
+                        .filter_map(|operand| self.compared_constant(function, operand))
+                        .max()
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn integer_constant(&self, function: &Function, expression: Handle<Expression>) -> Option<i64> {
+        let Expression::Constant(constant) = function.expressions[expression] else {
+            return None;
+        };
+        match self.module.constants[constant].inner {
+            ConstantInner::Scalar {
+                value: ScalarValue::Sint(value),
+                ..
+            } => Some(value),
+            ConstantInner::Scalar {
+                value: ScalarValue::Uint(value),
+                ..
+            } => i64::try_from(value).ok(),
+            _ => None,
+        }
+    }
+
+    /// The name of the field `index` if `base` is the uniform.
+    fn uniform_field(
+        &self,
+        function: &Function,
+        base: Handle<Expression>,
+        index: u32,
+    ) -> Option<String> {
+        let Expression::GlobalVariable(variable) = function.expressions[base] else {
+            return None;
+        };
+        let variable = &self.module.global_variables[variable];
+        if variable.space != AddressSpace::Uniform {
+            return None;
+        }
+        match &self.module.types[variable.ty].inner {
+            TypeInner::Struct { members, .. } => members.get(index as usize)?.name.clone(),
+            _ => None,
+        }
+    }
+}
+
+/// The conditions of the `if` breaking out of the loop of `body`,
+/// the body of a `for` loop being wrapped in a block by naga.
+fn exit_conditions(body: &Block, exits: &mut Vec<Handle<Expression>>) {
+    for statement in body.iter() {
+        match statement {
+            Statement::If {
+                condition,
+                accept,
+                reject,
+            } if [accept, reject]
+                .iter()
+                .any(|block| block.iter().any(|s| matches!(s, Statement::Break))) =>
+            {
+                exits.push(*condition)
+            }
+            Statement::Block(block) => exit_conditions(block, exits),
+            _ => {}
+        }
+    }
+}
+
+fn write_table(stats: &[ToyStats], mut writer: impl Write) -> std::io::Result<()> {
+    let name_width = stats
+        .iter()
+        .map(|stats| stats.name.len())
+        .chain(std::iter::once("toy".len()))
+        .max()
+        .unwrap_or_default();
+
+    writeln!(
+        writer,
+        "{:name_width$} | {:>9} | {:>10} | {:>9} | {:>10} | {:>7} | uniforms",
+        "toy", "functions", "loop depth", "max bound", "iterations", "samples"
+    )?;
+
+    for stats in stats {
+        let Some(complexity) = &stats.complexity else {
+            writeln!(
+                writer,
+                "{:name_width$} | error: {}",
+                stats.name,
+                stats
+                    .error
+                    .as_deref()
+                    .unwrap_or_default()
+                    .lines()
+                    .next()
+                    .unwrap_or_default()
+            )?;
+            continue;
+        };
+
+        writeln!(
+            writer,
+            "{:name_width$} | {:>9} | {:>10} | {:>9} | {:>10} | {:>7} | {}",
+            stats.name,
+            complexity.functions,
+            complexity.loop_depth,
+            format_option(complexity.max_loop_bound),
+            format_option(complexity.max_iterations),
+            complexity.texture_samples,
+            complexity.uniforms.join(", ")
+        )?;
+        for warning in &complexity.warnings {
+            writeln!(writer, "{:name_width$} | warning: {warning}", "")?;
+        }
+    }
+
+    Ok(())
+}
+
+fn format_option<T: ToString>(value: Option<T>) -> String {
+    value
+        .map(|value| value.to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{analyze, write_table, Complexity, ToyStats};
+
+    fn assemble(toy: &str) -> String {
+        format!(
+            "{}{toy}{}",
+            include_str!("assets/fragment.prefix.wgsl"),
+            include_str!("assets/fragment.suffix.wgsl")
+        )
+    }
+
+    #[test]
+    fn mandelbrot() {
+        let complexity = analyze(&assemble(include_str!(
+            "assets/toy/fractal/mandelbrot.wgsl"
+        )))
+        .unwrap();
+
+        assert_eq!(complexity.loop_depth, 1);
+        assert_eq!(complexity.max_loop_bound, Some(4000));
+        assert_eq!(complexity.max_iterations, Some(4000));
+        assert_eq!(complexity.texture_samples, 0);
+        assert!(complexity.uniforms.contains(&"view_zoom".to_string()));
+        assert_eq!(
+            complexity.warnings,
+            ["up to 4000 iterations per pixel".to_string()]
+        );
+    }
+
+    #[test]
+    fn nested_loops() {
+        let complexity = analyze(&assemble(
+            "
+fn inner(n: i32) -> f32 {
+    var sum = 0.;
+    for (var j = 0; j < 20; j++) {
+        sum += f32(j * n);
+    }
+    return sum;
+}
+
+fn main_image(frag_coord: vec4<f32>) -> vec4<f32> {
+    var sum = 0.;
+    for (var i = 0; i < 10; i++) {
+        sum += inner(i);
+    }
+    return vec4(sum);
+}
+",
+        ))
+        .unwrap();
+
+        assert_eq!(complexity.functions, 2);
+        assert_eq!(complexity.loop_depth, 2);
+        assert_eq!(complexity.max_loop_bound, Some(20));
+        assert_eq!(complexity.max_iterations, Some(200));
+        assert_eq!(complexity.uniforms, ["scale", "offset"]);
+        assert!(complexity.warnings.is_empty());
+    }
+
+    #[test]
+    fn early_exit() {
+        let complexity = analyze(&assemble(
+            "
+fn main_image(frag_coord: vec4<f32>) -> vec4<f32> {
+    var sum = 0.;
+    for (var i = 0; i < 100; i++) {
+        if i >= 30 {
+            break;
+        }
+        sum += f32(i);
+    }
+    return vec4(sum);
+}
+",
+        ))
+        .unwrap();
+
+        // The loop exits after 30 iterations at most, not 100.
+        assert_eq!(complexity.max_loop_bound, Some(30));
+        assert_eq!(complexity.max_iterations, Some(30));
+    }
+
+    #[test]
+    fn unbounded_loop() {
+        let complexity = analyze(&assemble(
+            "
+fn main_image(frag_coord: vec4<f32>) -> vec4<f32> {
+    var t = 0.;
+    loop {
+        t += 0.1;
+        if t > u.time { break; }
+    }
+    return vec4(t);
+}
+",
+        ))
+        .unwrap();
+
+        assert_eq!(complexity.max_iterations, None);
+        assert_eq!(complexity.warnings, ["loop without a constant bound"]);
+        assert!(complexity.uniforms.contains(&"time".to_string()));
+    }
+
+    #[test]
+    fn invalid_toy() {
+        assert!(analyze(&assemble("fn main_image() {}")).is_err());
+    }
+
+    #[test]
+    fn table() {
+        let stats = [
+            ToyStats {
+                name: "ring.wgsl".to_string(),
+                complexity: Some(Complexity {
+                    functions: 3,
+                    loop_depth: 1,
+                    max_loop_bound: Some(4000),
+                    max_iterations: Some(4000),
+                    texture_samples: 0,
+                    uniforms: vec!["resolution".to_string(), "time".to_string()],
+                    warnings: vec!["up to 4000 iterations per pixel".to_string()],
+                }),
+                error: None,
+            },
+            ToyStats {
+                name: "broken.wgsl".to_string(),
+                complexity: None,
+                error: Some("expected `;`\nmore details".to_string()),
+            },
+        ];
+
+        let mut table = Vec::new();
+        write_table(&stats, &mut table).unwrap();
+
+        assert_eq!(
+            String::from_utf8(table).unwrap(),
+            "\
+toy         | functions | loop depth | max bound | iterations | samples | uniforms
+ring.wgsl   |         3 |          1 |      4000 |       4000 |       0 | resolution, time
+            | warning: up to 4000 iterations per pixel
+broken.wgsl | error: expected `;`
+"
+        );
+    }
+}
@@ -0,0 +1,514 @@
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+    process::{Child, ChildStdout, Command, Stdio},
+    sync::mpsc,
+};
+
+use anyhow::Context;
+
+use crate::{channel::ChannelSource, screenshot::padded_bytes_per_row};
+
+/// Number of staging buffers, a frame can be written while the previous ones are in flight.
+const RING_SIZE: usize = 3;
+const BYTES_PER_PIXEL: u32 = 4;
+
+/// A channel playing the frames of an image sequence or of a video in sync with the toy clock.
+///
+/// The frames are decoded on a thread and uploaded through a ring of staging buffers,
+/// a frame that isn't decoded yet or without a free buffer keeps the previous one on screen
+/// rather than stalling the render.
+/// The thread only runs while the stream is shown, it's started by [`Stream::update`] and
+/// stopped by [`Stream::close`].
+pub struct Stream {
+    source: ChannelSource,
+    texture: wgpu::Texture,
+    width: u32,
+    height: u32,
+    fps: f32,
+    decoding: Option<Decoding>,
+    /// A decoded frame waiting for a free staging buffer.
+    waiting: Option<Vec<u8>>,
+    ring: Vec<Staging>,
+}
+
+/// The channels to the decoding thread, which stops once they are dropped.
+struct Decoding {
+    requests: mpsc::Sender<u64>,
+    decoded: mpsc::Receiver<Vec<u8>>,
+    /// The last frame requested to the decoder.
+    requested: Option<u64>,
+}
+
+struct Staging {
+    buffer: wgpu::Buffer,
+    state: StagingState,
+}
+
+enum StagingState {
+    /// Mapped, ready to be written.
+    Free,
+    /// Copied to the texture by an encoder not yet submitted, to map once it is.
+    Copied,
+    Mapping(mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>),
+}
+
+/// Decode the frames of a stream, in RGBA.
+trait Decoder {
+    /// The frame `index`, wrapping around at the end of the stream.
+    fn frame(&mut self, index: u64) -> anyhow::Result<Vec<u8>>;
+}
+
+/// Open the decoder of a sequence or of a video, with the size and the frame rate of the stream.
+fn open_decoder(source: &ChannelSource) -> anyhow::Result<(Box<dyn Decoder>, u32, u32, f32)> {
+    match source {
+        ChannelSource::Sequence { directory, fps } => {
+            let sequence = Sequence::open(Path::new(directory))?;
+            let (width, height) = (sequence.width, sequence.height);
+            Ok((Box::new(sequence), width, height, *fps))
+        }
+        ChannelSource::Video(file) => {
+            let video = Video::open(Path::new(file))?;
+            let (width, height, fps) = (video.width, video.height, video.fps);
+            Ok((Box::new(video), width, height, fps))
+        }
+        _ => anyhow::bail!("The channel isn't a sequence or a video"),
+    }
+}
+
+impl Stream {
+    /// Open the sequence or the video and show its first frame.
+    ///
+    /// The decoder is closed once the frame is read, so a stream of a cached pipeline that isn't
+    /// shown doesn't hold a thread nor an `ffmpeg` process.
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        source: &ChannelSource,
+    ) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            cfg!(not(target_arch = "wasm32")),
+            "The sequence and video channels need the native viewer"
+        );
+
+        let (mut decoder, width, height, fps) = open_decoder(source)?;
+
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("stream-texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            texture.as_image_copy(),
+            &decoder.frame(0)?,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(width * BYTES_PER_PIXEL),
+                rows_per_image: Some(height),
+            },
+            size,
+        );
+
+        let ring = (0..RING_SIZE)
+            .map(|_| Staging {
+                buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("stream-staging-buffer"),
+                    size: u64::from(padded_bytes_per_row(width)) * u64::from(height),
+                    usage: wgpu::BufferUsages::MAP_WRITE | wgpu::BufferUsages::COPY_SRC,
+                    mapped_at_creation: true,
+                }),
+                state: StagingState::Free,
+            })
+            .collect();
+
+        Ok(Self {
+            source: source.clone(),
+            texture,
+            width,
+            height,
+            fps,
+            decoding: None,
+            waiting: None,
+            ring,
+        })
+    }
+
+    pub fn create_view(&self) -> wgpu::TextureView {
+        self.texture
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    /// Request the frame shown at `time` and copy the latest decoded frame to the texture.
+    pub fn update(&mut self, encoder: &mut wgpu::CommandEncoder, time: f32) {
+        for staging in &mut self.ring {
+            if let StagingState::Mapping(rx) = &staging.state {
+                match rx.try_recv() {
+                    Ok(Ok(())) => staging.state = StagingState::Free,
+                    Ok(Err(_)) | Err(mpsc::TryRecvError::Disconnected) => {
+                        // Mapped again after the next submission.
+                        log::warn!("Can't map a staging buffer of the stream");
+                        staging.state = StagingState::Copied;
+                    }
+                    Err(mpsc::TryRecvError::Empty) => {}
+                }
+            }
+        }
+
+        if self.decoding.is_none() {
+            match Decoding::start(self.source.clone()) {
+                Ok(decoding) => self.decoding = Some(decoding),
+                Err(e) => log::error!("{e:#}"),
+            }
+        }
+        if let Some(decoding) = &mut self.decoding {
+            let frame = (time.max(0.) * self.fps) as u64;
+            if decoding.requested != Some(frame) {
+                // The thread stopped on an error, the last frame stays on screen.
+                let _ = decoding.requests.send(frame);
+                decoding.requested = Some(frame);
+            }
+            if let Some(texels) = decoding.decoded.try_iter().last() {
+                self.waiting = Some(texels);
+            }
+        }
+
+        let Some(staging) = self
+            .ring
+            .iter_mut()
+            .find(|staging| matches!(staging.state, StagingState::Free))
+        else {
+            return;
+        };
+        let Some(texels) = self.waiting.take() else {
+            return;
+        };
+
+        let bytes_per_row = (self.width * BYTES_PER_PIXEL) as usize;
+        let padded_bytes_per_row = padded_bytes_per_row(self.width);
+        {
+            let mut view = staging.buffer.slice(..).get_mapped_range_mut();
+            for (dst, src) in view
+                .chunks_mut(padded_bytes_per_row as usize)
+                .zip(texels.chunks(bytes_per_row))
+            {
+                dst[..bytes_per_row].copy_from_slice(src);
+            }
+        }
+        staging.buffer.unmap();
+
+        encoder.copy_buffer_to_texture(
+            wgpu::ImageCopyBuffer {
+                buffer: &staging.buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            self.texture.as_image_copy(),
+            wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+        );
+        staging.state = StagingState::Copied;
+    }
+
+    /// Must be called right after the encoder passed to [`Stream::update`] was submitted,
+    /// the staging buffers are mapped again once the copy is done.
+    /// The device need to be polled for them to be available.
+    pub fn submitted(&mut self) {
+        for staging in &mut self.ring {
+            if let StagingState::Copied = staging.state {
+                let (tx, rx) = mpsc::channel();
+                staging
+                    .buffer
+                    .slice(..)
+                    .map_async(wgpu::MapMode::Write, move |res| {
+                        let _ = tx.send(res);
+                    });
+                staging.state = StagingState::Mapping(rx);
+            }
+        }
+    }
+
+    /// Stop the decoding thread, and the `ffmpeg` process of a video, until the next update.
+    pub fn close(&mut self) {
+        self.decoding = None;
+        self.waiting = None;
+    }
+}
+
+impl Decoding {
+    /// Open the decoder on a thread, so the render doesn't wait for `ffprobe`.
+    fn start(source: ChannelSource) -> anyhow::Result<Self> {
+        let (requests, requests_rx) = mpsc::channel::<u64>();
+        let (decoded_tx, decoded) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("stream-decoder".to_string())
+            .spawn(move || {
+                let mut decoder = match open_decoder(&source) {
+                    Ok((decoder, ..)) => decoder,
+                    Err(e) => {
+                        log::error!("Can't open the stream: {e:#}");
+                        return;
+                    }
+                };
+                while let Ok(mut index) = requests_rx.recv() {
+                    // Skip the frames requested while the previous one was decoded.
+                    while let Ok(newer) = requests_rx.try_recv() {
+                        index = newer;
+                    }
+                    match decoder.frame(index) {
+                        Ok(texels) => {
+                            if decoded_tx.send(texels).is_err() {
+                                break;
+                            }
+                        }
+                        Err(e) => {
+                            log::error!("Can't decode the frame {index} of the stream: {e:#}");
+                            break;
+                        }
+                    }
+                }
+            })
+            .context("Failed to spawn the stream decoding thread")?;
+
+        Ok(Self {
+            requests,
+            decoded,
+            requested: None,
+        })
+    }
+}
+
+/// The numbered images of a directory.
+struct Sequence {
+    frames: Vec<PathBuf>,
+    width: u32,
+    height: u32,
+}
+
+impl Sequence {
+    fn open(directory: &Path) -> anyhow::Result<Self> {
+        let entries = std::fs::read_dir(directory)
+            .with_context(|| format!("Can't read {}", directory.display()))?;
+        let mut frames = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == "png") {
+                frames.push(path);
+            }
+        }
+        anyhow::ensure!(
+            !frames.is_empty(),
+            "There is no PNG in {}",
+            directory.display()
+        );
+        frames.sort_by_cached_key(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            (frame_number(&name), name.into_owned())
+        });
+
+        let (width, height) = image::image_dimensions(&frames[0])
+            .with_context(|| format!("Can't read {}", frames[0].display()))?;
+        Ok(Self {
+            frames,
+            width,
+            height,
+        })
+    }
+}
+
+impl Decoder for Sequence {
+    fn frame(&mut self, index: u64) -> anyhow::Result<Vec<u8>> {
+        let path = &self.frames[(index % self.frames.len() as u64) as usize];
+        let image = image::open(path)
+            .with_context(|| format!("Can't decode {}", path.display()))?
+            .to_rgba8();
+        anyhow::ensure!(
+            image.dimensions() == (self.width, self.height),
+            "{} doesn't have the size of the first frame",
+            path.display()
+        );
+        Ok(image.into_raw())
+    }
+}
+
+/// The number in the name of a frame, e.g. 12 for `frame-012.png`, so `frame-2.png` comes before
+/// `frame-10.png`.
+fn frame_number(name: &str) -> Option<u64> {
+    let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+    let digits = stem.len() - stem.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    stem[stem.len() - digits..].parse().ok()
+}
+
+/// A video decoded by `ffmpeg`, which must be installed.
+struct Video {
+    path: PathBuf,
+    width: u32,
+    height: u32,
+    fps: f32,
+    /// Number of frames, known once the end of the video is reached.
+    frame_count: Option<u64>,
+    process: Option<(Child, ChildStdout)>,
+    /// Index of the next frame read from the process.
+    position: u64,
+}
+
+impl Video {
+    fn open(path: &Path) -> anyhow::Result<Self> {
+        let output = Command::new("ffprobe")
+            .args(["-v", "error", "-select_streams", "v:0"])
+            .args(["-show_entries", "stream=width,height,r_frame_rate"])
+            .args(["-of", "csv=p=0"])
+            .arg(path)
+            .output()
+            .context("Can't run ffprobe, is ffmpeg installed?")?;
+        anyhow::ensure!(
+            output.status.success(),
+            "ffprobe can't read {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        let (width, height, fps) = parse_probe(&String::from_utf8_lossy(&output.stdout))
+            .with_context(|| format!("Can't read the video stream of {}", path.display()))?;
+
+        Ok(Self {
+            path: path.to_owned(),
+            width,
+            height,
+            fps,
+            frame_count: None,
+            process: None,
+            position: 0,
+        })
+    }
+
+    /// Start decoding from the frame `index`.
+    fn seek(&mut self, index: u64) -> anyhow::Result<()> {
+        if let Some((mut child, _)) = self.process.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+
+        let mut child = Command::new("ffmpeg")
+            .args(["-v", "error", "-ss"])
+            .arg(format!("{}", index as f64 / f64::from(self.fps)))
+            .arg("-i")
+            .arg(&self.path)
+            .args(["-f", "rawvideo", "-pix_fmt", "rgba", "-"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()
+            .context("Can't run ffmpeg, is it installed?")?;
+        let stdout = child.stdout.take().context("ffmpeg has no output")?;
+        self.process = Some((child, stdout));
+        self.position = index;
+        Ok(())
+    }
+}
+
+impl Decoder for Video {
+    fn frame(&mut self, index: u64) -> anyhow::Result<Vec<u8>> {
+        let mut index = self.frame_count.map_or(index, |count| index % count);
+        if self.process.is_none() || needs_seek(self.position, index, self.fps) {
+            self.seek(index)?;
+        }
+
+        let mut texels = vec![0; (self.width * self.height * BYTES_PER_PIXEL) as usize];
+        loop {
+            let (_, stdout) = self.process.as_mut().context("ffmpeg isn't running")?;
+            match stdout.read_exact(&mut texels) {
+                Ok(()) => self.position += 1,
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                    // Loop back to the start now that the length is known.
+                    anyhow::ensure!(self.position > 0, "{} has no frame", self.path.display());
+                    self.frame_count = Some(self.position);
+                    index %= self.position;
+                    self.seek(index)?;
+                    continue;
+                }
+                Err(e) => return Err(e).context("Can't read the output of ffmpeg"),
+            }
+            if self.position > index {
+                return Ok(texels);
+            }
+        }
+    }
+}
+
+impl Drop for Video {
+    fn drop(&mut self) {
+        if let Some((mut child, _)) = self.process.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Whether the decoder must restart at the frame `index` rather than read up to it from the
+/// frame at `position`: going back, or jumping more than a second ahead.
+fn needs_seek(position: u64, index: u64, fps: f32) -> bool {
+    index < position || index - position > fps.ceil() as u64
+}
+
+/// Parse the `width,height,num/den` line printed by ffprobe.
+fn parse_probe(output: &str) -> anyhow::Result<(u32, u32, f32)> {
+    let line = output.lines().next().context("No video stream")?;
+    let mut fields = line.trim().split(',');
+    let mut next = || fields.next().context("Missing field");
+    let width = next()?.parse()?;
+    let height = next()?.parse()?;
+    let fps = match next()?.split_once('/') {
+        Some((num, den)) => num.parse::<f32>()? / den.parse::<f32>()?,
+        None => anyhow::bail!("Invalid frame rate"),
+    };
+    anyhow::ensure!(fps.is_finite() && fps > 0., "Invalid frame rate");
+    Ok((width, height, fps))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{frame_number, needs_seek, parse_probe};
+
+    #[test]
+    fn frame_numbers() {
+        assert_eq!(frame_number("frame-012.png"), Some(12));
+        assert_eq!(frame_number("7.png"), Some(7));
+        assert_eq!(frame_number("cover.png"), None);
+
+        let mut names = ["f10.png", "f9.png", "cover.png", "f1.png"];
+        names.sort_by_key(|name| (frame_number(name), *name));
+        assert_eq!(names, ["cover.png", "f1.png", "f9.png", "f10.png"]);
+    }
+
+    #[test]
+    fn probe() {
+        assert_eq!(parse_probe("1280,720,30/1\n").unwrap(), (1280, 720, 30.));
+        let (_, _, fps) = parse_probe("640,360,30000/1001").unwrap();
+        assert!((fps - 29.97).abs() < 0.01);
+        assert!(parse_probe("640,360,0/0").is_err());
+        assert!(parse_probe("").is_err());
+    }
+
+    #[test]
+    fn seek() {
+        assert!(!needs_seek(10, 10, 30.));
+        assert!(!needs_seek(10, 40, 30.));
+        assert!(needs_seek(10, 41, 30.));
+        assert!(needs_seek(10, 9, 30.));
+        assert!(needs_seek(0, 100, 29.97));
+    }
+}
@@ -0,0 +1,539 @@
+use std::{collections::VecDeque, fmt::Display, io::Write, sync::mpsc, time::Duration};
+
+use instant::Instant;
+
+/// Number of frames kept to compute the statistics.
+const WINDOW_SIZE: usize = 240;
+/// Interval between two reports in the log.
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A fixed size window of the latest samples.
+#[derive(Debug, Clone)]
+pub struct RollingWindow {
+    samples: VecDeque<Duration>,
+    capacity: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+    pub min: Duration,
+    pub avg: Duration,
+    pub p99: Duration,
+}
+
+impl RollingWindow {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0);
+
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, sample: Duration) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    pub fn summary(&self) -> Option<Summary> {
+        Summary::of(self.samples.iter().copied().collect())
+    }
+}
+
+impl Summary {
+    fn of(mut samples: Vec<Duration>) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+
+        samples.sort_unstable();
+
+        let total = samples.iter().sum::<Duration>();
+        // Nearest-rank percentile: `ceil(0.99 * len) - 1`.
+        let p99_index = samples.len() - samples.len() / 100 - 1;
+
+        Some(Self {
+            min: samples[0],
+            avg: total / samples.len() as u32,
+            p99: samples[p99_index],
+        })
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "min/avg/p99 {:.2}/{:.2}/{:.2} ms",
+            as_millis(self.min),
+            as_millis(self.avg),
+            as_millis(self.p99)
+        )
+    }
+}
+
+/// Frame interval, CPU time and GPU pass duration of the latest frames.
+///
+/// They are only shown in the title bar of the window and exported to CSV,
+/// there is no overlay drawn over the toy.
+#[derive(Debug, Clone)]
+pub struct FrameStats {
+    records: VecDeque<FrameRecord>,
+    /// Number of frames recorded since the start.
+    frames: u64,
+    /// The frame whose GPU time is being measured.
+    gpu_frame: Option<u64>,
+    /// The start of the previous frame.
+    last_frame: Option<Instant>,
+    last_report: Instant,
+}
+
+/// The timings of one frame, the GPU time is only measured on some frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameRecord {
+    /// Time since the start of the previous frame, including the wait for the vertical blank.
+    pub interval: Option<Duration>,
+    /// Time spent recording and submitting the frame.
+    pub cpu: Duration,
+    pub gpu: Option<Duration>,
+}
+
+impl FrameStats {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            records: VecDeque::with_capacity(WINDOW_SIZE),
+            frames: 0,
+            gpu_frame: None,
+            last_frame: None,
+            last_report: now,
+        }
+    }
+
+    /// Record a frame started at `start` and submitted at `submitted`.
+    pub fn frame(&mut self, start: Instant, submitted: Instant) {
+        if self.records.len() == WINDOW_SIZE {
+            self.records.pop_front();
+        }
+        self.records.push_back(FrameRecord {
+            interval: self.last_frame.replace(start).map(|last| start - last),
+            cpu: submitted - start,
+            gpu: None,
+        });
+        self.frames += 1;
+    }
+
+    /// The GPU time of the next recorded frame is being measured.
+    pub fn measure_gpu(&mut self) {
+        self.gpu_frame = Some(self.frames);
+    }
+
+    /// Record the GPU time of the frame passed to [`FrameStats::measure_gpu`],
+    /// dropped if the frame is no longer in the window.
+    pub fn gpu(&mut self, duration: Duration) {
+        let Some(frame) = self.gpu_frame.take() else {
+            return;
+        };
+        let first = self.frames - self.records.len() as u64;
+        if let Some(record) = frame
+            .checked_sub(first)
+            .and_then(|index| self.records.get_mut(index as usize))
+        {
+            record.gpu = Some(duration);
+        }
+    }
+
+    pub fn interval_summary(&self) -> Option<Summary> {
+        Summary::of(
+            self.records
+                .iter()
+                .filter_map(|record| record.interval)
+                .collect(),
+        )
+    }
+
+    pub fn cpu_summary(&self) -> Option<Summary> {
+        Summary::of(self.records.iter().map(|record| record.cpu).collect())
+    }
+
+    pub fn gpu_summary(&self) -> Option<Summary> {
+        Summary::of(
+            self.records
+                .iter()
+                .filter_map(|record| record.gpu)
+                .collect(),
+        )
+    }
+
+    /// Return `true` once every [`REPORT_INTERVAL`].
+    pub fn should_report(&mut self, now: Instant) -> bool {
+        if now - self.last_report >= REPORT_INTERVAL {
+            self.last_report = now;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn write_csv(&self, mut writer: impl Write) -> std::io::Result<()> {
+        writeln!(writer, "frame,interval_ms,cpu_ms,gpu_ms")?;
+        for (i, record) in self.records.iter().enumerate() {
+            writeln!(
+                writer,
+                "{i},{},{},{}",
+                record
+                    .interval
+                    .map(|interval| as_millis(interval).to_string())
+                    .unwrap_or_default(),
+                as_millis(record.cpu),
+                record
+                    .gpu
+                    .map(|gpu| as_millis(gpu).to_string())
+                    .unwrap_or_default()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for FrameStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.interval_summary() {
+            Some(summary) => write!(f, "interval {summary}")?,
+            None => f.write_str("interval n/a")?,
+        }
+        match self.cpu_summary() {
+            Some(summary) => write!(f, ", cpu {summary}")?,
+            None => f.write_str(", cpu n/a")?,
+        }
+        match self.gpu_summary() {
+            Some(summary) => write!(f, ", gpu {summary}"),
+            None => f.write_str(", gpu n/a"),
+        }
+    }
+}
+
+/// Time elapsed since the start, without the time spent paused and scaled by the speed.
+#[derive(Debug, Clone, Copy)]
+pub struct Clock {
+    /// The time elapsed until `last`.
+    elapsed: Duration,
+    last: Instant,
+    speed: f64,
+    paused: bool,
+}
+
+impl Clock {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            elapsed: Duration::ZERO,
+            last: now,
+            speed: 1.,
+            paused: false,
+        }
+    }
+
+    pub fn elapsed(&self, now: Instant) -> Duration {
+        if self.paused {
+            self.elapsed
+        } else {
+            self.elapsed + (now - self.last).mul_f64(self.speed)
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self, now: Instant) {
+        self.advance(now);
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self, now: Instant) {
+        self.advance(now);
+        self.paused = false;
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    /// Change how fast the time pass, negative speeds are clamped to 0.
+    pub fn set_speed(&mut self, speed: f64, now: Instant) {
+        self.advance(now);
+        self.speed = speed.max(0.);
+    }
+
+    fn advance(&mut self, now: Instant) {
+        self.elapsed = self.elapsed(now);
+        self.last = now;
+    }
+}
+
+fn as_millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.
+}
+
+/// Measure how long the GPU take to execute the render passes.
+///
+/// Use timestamp queries when the device support them,
+/// otherwise measure on the CPU the time until the submitted work is done.
+pub struct GpuTimer {
+    timestamps: Option<Timestamps>,
+    pending: Option<Pending>,
+    recording: bool,
+}
+
+struct Timestamps {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    period: f32,
+}
+
+enum Pending {
+    Timestamps(mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>),
+    Submit(mpsc::Receiver<Duration>),
+}
+
+impl GpuTimer {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let timestamps = device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+            .then(|| Timestamps::new(device, queue));
+
+        log::info!(
+            "Measure GPU time using {}",
+            if timestamps.is_some() {
+                "timestamp queries"
+            } else {
+                "the CPU"
+            }
+        );
+
+        Self {
+            timestamps,
+            pending: None,
+            recording: false,
+        }
+    }
+
+    /// Start measuring the commands recorded after this call.
+    /// Skip the measure if the previous one isn't yet available, returning `false`.
+    pub fn begin(&mut self, encoder: &mut wgpu::CommandEncoder) -> bool {
+        self.recording = self.pending.is_none();
+        if let (true, Some(timestamps)) = (self.recording, &self.timestamps) {
+            encoder.write_timestamp(&timestamps.query_set, 0);
+        }
+        self.recording
+    }
+
+    pub fn end(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if let (true, Some(timestamps)) = (self.recording, &self.timestamps) {
+            encoder.write_timestamp(&timestamps.query_set, 1);
+            encoder.resolve_query_set(&timestamps.query_set, 0..2, &timestamps.resolve_buffer, 0);
+            encoder.copy_buffer_to_buffer(
+                &timestamps.resolve_buffer,
+                0,
+                &timestamps.readback_buffer,
+                0,
+                timestamps.resolve_buffer.size(),
+            );
+        }
+    }
+
+    /// Must be called right after the encoder passed to [`GpuTimer::end`] was submitted.
+    pub fn submitted(&mut self, queue: &wgpu::Queue) {
+        if !std::mem::take(&mut self.recording) {
+            return;
+        }
+
+        self.pending = Some(match &self.timestamps {
+            Some(timestamps) => {
+                let (tx, rx) = mpsc::channel();
+                timestamps
+                    .readback_buffer
+                    .slice(..)
+                    .map_async(wgpu::MapMode::Read, move |res| {
+                        let _ = tx.send(res);
+                    });
+                Pending::Timestamps(rx)
+            }
+            None => {
+                let (tx, rx) = mpsc::channel();
+                let submitted_at = Instant::now();
+                queue.on_submitted_work_done(move || {
+                    let _ = tx.send(submitted_at.elapsed());
+                });
+                Pending::Submit(rx)
+            }
+        });
+    }
+
+    /// Return the latest measure if available.
+    /// The device need to be polled for the measure to be collected.
+    pub fn poll(&mut self) -> Option<Duration> {
+        let duration = match self.pending.as_ref()? {
+            Pending::Timestamps(rx) => match rx.try_recv() {
+                Ok(res) => res.ok().and(self.timestamps.as_ref()).map(Timestamps::read),
+                Err(mpsc::TryRecvError::Empty) => return None,
+                Err(mpsc::TryRecvError::Disconnected) => None,
+            },
+            Pending::Submit(rx) => match rx.try_recv() {
+                Ok(duration) => Some(duration),
+                Err(mpsc::TryRecvError::Empty) => return None,
+                Err(mpsc::TryRecvError::Disconnected) => None,
+            },
+        };
+        self.pending = None;
+        duration
+    }
+}
+
+impl Timestamps {
+    fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let size = 2 * u64::from(wgpu::QUERY_SIZE);
+
+        Self {
+            query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("timestamp-query-set"),
+                ty: wgpu::QueryType::Timestamp,
+                count: 2,
+            }),
+            resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("timestamp-resolve-buffer"),
+                size,
+                usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            readback_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("timestamp-readback-buffer"),
+                size,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            period: queue.get_timestamp_period(),
+        }
+    }
+
+    /// Read the mapped readback buffer then unmap it.
+    fn read(&self) -> Duration {
+        let ticks = {
+            let view = self.readback_buffer.slice(..).get_mapped_range();
+            let timestamps: &[u64] = bytemuck::cast_slice(&view);
+            timestamps[1].wrapping_sub(timestamps[0])
+        };
+        self.readback_buffer.unmap();
+
+        Duration::from_nanos((ticks as f64 * f64::from(self.period)) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use instant::Instant;
+
+    use super::{Clock, FrameStats, RollingWindow, Summary};
+
+    fn ms(value: u64) -> Duration {
+        Duration::from_millis(value)
+    }
+
+    #[test]
+    fn rolling_window_summary() {
+        let mut window = RollingWindow::new(100);
+
+        assert_eq!(window.summary(), None);
+
+        for i in 1..=100 {
+            window.push(ms(i));
+        }
+
+        assert_eq!(
+            window.summary(),
+            Some(Summary {
+                min: ms(1),
+                avg: Duration::from_micros(50_500),
+                p99: ms(99),
+            })
+        );
+
+        // The oldest samples are dropped once the window is full.
+        window.push(ms(200));
+        window.push(ms(200));
+
+        let summary = window.summary().unwrap();
+        assert_eq!(summary.min, ms(3));
+        assert_eq!(summary.p99, ms(200));
+    }
+
+    #[test]
+    fn p99_of_few_samples() {
+        let mut window = RollingWindow::new(10);
+
+        window.push(ms(1));
+        window.push(ms(3));
+
+        assert_eq!(window.summary().unwrap().p99, ms(3));
+    }
+
+    #[test]
+    fn frame_stats_csv() {
+        let start = Instant::now();
+        let mut stats = FrameStats::new(start);
+
+        stats.frame(start, start + ms(2));
+        stats.frame(start + ms(10), start + ms(11));
+        stats.measure_gpu();
+        stats.frame(start + ms(30), start + ms(33));
+        // The GPU time arrives a frame later, for the frame it measured.
+        stats.gpu(ms(4));
+        // Without a frame being measured, the GPU time is dropped.
+        stats.gpu(ms(8));
+
+        let mut csv = Vec::new();
+        stats.write_csv(&mut csv).unwrap();
+
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "frame,interval_ms,cpu_ms,gpu_ms\n0,,2,\n1,10,1,\n2,20,3,4\n"
+        );
+        assert_eq!(stats.interval_summary().unwrap().min, ms(10));
+        assert_eq!(stats.gpu_summary().unwrap().p99, ms(4));
+    }
+
+    #[test]
+    fn pause_clock() {
+        let start = Instant::now();
+        let mut clock = Clock::new(start);
+
+        clock.pause(start + ms(10));
+        // Pausing twice keep the first pause.
+        clock.pause(start + ms(20));
+        assert!(clock.is_paused());
+        assert_eq!(clock.elapsed(start + ms(50)), ms(10));
+
+        clock.resume(start + ms(50));
+        assert!(!clock.is_paused());
+        assert_eq!(clock.elapsed(start + ms(60)), ms(20));
+    }
+
+    #[test]
+    fn clock_speed() {
+        let start = Instant::now();
+        let mut clock = Clock::new(start);
+
+        clock.set_speed(2., start + ms(10));
+        assert_eq!(clock.elapsed(start + ms(20)), ms(30));
+
+        clock.set_speed(-1., start + ms(20));
+        assert_eq!(clock.speed(), 0.);
+        assert_eq!(clock.elapsed(start + ms(100)), ms(30));
+    }
+}
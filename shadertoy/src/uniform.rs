@@ -1,11 +1,46 @@
 use wgpu::util::DeviceExt;
 
 #[repr(C, packed)]
-#[derive(Debug, Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Uniform {
     pub resolution: [f32; 2],
     pub time: f32,
-    pad: u32,
+    /// Scale and offset applied to the fragment coordinate before calling the toy,
+    /// used to render a frame bigger than the render target in several tiles.
+    pub scale: f32,
+    pub offset: [f32; 2],
+    /// Zoom of the view, see [`crate::view::View`].
+    pub view_zoom: f32,
+    /// Always `1.`, see `df64_one` in the prefix.
+    one: f32,
+    /// Center of the view as double-floats: `[x_hi, y_hi, x_lo, y_lo]`.
+    pub view_center: [f32; 4],
+    /// Rotation of the view, in radians.
+    pub view_angle: f32,
+    /// Vertices drawn by a vertex toy, see [`crate::pipeline::Geometry`].
+    pub vertex_count: f32,
+    /// Size of the render target, smaller than the resolution divided by the scale for the tiles.
+    pub target_resolution: [f32; 2],
+    /// Custom parameters of the toys, set with OSC.
+    pub params: [f32; 8],
+}
+
+impl Default for Uniform {
+    fn default() -> Self {
+        Self {
+            resolution: [0.; 2],
+            time: 0.,
+            scale: 1.,
+            offset: [0.; 2],
+            view_zoom: 1.,
+            one: 1.,
+            view_center: [0.; 4],
+            view_angle: 0.,
+            vertex_count: 3.,
+            target_resolution: [0.; 2],
+            params: [0.; 8],
+        }
+    }
 }
 
 impl AsRef<[u8]> for Uniform {
@@ -24,17 +59,11 @@ pub fn setup_uniform(
 ) {
     let uniform = Uniform::default();
 
-    let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: Some("uniform-buffer"),
-        contents: bytemuck::cast_slice(&[uniform]),
-        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-    });
-
     let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
         label: Some("uniform-bind-group-layout"),
         entries: &[wgpu::BindGroupLayoutEntry {
             binding: 0,
-            visibility: wgpu::ShaderStages::FRAGMENT,
+            visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
             ty: wgpu::BindingType::Buffer {
                 ty: wgpu::BufferBindingType::Uniform,
                 has_dynamic_offset: false,
@@ -44,14 +73,30 @@ pub fn setup_uniform(
         }],
     });
 
+    let (buffer, bind_group) = create_uniform_buffer(device, &bind_group_layout);
+
+    (uniform, buffer, bind_group_layout, bind_group)
+}
+
+/// Another uniform buffer for the toys, e.g. for a layer with its own uniforms.
+pub fn create_uniform_buffer(
+    device: &wgpu::Device,
+    bind_group_layout: &wgpu::BindGroupLayout,
+) -> (wgpu::Buffer, wgpu::BindGroup) {
+    let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("uniform-buffer"),
+        contents: Uniform::default().as_ref(),
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    });
+
     let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
         label: Some("uniform-bind-group"),
-        layout: &bind_group_layout,
+        layout: bind_group_layout,
         entries: &[wgpu::BindGroupEntry {
             binding: 0,
             resource: buffer.as_entire_binding(),
         }],
     });
 
-    (uniform, buffer, bind_group_layout, bind_group)
+    (buffer, bind_group)
 }
@@ -0,0 +1,160 @@
+use crate::uniform::Uniform;
+
+/// Pan, zoom and rotation applied by the `uv_ratioed` and `uv_centered` helpers of the prefix,
+/// so every toy using them can be explored.
+///
+/// The view is kept in `f64` and sent to the GPU as double-floats, so the toys using
+/// `uv_ratioed_df64` can zoom beyond the `f32` precision, until the view is about 1e-11 wide.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct View {
+    /// Center of the window, in the coordinates returned by `uv_ratioed`.
+    pub center: [f64; 2],
+    pub zoom: f64,
+    /// Rotation around the center of the window, in radians.
+    pub angle: f64,
+}
+
+impl Default for View {
+    fn default() -> Self {
+        Self {
+            center: [0.; 2],
+            zoom: 1.,
+            angle: 0.,
+        }
+    }
+}
+
+impl View {
+    /// Move the view so the content follow a cursor moved by `delta` pixels.
+    pub fn pan(&mut self, delta: [f64; 2], resolution: [f32; 2]) {
+        let scale = 2. / f64::from(resolution[1]) / self.zoom;
+        let [x, y] = self.rotated([delta[0] * scale, delta[1] * scale]);
+        self.center[0] -= x;
+        self.center[1] -= y;
+    }
+
+    /// Multiply the zoom by `factor`, keeping the point under the cursor in place.
+    pub fn zoom_at(&mut self, factor: f64, cursor: [f64; 2], resolution: [f32; 2]) {
+        let uv = uv_ratioed(cursor, resolution);
+        let point = self.position(uv);
+
+        self.zoom *= factor;
+        let [x, y] = self.rotated(uv);
+        self.center = [point[0] - x / self.zoom, point[1] - y / self.zoom];
+    }
+
+    /// Rotate the view around the center of the window.
+    pub fn rotate(&mut self, angle: f64) {
+        self.angle = (self.angle + angle).rem_euclid(std::f64::consts::TAU);
+    }
+
+    pub fn update_uniform(&self, uniform: &mut Uniform) {
+        let [x_hi, x_lo] = split(self.center[0]);
+        let [y_hi, y_lo] = split(self.center[1]);
+
+        uniform.view_zoom = self.zoom as f32;
+        uniform.view_angle = self.angle as f32;
+        uniform.view_center = [x_hi, y_hi, x_lo, y_lo];
+    }
+
+    /// Same as `view_transform` in the prefix.
+    fn position(&self, uv: [f64; 2]) -> [f64; 2] {
+        let [x, y] = self.rotated(uv);
+        [
+            self.center[0] + x / self.zoom,
+            self.center[1] + y / self.zoom,
+        ]
+    }
+
+    fn rotated(&self, [x, y]: [f64; 2]) -> [f64; 2] {
+        let (sin, cos) = self.angle.sin_cos();
+        [x * cos - y * sin, x * sin + y * cos]
+    }
+}
+
+/// Same as `screen_uv_ratioed` in the prefix.
+fn uv_ratioed(coord: [f64; 2], resolution: [f32; 2]) -> [f64; 2] {
+    let [width, height] = resolution.map(f64::from);
+    [
+        (coord[0] * 2. - width) / height,
+        (coord[1] * 2. - height) / height,
+    ]
+}
+
+/// Split a `f64` in a double-float, the unevaluated sum of two `f32`.
+fn split(value: f64) -> [f32; 2] {
+    let hi = value as f32;
+    let lo = (value - f64::from(hi)) as f32;
+    [hi, lo]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split, View};
+
+    const RESOLUTION: [f32; 2] = [200., 100.];
+
+    #[test]
+    fn split_double() {
+        let value = -0.743_643_887_037_151;
+        let [hi, lo] = split(value);
+
+        assert_ne!(f64::from(hi), value);
+        assert!((f64::from(hi) + f64::from(lo) - value).abs() < 1e-15);
+    }
+
+    #[test]
+    fn pan() {
+        let mut view = View::default();
+
+        // Half the window height is one unit at zoom 1.
+        view.pan([50., -50.], RESOLUTION);
+        assert_eq!(view.center, [-1., 1.]);
+
+        view.zoom = 4.;
+        view.pan([50., 0.], RESOLUTION);
+        assert_eq!(view.center, [-1.25, 1.]);
+    }
+
+    #[test]
+    fn zoom_keep_cursor_in_place() {
+        let mut view = View::default();
+        let cursor = [150., 25.];
+        let before = view.position(super::uv_ratioed(cursor, RESOLUTION));
+
+        view.zoom_at(1e12, cursor, RESOLUTION);
+
+        let after = view.position(super::uv_ratioed(cursor, RESOLUTION));
+        assert_eq!(view.zoom, 1e12);
+        assert!((after[0] - before[0]).abs() < 1e-15);
+        assert!((after[1] - before[1]).abs() < 1e-15);
+    }
+
+    #[test]
+    fn rotate() {
+        let mut view = View {
+            center: [0.5, 0.],
+            ..View::default()
+        };
+        let cursor = [150., 25.];
+        let uv = super::uv_ratioed(cursor, RESOLUTION);
+
+        view.rotate(std::f64::consts::FRAC_PI_2);
+        // The center of the window doesn't move.
+        assert_eq!(view.position([0., 0.]), [0.5, 0.]);
+        let position = view.position(uv);
+        assert!((position[0] - 1.).abs() < 1e-15);
+        assert!((position[1] - 1.).abs() < 1e-15);
+
+        // Dragging right move the content right, whatever the rotation.
+        view.pan([50., 0.], RESOLUTION);
+        assert!((view.center[0] - 0.5).abs() < 1e-15);
+        assert!((view.center[1] + 1.).abs() < 1e-15);
+
+        view.zoom_at(8., cursor, RESOLUTION);
+        let after = view.position(uv);
+        let before = [position[0], position[1] - 1.];
+        assert!((after[0] - before[0]).abs() < 1e-15);
+        assert!((after[1] - before[1]).abs() < 1e-15);
+    }
+}
@@ -0,0 +1,100 @@
+use anyhow::Context;
+use clap::Parser;
+use winit::{dpi::LogicalSize, platform::web::WindowExtWebSys, window::Window};
+
+use crate::cli::Options;
+
+/// Id of the element the canvas is appended to, the canvas fill it.
+const CONTAINER_ID: &str = "shadertoy";
+
+/// Read the options from the query string of the page, e.g. `?toy=ring&max-fps=30`.
+pub fn options_from_url() -> Options {
+    let query = web_sys::window()
+        .and_then(|window| window.location().search().ok())
+        .unwrap_or_default();
+
+    options_from_query(&query)
+}
+
+/// Each `key=value` pair of the query is parsed like the `--key value` command line argument.
+fn options_from_query(query: &str) -> Options {
+    let args = query
+        .trim_start_matches('?')
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .flat_map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            [format!("--{}", decode(key)), decode(value)]
+        });
+
+    Options::try_parse_from(std::iter::once("shadertoy".to_string()).chain(args)).unwrap_or_else(
+        |e| {
+            log::error!("Invalid query `{query}`, using the default options: {e}");
+            Options::parse_from(["shadertoy"])
+        },
+    )
+}
+
+fn decode(component: &str) -> String {
+    let component = component.replace('+', " ");
+    js_sys::decode_uri_component(&component)
+        .ok()
+        .and_then(|decoded| decoded.as_string())
+        .unwrap_or(component)
+}
+
+/// Append the canvas of the window to the container element, or to the body if it's missing.
+pub fn attach_canvas(window: &Window) -> anyhow::Result<()> {
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .context("Can't access the document")?;
+    let container = document
+        .get_element_by_id(CONTAINER_ID)
+        .or_else(|| document.body().map(web_sys::Element::from))
+        .context("Can't find an element to attach the canvas to")?;
+
+    container
+        .append_child(&window.canvas())
+        .ok()
+        .context("Can't append the canvas")?;
+
+    fit_to_container(window);
+    Ok(())
+}
+
+/// Resize the window to the size of its container, when the container was resized.
+pub fn fit_to_container(window: &Window) {
+    let Some(container) = window.canvas().parent_element() else {
+        return;
+    };
+
+    let size = LogicalSize::new(container.client_width(), container.client_height());
+    let current = window.inner_size().to_logical::<i32>(window.scale_factor());
+
+    if size.width > 0 && size.height > 0 && size != current {
+        window.set_inner_size(size);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::options_from_query;
+
+    #[wasm_bindgen_test]
+    fn toy_from_query() {
+        let options = options_from_query("?toy=multi%20ring&max-fps=30");
+
+        assert_eq!(options.toy.as_deref(), Some("multi ring"));
+        assert_eq!(options.max_fps, Some(30.));
+    }
+
+    #[wasm_bindgen_test]
+    fn invalid_query() {
+        let options = options_from_query("?unknown=1");
+
+        assert_eq!(options.toy, None);
+        assert_eq!(options.max_fps, None);
+    }
+}
@@ -0,0 +1,68 @@
+/// The present modes tried for a requested one, from the best to the most supported.
+fn candidates(requested: wgpu::PresentMode) -> &'static [wgpu::PresentMode] {
+    use wgpu::PresentMode as Mode;
+
+    match requested {
+        Mode::Immediate => &[Mode::Immediate, Mode::Mailbox, Mode::Fifo],
+        Mode::Mailbox => &[Mode::Mailbox, Mode::Fifo],
+        _ => &[Mode::Fifo],
+    }
+}
+
+/// Select the requested present mode if the surface support it,
+/// otherwise fallback to the closest one available.
+pub fn select(requested: wgpu::PresentMode, supported: &[wgpu::PresentMode]) -> wgpu::PresentMode {
+    let candidates = candidates(requested);
+
+    let present_mode = candidates
+        .iter()
+        .find(|mode| supported.contains(mode))
+        .copied()
+        // `Fifo` is required to be supported, but better safe than sorry.
+        .or_else(|| supported.first().copied())
+        .unwrap_or(wgpu::PresentMode::Fifo);
+
+    if present_mode != candidates[0] {
+        log::warn!("Present mode {requested:?} isn't supported, fallback to {present_mode:?}");
+    }
+    present_mode
+}
+
+/// Parse the name of a present mode, as written on the command line.
+pub fn parse(name: &str) -> Option<wgpu::PresentMode> {
+    match name.to_ascii_lowercase().as_str() {
+        "fifo" => Some(wgpu::PresentMode::Fifo),
+        "mailbox" => Some(wgpu::PresentMode::Mailbox),
+        "immediate" => Some(wgpu::PresentMode::Immediate),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wgpu::PresentMode as Mode;
+
+    use super::{parse, select};
+
+    #[test]
+    fn select_present_mode() {
+        let all = [Mode::Fifo, Mode::Mailbox, Mode::Immediate];
+
+        assert_eq!(select(Mode::Immediate, &all), Mode::Immediate);
+        assert_eq!(
+            select(Mode::Immediate, &[Mode::Fifo, Mode::Mailbox]),
+            Mode::Mailbox
+        );
+        assert_eq!(
+            select(Mode::Mailbox, &[Mode::Fifo, Mode::Immediate]),
+            Mode::Fifo
+        );
+        assert_eq!(select(Mode::AutoVsync, &all), Mode::Fifo);
+    }
+
+    #[test]
+    fn parse_present_mode() {
+        assert_eq!(parse("Mailbox"), Some(Mode::Mailbox));
+        assert_eq!(parse("vsync"), None);
+    }
+}
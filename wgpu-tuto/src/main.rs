@@ -1,5 +1,3 @@
-use wgpu_shaders;
-
 fn main() {
     tokio::runtime::Runtime::new()
         .expect("Can't create tokio async runtime")
@@ -112,7 +112,7 @@ impl State {
             format: surface_format,
             width: size.width,
             height: size.height,
-            present_mode: surface_caps.present_modes[0],
+            present_mode: select_present_mode(&surface_caps.present_modes),
             alpha_mode: surface_caps.alpha_modes[0],
             view_formats: vec![],
         };
@@ -415,3 +415,17 @@ impl State {
         Ok(())
     }
 }
+
+/// Select the present mode requested with the `WGPU_PRESENT_MODE` environment variable
+/// (`fifo`, `mailbox` or `immediate`), with the same fallback as the shadertoy viewer.
+fn select_present_mode(supported: &[wgpu::PresentMode]) -> wgpu::PresentMode {
+    let requested = match std::env::var("WGPU_PRESENT_MODE") {
+        Ok(name) => wgpu_present::parse(&name).unwrap_or_else(|| {
+            log::warn!("Unknown present mode `{name}`, using `fifo`");
+            wgpu::PresentMode::Fifo
+        }),
+        Err(_) => wgpu::PresentMode::Fifo,
+    };
+
+    wgpu_present::select(requested, supported)
+}